@@ -0,0 +1,231 @@
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+// https://www.nesdev.org/wiki/APU_DMC, periods in CPU cycles
+const NTSC_RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_RATE_TABLE: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+const SAMPLE_ADDRESS_START: u16 = 0xC000;
+
+/// # Delta modulation channel ($4010-$4013)
+///
+/// $4010  IL-- RRRR  IRQ enable, loop flag, rate index
+/// $4011  -DDD DDDD  Direct load of the output level
+/// $4012  AAAA AAAA  Sample address = $C000 + A * 64
+/// $4013  LLLL LLLL  Sample length = L * 16 + 1 bytes
+///
+/// The sample bytes are fetched from CPU memory, which the channel can't reach by itself. The
+/// bus polls `pending_read` and answers with `fill_sample_buffer`.
+#[derive(Copy, Clone, Debug)]
+pub struct Dmc {
+    irq_enabled: bool,
+    loop_flag: bool,
+    rate_index: u8,
+    timer_period: u16,
+    timer: u16,
+    output_level: u8,
+
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+
+    pub irq_flag: bool,
+    rate_table: &'static [u16; 16],
+}
+
+impl Dmc {
+    pub fn new() -> Self {
+        Dmc {
+            irq_enabled: false,
+            loop_flag: false,
+            rate_index: 0,
+            timer_period: NTSC_RATE_TABLE[0],
+            timer: 0,
+            output_level: 0,
+
+            sample_address: SAMPLE_ADDRESS_START,
+            sample_length: 1,
+            current_address: SAMPLE_ADDRESS_START,
+            bytes_remaining: 0,
+            sample_buffer: None,
+
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+
+            irq_flag: false,
+            rate_table: &NTSC_RATE_TABLE,
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.rate_table = match region {
+            Region::Pal => &PAL_RATE_TABLE,
+            Region::Ntsc | Region::Dendy => &NTSC_RATE_TABLE,
+        };
+        self.timer_period = self.rate_table[self.rate_index as usize];
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register & 0b11 {
+            0 => {
+                self.irq_enabled = data & 0b1000_0000 != 0;
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+                self.loop_flag = data & 0b0100_0000 != 0;
+                self.rate_index = data & 0b1111;
+                self.timer_period = self.rate_table[self.rate_index as usize];
+            }
+            1 => self.output_level = data & 0b0111_1111,
+            2 => self.sample_address = SAMPLE_ADDRESS_START + (data as u16) * 64,
+            3 => self.sample_length = (data as u16) * 16 + 1,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    pub fn pending_read(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    pub fn fill_sample_buffer(&mut self, data: u8) {
+        self.sample_buffer = Some(data);
+        self.current_address = match self.current_address {
+            0xFFFF => 0x8000,
+            addr => addr + 1,
+        };
+
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    /// Clocked on every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            self.clock_output_unit();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_output_unit(&mut self) {
+        if !self.silence {
+            if self.shift_register & 1 == 1 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+}
+
+impl Default for Dmc {
+    fn default() -> Self {
+        Dmc::new()
+    }
+}
+
+impl Snapshot for Dmc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.loop_flag);
+        state.write_u8(self.rate_index);
+        state.write_u16(self.timer);
+        state.write_u8(self.output_level);
+
+        state.write_u16(self.sample_address);
+        state.write_u16(self.sample_length);
+        state.write_u16(self.current_address);
+        state.write_u16(self.bytes_remaining);
+        state.write_bool(self.sample_buffer.is_some());
+        state.write_u8(self.sample_buffer.unwrap_or(0));
+
+        state.write_u8(self.shift_register);
+        state.write_u8(self.bits_remaining);
+        state.write_bool(self.silence);
+
+        state.write_bool(self.irq_flag);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.irq_enabled = state.read_bool()?;
+        self.loop_flag = state.read_bool()?;
+        self.rate_index = state.read_u8()? & 0b1111;
+        self.timer_period = self.rate_table[self.rate_index as usize];
+        self.timer = state.read_u16()?;
+        self.output_level = state.read_u8()?;
+
+        self.sample_address = state.read_u16()?;
+        self.sample_length = state.read_u16()?;
+        self.current_address = state.read_u16()?;
+        self.bytes_remaining = state.read_u16()?;
+        let sample_buffered = state.read_bool()?;
+        let sample_buffer = state.read_u8()?;
+        self.sample_buffer = sample_buffered.then_some(sample_buffer);
+
+        self.shift_register = state.read_u8()?;
+        self.bits_remaining = state.read_u8()?;
+        self.silence = state.read_bool()?;
+
+        self.irq_flag = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+pub mod dmc;
+pub mod noise;
+pub mod pulse;
+pub mod triangle;
@@ -0,0 +1,131 @@
+use crate::components::envelope::Envelope;
+use crate::components::length_counter::LengthCounter;
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+// https://www.nesdev.org/wiki/APU_Noise, periods in CPU cycles
+const NTSC_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+/// # Noise channel ($400C-$400F)
+///
+/// $400C  --LC VVVV  Length counter halt, envelope
+/// $400E  M--- PPPP  Mode flag, period index
+/// $400F  LLLL L---  Length counter load
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Noise {
+    envelope: Envelope,
+    mode: bool,
+    period_index: u8,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+    pub length_counter: LengthCounter,
+    period_table: &'static [u16; 16],
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            envelope: Envelope::new(),
+            mode: false,
+            period_index: 0,
+            timer_period: NTSC_PERIOD_TABLE[0],
+            timer: 0,
+            shift_register: 1,
+            length_counter: LengthCounter::new(),
+            period_table: &NTSC_PERIOD_TABLE,
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.period_table = match region {
+            Region::Pal => &PAL_PERIOD_TABLE,
+            Region::Ntsc | Region::Dendy => &NTSC_PERIOD_TABLE,
+        };
+        self.timer_period = self.period_table[self.period_index as usize];
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register & 0b11 {
+            0 => {
+                self.length_counter.set_halted(data & 0b0010_0000 != 0);
+                self.envelope.write(data);
+            }
+            1 => {
+                // Unused
+            }
+            2 => {
+                self.mode = data & 0b1000_0000 != 0;
+                self.period_index = data & 0b1111;
+                self.timer_period = self.period_table[self.period_index as usize];
+            }
+            3 => {
+                self.length_counter.load(data >> 3);
+                self.envelope.restart();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked on every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+
+            let other_bit = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift_register & 1) ^ ((self.shift_register >> other_bit) & 1);
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length_counter.is_active() || self.shift_register & 1 == 1 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise::new()
+    }
+}
+
+impl Snapshot for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.envelope.save_state(state);
+        state.write_bool(self.mode);
+        state.write_u8(self.period_index);
+        state.write_u16(self.timer);
+        state.write_u16(self.shift_register);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.envelope.load_state(state)?;
+        self.mode = state.read_bool()?;
+        self.period_index = state.read_u8()? & 0b1111;
+        self.timer_period = self.period_table[self.period_index as usize];
+        self.timer = state.read_u16()?;
+        self.shift_register = state.read_u16()?;
+        self.length_counter.load_state(state)
+    }
+}
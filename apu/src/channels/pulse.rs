@@ -0,0 +1,115 @@
+use crate::components::envelope::Envelope;
+use crate::components::length_counter::LengthCounter;
+use crate::components::sweep::Sweep;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+// https://www.nesdev.org/wiki/APU_Pulse
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+    [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+    [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+    [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+/// # Pulse channel ($4000-$4003 and $4004-$4007)
+///
+/// $4000  DDLC VVVV  Duty, length counter halt, envelope
+/// $4001  EPPP NSSS  Sweep unit
+/// $4002  TTTT TTTT  Timer low
+/// $4003  LLLL LTTT  Length counter load, timer high
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Pulse {
+    duty: u8,
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+    envelope: Envelope,
+    sweep: Sweep,
+    pub length_counter: LengthCounter,
+}
+
+impl Pulse {
+    pub fn new(ones_complement_sweep: bool) -> Self {
+        Pulse {
+            duty: 0,
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::new(),
+            sweep: Sweep::new(ones_complement_sweep),
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register & 0b11 {
+            0 => {
+                self.duty = data >> 6;
+                self.length_counter.set_halted(data & 0b0010_0000 != 0);
+                self.envelope.write(data);
+            }
+            1 => self.sweep.write(data),
+            2 => self.timer_period = (self.timer_period & 0xFF00) | data as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0b111) << 8);
+                self.length_counter.load(data >> 3);
+                self.sequence_step = 0;
+                self.envelope.restart();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked on every APU cycle (every second CPU cycle).
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = self.sequence_step.wrapping_sub(1) & 0b111;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+        self.sweep.clock(&mut self.timer_period);
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length_counter.is_active()
+            || DUTY_TABLE[self.duty as usize][self.sequence_step as usize] == 0
+            || self.sweep.is_muting(self.timer_period)
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Snapshot for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.duty);
+        state.write_u8(self.sequence_step);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        self.envelope.save_state(state);
+        self.sweep.save_state(state);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.duty = state.read_u8()?;
+        self.sequence_step = state.read_u8()?;
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.envelope.load_state(state)?;
+        self.sweep.load_state(state)?;
+        self.length_counter.load_state(state)
+    }
+}
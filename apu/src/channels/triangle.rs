@@ -0,0 +1,123 @@
+use crate::components::length_counter::LengthCounter;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+// https://www.nesdev.org/wiki/APU_Triangle
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// # Triangle channel ($4008-$400B)
+///
+/// $4008  CRRR RRRR  Length counter halt / linear counter control, linear counter reload value
+/// $400A  TTTT TTTT  Timer low
+/// $400B  LLLL LTTT  Length counter load, timer high
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    control_flag: bool,
+    linear_counter_reload_value: u8,
+    linear_counter: u8,
+    linear_counter_reload: bool,
+    timer_period: u16,
+    timer: u16,
+    sequence_step: u8,
+    pub length_counter: LengthCounter,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle {
+            control_flag: false,
+            linear_counter_reload_value: 0,
+            linear_counter: 0,
+            linear_counter_reload: false,
+            timer_period: 0,
+            timer: 0,
+            sequence_step: 0,
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register & 0b11 {
+            0 => {
+                self.control_flag = data & 0b1000_0000 != 0;
+                self.length_counter.set_halted(self.control_flag);
+                self.linear_counter_reload_value = data & 0b0111_1111;
+            }
+            1 => {
+                // Unused
+            }
+            2 => self.timer_period = (self.timer_period & 0xFF00) | data as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0b111) << 8);
+                self.length_counter.load(data >> 3);
+                self.linear_counter_reload = true;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked on every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length_counter.is_active() && self.linear_counter > 0 {
+                self.sequence_step = (self.sequence_step + 1) & 0b1_1111;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        if self.linear_counter_reload {
+            self.linear_counter = self.linear_counter_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control_flag {
+            self.linear_counter_reload = false;
+        }
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    pub fn output(&self) -> u8 {
+        TRIANGLE_SEQUENCE[self.sequence_step as usize]
+    }
+}
+
+impl Default for Triangle {
+    fn default() -> Self {
+        Triangle::new()
+    }
+}
+
+impl Snapshot for Triangle {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.control_flag);
+        state.write_u8(self.linear_counter_reload_value);
+        state.write_u8(self.linear_counter);
+        state.write_bool(self.linear_counter_reload);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u8(self.sequence_step);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.control_flag = state.read_bool()?;
+        self.linear_counter_reload_value = state.read_u8()?;
+        self.linear_counter = state.read_u8()?;
+        self.linear_counter_reload = state.read_bool()?;
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.sequence_step = state.read_u8()?;
+        self.length_counter.load_state(state)
+    }
+}
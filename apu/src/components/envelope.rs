@@ -0,0 +1,97 @@
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+/// # Envelope generator https://www.nesdev.org/wiki/APU_Envelope
+///
+/// 7  bit  0
+/// ---- ----
+/// --LC VVVV
+///   || ||||
+///   || ++++- Volume, or envelope divider period
+///   |+------ Constant volume flag (0: use decay level; 1: use volume)
+///   +------- Loop flag (shares the bit with the length counter halt)
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Envelope {
+    start: bool,
+    loop_flag: bool,
+    constant_volume: bool,
+    volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Envelope {
+            start: false,
+            loop_flag: false,
+            constant_volume: false,
+            volume: 0,
+            divider: 0,
+            decay: 0,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.loop_flag = data & 0b0010_0000 != 0;
+        self.constant_volume = data & 0b0001_0000 != 0;
+        self.volume = data & 0b0000_1111;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    /// Clocked by the frame counter on every quarter frame.
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.loop_flag {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope::new()
+    }
+}
+
+impl Snapshot for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.start);
+        state.write_bool(self.loop_flag);
+        state.write_bool(self.constant_volume);
+        state.write_u8(self.volume);
+        state.write_u8(self.divider);
+        state.write_u8(self.decay);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.start = state.read_bool()?;
+        self.loop_flag = state.read_bool()?;
+        self.constant_volume = state.read_bool()?;
+        self.volume = state.read_u8()?;
+        self.divider = state.read_u8()?;
+        self.decay = state.read_u8()?;
+        Ok(())
+    }
+}
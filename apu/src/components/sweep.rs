@@ -0,0 +1,97 @@
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+/// # Sweep unit https://www.nesdev.org/wiki/APU_Sweep
+///
+/// 7  bit  0
+/// ---- ----
+/// EPPP NSSS
+/// |||| ||||
+/// |||| |+++- Shift count (number of bits)
+/// |||| +---- Negate flag
+/// |+++------ Divider period is P + 1 half-frames
+/// +--------- Enabled flag
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Sweep {
+    enabled: bool,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    reload: bool,
+    divider: u8,
+
+    // Pulse 1 adds the ones' complement when negating, pulse 2 the two's complement.
+    ones_complement: bool,
+}
+
+impl Sweep {
+    pub fn new(ones_complement: bool) -> Self {
+        Sweep {
+            enabled: false,
+            period: 0,
+            negate: false,
+            shift: 0,
+            reload: false,
+            divider: 0,
+            ones_complement,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.enabled = data & 0b1000_0000 != 0;
+        self.period = (data >> 4) & 0b111;
+        self.negate = data & 0b0000_1000 != 0;
+        self.shift = data & 0b111;
+        self.reload = true;
+    }
+
+    pub fn target_period(&self, current: u16) -> u16 {
+        let change = current >> self.shift;
+        match (self.negate, self.ones_complement) {
+            (false, _) => current + change,
+            (true, true) => current.saturating_sub(change + 1),
+            (true, false) => current.saturating_sub(change),
+        }
+    }
+
+    /// The channel is silenced whenever the current period is too small or the target period
+    /// overflows, even if the sweep unit itself is disabled.
+    pub fn is_muting(&self, current: u16) -> bool {
+        current < 8 || self.target_period(current) > 0x7FF
+    }
+
+    /// Clocked by the frame counter on every half frame.
+    pub fn clock(&mut self, period: &mut u16) {
+        if self.divider == 0 && self.enabled && self.shift > 0 && !self.is_muting(*period) {
+            *period = self.target_period(*period);
+        }
+
+        if self.divider == 0 || self.reload {
+            self.divider = self.period;
+            self.reload = false;
+        } else {
+            self.divider -= 1;
+        }
+    }
+}
+
+impl Snapshot for Sweep {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.period);
+        state.write_bool(self.negate);
+        state.write_u8(self.shift);
+        state.write_bool(self.reload);
+        state.write_u8(self.divider);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.enabled = state.read_bool()?;
+        self.period = state.read_u8()?;
+        self.negate = state.read_bool()?;
+        self.shift = state.read_u8()?;
+        self.reload = state.read_bool()?;
+        self.divider = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+/// When the steps come, in CPU cycles since the sequence was reset.
+#[derive(Debug)]
+struct Sequence {
+    step_1: usize,
+    step_2: usize,
+    step_3: usize,
+    four_step_last: usize,
+    four_step_period: usize,
+    five_step_last: usize,
+    five_step_period: usize,
+}
+
+// https://www.nesdev.org/wiki/APU_Frame_Counter
+const NTSC_SEQUENCE: Sequence = Sequence {
+    step_1: 7457,
+    step_2: 14913,
+    step_3: 22371,
+    four_step_last: 29829,
+    four_step_period: 29830,
+    five_step_last: 37281,
+    five_step_period: 37282,
+};
+const PAL_SEQUENCE: Sequence = Sequence {
+    step_1: 8313,
+    step_2: 16627,
+    step_3: 24939,
+    four_step_last: 33253,
+    four_step_period: 33254,
+    five_step_last: 41565,
+    five_step_period: 41566,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameCounterMode {
+    FourStep,
+    FiveStep,
+}
+
+/// Which units the frame counter clocks on a given cycle. A half frame also clocks everything a
+/// quarter frame does.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameClock {
+    Quarter,
+    Half,
+}
+
+/// # Frame Counter ($4017)
+///
+/// 7  bit  0
+/// ---- ----
+/// MI-- ----
+/// ||
+/// |+-------- IRQ inhibit flag
+/// +--------- Sequencer mode: 0 selects 4-step sequence, 1 selects 5-step sequence
+///
+#[derive(Copy, Clone, Debug)]
+pub struct FrameCounter {
+    pub mode: FrameCounterMode,
+    irq_inhibit: bool,
+    cycle: usize,
+    pub irq_flag: bool,
+    sequence: &'static Sequence,
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        FrameCounter {
+            mode: FrameCounterMode::FourStep,
+            irq_inhibit: false,
+            cycle: 0,
+            irq_flag: false,
+            sequence: &NTSC_SEQUENCE,
+        }
+    }
+
+    /// The PAL sequence is slower, to keep 240 Hz on the slower CPU clock.
+    pub fn set_region(&mut self, region: Region) {
+        self.sequence = match region {
+            Region::Pal => &PAL_SEQUENCE,
+            Region::Ntsc | Region::Dendy => &NTSC_SEQUENCE,
+        };
+    }
+
+    /// Writing $4017 resets the sequencer, and selecting the 5-step mode clocks all units
+    /// immediately.
+    pub fn write(&mut self, data: u8) -> Option<FrameClock> {
+        self.mode = if data & 0b1000_0000 != 0 {
+            FrameCounterMode::FiveStep
+        } else {
+            FrameCounterMode::FourStep
+        };
+
+        self.irq_inhibit = data & 0b0100_0000 != 0;
+        if self.irq_inhibit {
+            self.irq_flag = false;
+        }
+
+        self.cycle = 0;
+        match self.mode {
+            FrameCounterMode::FourStep => None,
+            FrameCounterMode::FiveStep => Some(FrameClock::Half),
+        }
+    }
+
+    /// Clocked on every CPU cycle.
+    pub fn clock(&mut self) -> Option<FrameClock> {
+        self.cycle += 1;
+
+        let sequence = self.sequence;
+        let cycle = self.cycle;
+        match self.mode {
+            _ if cycle == sequence.step_1 || cycle == sequence.step_3 => Some(FrameClock::Quarter),
+            _ if cycle == sequence.step_2 => Some(FrameClock::Half),
+            FrameCounterMode::FourStep if cycle == sequence.four_step_last => {
+                if !self.irq_inhibit {
+                    self.irq_flag = true;
+                }
+                Some(FrameClock::Half)
+            }
+            FrameCounterMode::FiveStep if cycle == sequence.five_step_last => {
+                Some(FrameClock::Half)
+            }
+            FrameCounterMode::FourStep if cycle == sequence.four_step_period => {
+                self.cycle = 0;
+                None
+            }
+            FrameCounterMode::FiveStep if cycle == sequence.five_step_period => {
+                self.cycle = 0;
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for FrameCounter {
+    fn default() -> Self {
+        FrameCounter::new()
+    }
+}
+
+impl Snapshot for FrameCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.mode == FrameCounterMode::FiveStep);
+        state.write_bool(self.irq_inhibit);
+        state.write_usize(self.cycle);
+        state.write_bool(self.irq_flag);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.mode = if state.read_bool()? {
+            FrameCounterMode::FiveStep
+        } else {
+            FrameCounterMode::FourStep
+        };
+        self.irq_inhibit = state.read_bool()?;
+        self.cycle = state.read_usize()?;
+        self.irq_flag = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+use crate::channels::dmc::Dmc;
+use crate::channels::noise::Noise;
+use crate::channels::pulse::Pulse;
+use crate::channels::triangle::Triangle;
+use crate::frame_counter::{FrameClock, FrameCounter};
+use crate::mixer::Mixer;
+use crate::resampler::Resampler;
+use core::mem::Mem;
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+pub mod channels;
+mod components;
+pub mod frame_counter;
+pub mod mixer;
+pub mod resampler;
+pub mod wav;
+
+const PULSE_1_START: u16 = 0x4000;
+const PULSE_1_END: u16 = 0x4003;
+const PULSE_2_START: u16 = 0x4004;
+const PULSE_2_END: u16 = 0x4007;
+const TRIANGLE_START: u16 = 0x4008;
+const TRIANGLE_END: u16 = 0x400B;
+const NOISE_START: u16 = 0x400C;
+const NOISE_END: u16 = 0x400F;
+const DMC_START: u16 = 0x4010;
+const DMC_END: u16 = 0x4013;
+pub const APU_STATUS: u16 = 0x4015;
+pub const APU_FRAME_COUNTER: u16 = 0x4017;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+pub struct APU {
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: Dmc,
+    pub frame_counter: FrameCounter,
+
+    mixer: Mixer,
+    resampler: Resampler,
+    region: Region,
+
+    pub cycles: usize,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            frame_counter: FrameCounter::new(),
+
+            mixer: Mixer::new(),
+            resampler: Resampler::new(Region::Ntsc.cpu_clock_rate(), DEFAULT_SAMPLE_RATE),
+            region: Region::Ntsc,
+
+            cycles: 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.resampler.sample_rate()
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.resampler = Resampler::new(self.region.cpu_clock_rate(), sample_rate);
+    }
+
+    /// The frame counter, the noise and DMC periods and the CPU clock the samples are made from
+    /// differ on PAL consoles. NTSC to begin with.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.frame_counter.set_region(region);
+        self.noise.set_region(region);
+        self.dmc.set_region(region);
+        self.set_sample_rate(self.sample_rate());
+    }
+
+    /// Drains the PCM samples produced since the last call, mono in the range -1.0..=1.0.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.resampler.take_samples()
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.clock();
+        }
+    }
+
+    fn clock(&mut self) {
+        self.cycles += 1;
+
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.cycles.is_multiple_of(2) {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+
+        let frame_clock = self.frame_counter.clock();
+        self.clock_frame(frame_clock);
+
+        let sample = self.mixer.mix(
+            self.pulse1.output(),
+            self.pulse2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        );
+        self.resampler.push(sample);
+    }
+
+    fn clock_frame(&mut self, frame_clock: Option<FrameClock>) {
+        match frame_clock {
+            Some(FrameClock::Quarter) => self.clock_quarter_frame(),
+            Some(FrameClock::Half) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            None => {}
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.clock_quarter_frame();
+        self.pulse2.clock_quarter_frame();
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_half_frame();
+        self.pulse2.clock_half_frame();
+        self.triangle.clock_half_frame();
+        self.noise.clock_half_frame();
+    }
+
+    /// True while the frame counter or the DMC is asserting the CPU IRQ line.
+    pub fn irq(&self) -> bool {
+        self.frame_counter.irq_flag || self.dmc.irq_flag
+    }
+
+    /// The address the DMC wants the bus to read its next sample byte from, if any.
+    pub fn poll_dmc_read(&self) -> Option<u16> {
+        self.dmc.pending_read()
+    }
+
+    pub fn fill_dmc_sample(&mut self, data: u8) {
+        self.dmc.fill_sample_buffer(data);
+    }
+
+    /// # Status ($4015 read)
+    ///
+    /// 7  bit  0
+    /// ---- ----
+    /// IF-D NT21
+    /// || | ||||
+    /// || | |||+- Pulse 1 length counter > 0
+    /// || | ||+-- Pulse 2 length counter > 0
+    /// || | |+--- Triangle length counter > 0
+    /// || | +---- Noise length counter > 0
+    /// || +------ DMC bytes remaining > 0
+    /// |+-------- Frame interrupt (cleared by the read)
+    /// +--------- DMC interrupt
+    ///
+    fn read_status(&mut self) -> u8 {
+        let mut data = 0;
+        data |= self.pulse1.length_counter.is_active() as u8;
+        data |= (self.pulse2.length_counter.is_active() as u8) << 1;
+        data |= (self.triangle.length_counter.is_active() as u8) << 2;
+        data |= (self.noise.length_counter.is_active() as u8) << 3;
+        data |= (self.dmc.is_active() as u8) << 4;
+        data |= (self.frame_counter.irq_flag as u8) << 6;
+        data |= (self.dmc.irq_flag as u8) << 7;
+
+        self.frame_counter.irq_flag = false;
+        data
+    }
+
+    /// # Control ($4015 write)
+    ///
+    /// 7  bit  0
+    /// ---- ----
+    /// ---D NT21  Enable DMC, noise, triangle, pulse 2 and pulse 1
+    ///
+    fn write_control(&mut self, data: u8) {
+        self.pulse1
+            .length_counter
+            .set_enabled(data & 0b0000_0001 != 0);
+        self.pulse2
+            .length_counter
+            .set_enabled(data & 0b0000_0010 != 0);
+        self.triangle
+            .length_counter
+            .set_enabled(data & 0b0000_0100 != 0);
+        self.noise
+            .length_counter
+            .set_enabled(data & 0b0000_1000 != 0);
+        self.dmc.set_enabled(data & 0b0001_0000 != 0);
+        self.dmc.irq_flag = false;
+    }
+
+    fn write_frame_counter(&mut self, data: u8) {
+        let frame_clock = self.frame_counter.write(data);
+        self.clock_frame(frame_clock);
+    }
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        APU::new()
+    }
+}
+
+impl Mem for APU {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            APU_STATUS => self.read_status(),
+            _ => panic!("Unexpected read on APU register {:04X}", addr),
+        }
+    }
+
+    fn mem_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            PULSE_1_START..=PULSE_1_END => self.pulse1.write(addr, value),
+            PULSE_2_START..=PULSE_2_END => self.pulse2.write(addr, value),
+            TRIANGLE_START..=TRIANGLE_END => self.triangle.write(addr, value),
+            NOISE_START..=NOISE_END => self.noise.write(addr, value),
+            DMC_START..=DMC_END => self.dmc.write(addr, value),
+            APU_STATUS => self.write_control(value),
+            APU_FRAME_COUNTER => self.write_frame_counter(value),
+            _ => panic!("Unexpected write on APU register {:04X}", addr),
+        }
+    }
+}
+
+/// The mixer and the resampler only shape the output, samples that are still buffered are
+/// dropped on load.
+impl Snapshot for APU {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        self.frame_counter.save_state(state);
+        state.write_usize(self.cycles);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.frame_counter.load_state(state)?;
+        self.cycles = state.read_usize()?;
+        self.resampler.take_samples();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    #[test]
+    fn test_length_counter_is_reported_in_status() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_STATUS, 0b0000_0011);
+        apu.mem_write(0x4003, 0b0000_1000);
+
+        assert_equal!(apu.mem_read(APU_STATUS), 0b0000_0001);
+    }
+
+    #[test]
+    fn test_length_counter_is_not_loaded_when_channel_is_disabled() {
+        let mut apu = APU::new();
+        apu.mem_write(0x4003, 0b0000_1000);
+        apu.mem_write(0x400B, 0b0000_1000);
+        apu.mem_write(0x400F, 0b0000_1000);
+
+        assert_equal!(apu.mem_read(APU_STATUS), 0);
+    }
+
+    #[test]
+    fn test_disabling_channel_clears_length_counter() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_STATUS, 0b0000_1111);
+        apu.mem_write(0x4007, 0b0000_1000);
+        apu.mem_write(0x400B, 0b0000_1000);
+        apu.mem_write(0x400F, 0b0000_1000);
+        assert_equal!(apu.mem_read(APU_STATUS), 0b0000_1110);
+
+        apu.mem_write(APU_STATUS, 0b0000_1000);
+        assert_equal!(apu.mem_read(APU_STATUS), 0b0000_1000);
+    }
+
+    #[test]
+    fn test_length_counter_is_clocked_by_half_frames() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_STATUS, 0b0000_0001);
+        // Length index 3 loads a length of 2
+        apu.mem_write(0x4003, 0b0001_1000);
+
+        // Four-step mode clocks half frames twice per sequence
+        for _ in 0..29830 {
+            apu.tick(1);
+        }
+
+        assert_equal!(apu.mem_read(APU_STATUS) & 0b1, 0);
+    }
+
+    #[test]
+    fn test_halted_length_counter_keeps_channel_active() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_STATUS, 0b0000_0001);
+        apu.mem_write(0x4000, 0b0010_0000);
+        apu.mem_write(0x4003, 0b0001_1000);
+
+        for _ in 0..29830 {
+            apu.tick(1);
+        }
+
+        assert_equal!(apu.mem_read(APU_STATUS) & 0b1, 1);
+    }
+
+    #[test]
+    fn test_frame_irq_is_raised_in_four_step_mode_and_cleared_by_status_read() {
+        let mut apu = APU::new();
+        for _ in 0..29829 {
+            apu.tick(1);
+        }
+
+        assert!(apu.irq());
+        assert_equal!(apu.mem_read(APU_STATUS) & 0b0100_0000, 0b0100_0000);
+        assert!(!apu.irq());
+        assert_equal!(apu.mem_read(APU_STATUS) & 0b0100_0000, 0);
+    }
+
+    #[test]
+    fn test_frame_irq_is_not_raised_when_inhibited_or_in_five_step_mode() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_FRAME_COUNTER, 0b0100_0000);
+        for _ in 0..29830 {
+            apu.tick(1);
+        }
+        assert!(!apu.irq());
+
+        let mut apu = APU::new();
+        apu.mem_write(APU_FRAME_COUNTER, 0b1000_0000);
+        for _ in 0..37282 {
+            apu.tick(1);
+        }
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_pal_frame_counter_is_slower() {
+        let mut apu = APU::new();
+        apu.set_region(Region::Pal);
+        for _ in 0..29830 {
+            apu.tick(1);
+        }
+        assert!(!apu.irq());
+
+        for _ in 29830..33253 {
+            apu.tick(1);
+        }
+        assert!(apu.irq());
+    }
+
+    #[test]
+    fn test_pal_samples_follow_the_slower_clock() {
+        let mut apu = APU::new();
+        apu.set_sample_rate(48_000);
+        apu.set_region(Region::Pal);
+        assert_equal!(apu.sample_rate(), 48_000);
+
+        // 33254 CPU cycles is a 1/50th of a second
+        for _ in 0..33254 {
+            apu.tick(1);
+        }
+        assert!((959..=961).contains(&apu.take_samples().len()));
+    }
+
+    #[test]
+    fn test_dmc_requests_sample_bytes_and_raises_irq_at_the_end() {
+        let mut apu = APU::new();
+        apu.mem_write(0x4010, 0b1000_0000);
+        apu.mem_write(0x4012, 0x01); // $C040
+        apu.mem_write(0x4013, 0x00); // 1 byte
+        assert_equal!(apu.poll_dmc_read(), None);
+
+        apu.mem_write(APU_STATUS, 0b0001_0000);
+        assert_equal!(apu.mem_read(APU_STATUS) & 0b0001_0000, 0b0001_0000);
+        assert_equal!(apu.poll_dmc_read(), Some(0xC040));
+
+        apu.fill_dmc_sample(0xFF);
+        assert_equal!(apu.poll_dmc_read(), None);
+        assert!(apu.irq());
+        assert_equal!(apu.mem_read(APU_STATUS), 0b1000_0000);
+
+        apu.mem_write(APU_STATUS, 0);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_pulse_is_muted_for_periods_below_8() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_STATUS, 0b0000_0001);
+        apu.mem_write(0x4000, 0b1011_1111); // 50% duty, constant volume 15
+        apu.mem_write(0x4002, 0x07);
+        apu.mem_write(0x4003, 0b0000_1000);
+
+        for _ in 0..64 {
+            apu.tick(1);
+            assert_equal!(apu.pulse1.output(), 0);
+        }
+
+        apu.mem_write(0x4002, 0x08);
+        let mut outputs = vec![];
+        for _ in 0..64 {
+            apu.tick(1);
+            outputs.push(apu.pulse1.output());
+        }
+        assert!(outputs.contains(&15));
+        assert!(outputs.contains(&0));
+    }
+
+    #[test]
+    fn test_samples_are_produced_at_the_sample_rate() {
+        let mut apu = APU::new();
+        apu.set_sample_rate(48_000);
+        for _ in 0..29830 {
+            apu.tick(1);
+        }
+
+        // 29830 CPU cycles is a 1/60th of a second
+        let samples = apu.take_samples();
+        assert!((799..=801).contains(&samples.len()));
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn test_triangle_needs_linear_counter_to_step() {
+        let mut apu = APU::new();
+        apu.mem_write(APU_STATUS, 0b0000_0100);
+        apu.mem_write(0x400A, 0x00);
+        apu.mem_write(0x400B, 0b0000_1000);
+
+        let first = apu.triangle.output();
+        apu.tick(100);
+        assert_equal!(apu.triangle.output(), first);
+
+        apu.mem_write(0x4008, 0b0111_1111);
+        apu.mem_write(0x400B, 0b0000_1000);
+        for _ in 0..7457 {
+            apu.tick(1);
+        }
+        apu.tick(1);
+        assert_ne!(apu.triangle.output(), first);
+    }
+}
@@ -0,0 +1,74 @@
+// https://www.nesdev.org/wiki/APU_Mixer, lookup table approximation of the nonlinear DAC
+const PULSE_TABLE_SIZE: usize = 31;
+const TND_TABLE_SIZE: usize = 203;
+
+/// Combines the channel outputs into a single sample in the range 0.0..=1.0, the way the two
+/// resistor ladders on the 2A03 do.
+///
+/// pulse_out = pulse_table[pulse1 + pulse2]
+/// tnd_out   = tnd_table[3 * triangle + 2 * noise + dmc]
+///
+pub struct Mixer {
+    pulse_table: [f32; PULSE_TABLE_SIZE],
+    tnd_table: [f32; TND_TABLE_SIZE],
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        let mut pulse_table = [0.0; PULSE_TABLE_SIZE];
+        for (n, item) in pulse_table.iter_mut().enumerate().skip(1) {
+            *item = 95.52 / (8128.0 / n as f32 + 100.0);
+        }
+
+        let mut tnd_table = [0.0; TND_TABLE_SIZE];
+        for (n, item) in tnd_table.iter_mut().enumerate().skip(1) {
+            *item = 163.67 / (24329.0 / n as f32 + 100.0);
+        }
+
+        Mixer {
+            pulse_table,
+            tnd_table,
+        }
+    }
+
+    pub fn mix(&self, pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+        let pulse_out = self.pulse_table[(pulse1 + pulse2) as usize];
+        let tnd_index = 3 * triangle as usize + 2 * noise as usize + dmc as usize;
+        pulse_out + self.tnd_table[tnd_index]
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    #[test]
+    fn test_silence_mixes_to_zero() {
+        let mixer = Mixer::new();
+        assert_equal!(mixer.mix(0, 0, 0, 0, 0), 0.0);
+    }
+
+    #[test]
+    fn test_mix_is_nonlinear() {
+        let mixer = Mixer::new();
+        let one_pulse = mixer.mix(15, 0, 0, 0, 0);
+        let two_pulses = mixer.mix(15, 15, 0, 0, 0);
+
+        assert!((one_pulse - 0.1488).abs() < 0.0001);
+        assert!(two_pulses < one_pulse * 2.0);
+    }
+
+    #[test]
+    fn test_full_output_stays_below_one() {
+        let mixer = Mixer::new();
+        let max = mixer.mix(15, 15, 15, 15, 127);
+        assert!(max > 0.9 && max <= 1.0);
+    }
+}
@@ -0,0 +1,149 @@
+// https://www.nesdev.org/wiki/APU_Mixer#Emulation, the filters the NES applies after the DAC
+const HIGH_PASS_1_HZ: f32 = 90.0;
+const HIGH_PASS_2_HZ: f32 = 440.0;
+const LOW_PASS_HZ: f32 = 14_000.0;
+
+struct HighPassFilter {
+    alpha: f32,
+    prev_input: f32,
+    prev_output: f32,
+}
+
+impl HighPassFilter {
+    fn new(cutoff: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        HighPassFilter {
+            alpha: rc / (rc + dt),
+            prev_input: 0.0,
+            prev_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.prev_output = self.alpha * (self.prev_output + input - self.prev_input);
+        self.prev_input = input;
+        self.prev_output
+    }
+}
+
+struct LowPassFilter {
+    alpha: f32,
+    prev_output: f32,
+}
+
+impl LowPassFilter {
+    fn new(cutoff: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        LowPassFilter {
+            alpha: dt / (rc + dt),
+            prev_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.prev_output += self.alpha * (input - self.prev_output);
+        self.prev_output
+    }
+}
+
+/// Turns the one-sample-per-CPU-cycle mixer output into a PCM stream at `sample_rate`.
+///
+/// Every output sample is the average of the input samples that fall within its period, which
+/// keeps most of the ultrasonic content from aliasing back into the audible range. The result is
+/// then run through the high and low pass filters of the NES audio path.
+pub struct Resampler {
+    sample_rate: u32,
+    input_per_output: f64,
+    position: f64,
+    accumulator: f32,
+    accumulated: u32,
+
+    high_pass_1: HighPassFilter,
+    high_pass_2: HighPassFilter,
+    low_pass: LowPassFilter,
+
+    samples: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Resampler {
+            sample_rate,
+            input_per_output: clock_rate / sample_rate as f64,
+            position: 0.0,
+            accumulator: 0.0,
+            accumulated: 0,
+
+            high_pass_1: HighPassFilter::new(HIGH_PASS_1_HZ, sample_rate),
+            high_pass_2: HighPassFilter::new(HIGH_PASS_2_HZ, sample_rate),
+            low_pass: LowPassFilter::new(LOW_PASS_HZ, sample_rate),
+
+            samples: vec![],
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn push(&mut self, input: f32) {
+        self.accumulator += input;
+        self.accumulated += 1;
+        self.position += 1.0;
+
+        if self.position >= self.input_per_output {
+            self.position -= self.input_per_output;
+
+            let average = self.accumulator / self.accumulated as f32;
+            self.accumulator = 0.0;
+            self.accumulated = 0;
+
+            let sample = self.high_pass_1.process(average);
+            let sample = self.high_pass_2.process(sample);
+            let sample = self.low_pass.process(sample);
+
+            // Nobody is draining the samples, keep at most a second of audio around
+            if self.samples.len() < self.sample_rate as usize {
+                self.samples.push(sample);
+            }
+        }
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CLOCK_RATE: f64 = 1_789_773.0;
+
+    #[test]
+    fn test_produces_sample_rate_samples_per_second() {
+        for sample_rate in [44_100, 48_000] {
+            let mut resampler = Resampler::new(CLOCK_RATE, sample_rate);
+            for _ in 0..CLOCK_RATE as usize {
+                resampler.push(0.0);
+            }
+
+            let produced = resampler.take_samples().len() as i64;
+            assert!((produced - sample_rate as i64).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_constant_input_is_filtered_out() {
+        let mut resampler = Resampler::new(CLOCK_RATE, 44_100);
+        for _ in 0..CLOCK_RATE as usize / 2 {
+            resampler.push(0.5);
+        }
+
+        let samples = resampler.take_samples();
+        assert!(samples.first().unwrap().abs() > 0.1);
+        assert!(samples.last().unwrap().abs() < 0.001);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+const CHANNELS: u16 = 1;
+
+/// Writes mono 16-bit PCM WAV files. The sizes in the header are only known once all samples
+/// have been written, so they're patched in by `finalize`.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    data_size: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> std::io::Result<Self> {
+        WavWriter::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> std::io::Result<Self> {
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        let byte_rate = sample_rate * block_align as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&CHANNELS.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(WavWriter {
+            writer,
+            data_size: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.data_size += (samples.len() * 2) as u32;
+        Ok(())
+    }
+
+    pub fn finalize(mut self) -> std::io::Result<W> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_header_and_samples() {
+        let mut wav = WavWriter::new(Cursor::new(vec![]), 44_100).unwrap();
+        wav.write_samples(&[0.0, 1.0, -1.0]).unwrap();
+        let data = wav.finalize().unwrap().into_inner();
+
+        assert_equal!(data.len(), 44 + 6);
+        assert_equal!(&data[0..4], b"RIFF");
+        assert_equal!(&data[4..8], &42u32.to_le_bytes());
+        assert_equal!(&data[8..12], b"WAVE");
+        assert_equal!(&data[24..28], &44_100u32.to_le_bytes());
+        assert_equal!(&data[40..44], &6u32.to_le_bytes());
+        assert_equal!(&data[44..], &[0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+}
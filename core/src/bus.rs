@@ -1,4 +1,5 @@
 use crate::mem::Mem;
+use crate::snapshot::Snapshot;
 
 #[derive(Copy, Clone)]
 pub enum BusPeripheral {
@@ -6,10 +7,14 @@ pub enum BusPeripheral {
     Ppu,
     PpuScanlines,
     Apu,
+    /// Frames finished since power on.
+    Frames,
 }
 
-pub trait Bus<'a>: Mem {
+pub trait Bus<'a>: Mem + Snapshot {
     fn tick(&mut self, cycles: u8);
     fn poll_nmi_status(&mut self) -> Option<u8>;
+    /// The IRQ line is level triggered, it stays asserted until the source is acknowledged.
+    fn poll_irq_status(&self) -> bool;
     fn get_clock_cycles_for_peripheral(&self, peripheral: BusPeripheral) -> usize;
 }
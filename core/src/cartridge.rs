@@ -1,6 +1,28 @@
+use crate::snapshot::{Snapshot, StateReader, StateWriter};
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Mirroring {
     Vertical,
     Horizontal,
     FourScreen,
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+impl Snapshot for Mirroring {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(*self as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        *self = match state.read_u8()? {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::FourScreen,
+            3 => Mirroring::SingleScreenLower,
+            4 => Mirroring::SingleScreenUpper,
+            value => return Err(format!("Invalid mirroring {} in save state", value)),
+        };
+        Ok(())
+    }
 }
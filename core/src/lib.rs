@@ -1,4 +1,7 @@
 pub mod bus;
 pub mod cartridge;
+pub mod mapper;
 pub mod mem;
 pub mod ppu;
+pub mod region;
+pub mod snapshot;
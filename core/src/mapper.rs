@@ -0,0 +1,46 @@
+use crate::cartridge::Mirroring;
+use crate::snapshot::Snapshot;
+
+pub const PRG_RAM_START: u16 = 0x6000;
+pub const PRG_RAM_END: u16 = 0x7FFF;
+pub const PRG_ROM_START: u16 = 0x8000;
+pub const PRG_ROM_END: u16 = 0xFFFF;
+
+/// The cartridge board. It owns the PRG and CHR memory and decides which banks are visible to the
+/// CPU and the PPU, and how the nametables are mirrored.
+///
+/// The CPU side sees $6000-$FFFF, the PPU side sees the pattern tables at $0000-$1FFF.
+///
+/// The save state of a mapper covers its registers and RAM, the ROM comes from the cartridge.
+pub trait Mapper: Snapshot {
+    fn read_prg(&self, addr: u16) -> u8;
+    fn write_prg(&mut self, addr: u16, value: u8);
+    fn read_chr(&self, addr: u16) -> u8;
+    /// Writes from the PPU to the pattern tables, only boards with CHR-RAM keep them.
+    fn write_chr(&mut self, _addr: u16, _value: u8) {}
+    fn mirroring(&self) -> Mirroring;
+
+    /// Boards with an IRQ source (like the MMC3 scanline counter) assert the CPU IRQ line here.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Called with the address the PPU puts on its address bus when fetching from the pattern
+    /// tables, or when the CPU accesses them through $2007. Lets boards snoop on A12 to count
+    /// scanlines.
+    fn notify_ppu_address(&mut self, _addr: u16) {}
+
+    /// Called with the CPU cycle count right before every `write_prg`. Lets boards that ignore
+    /// writes on consecutive cycles (like MMC1) tell them apart.
+    fn notify_cpu_cycle(&mut self, _cycle: usize) {}
+
+    /// The work RAM at $6000-$7FFF, for boards that have it. On cartridges with a battery it
+    /// holds the saved games.
+    fn prg_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+}
@@ -0,0 +1,95 @@
+const DOTS_PER_SCANLINE: f64 = 341.0;
+
+/// The console the game runs on. The regions differ in clock rates and in the number of
+/// scanlines per frame, which decides how fast games run.
+///
+/// | Region | CPU clock     | PPU dots per CPU cycle | Scanlines | Vblank NMI on | Frames/s |
+/// |--------|---------------|------------------------|-----------|---------------|----------|
+/// | NTSC   | 1.789773 MHz  | 3                      | 262       | 241           | 60.10    |
+/// | PAL    | 1.662607 MHz  | 3.2                    | 312       | 241           | 50.01    |
+/// | Dendy  | 1.773448 MHz  | 3                      | 312       | 291           | 50.01    |
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    pub fn cpu_clock_rate(&self) -> f64 {
+        match self {
+            Region::Ntsc => 1_789_773.0,
+            Region::Pal => 1_662_607.0,
+            Region::Dendy => 1_773_448.0,
+        }
+    }
+
+    /// PPU dots per CPU cycle, as numerator and denominator.
+    pub fn ppu_clock_ratio(&self) -> (u16, u16) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// The scanline that starts vblank. Dendy keeps the PAL frame but starts vblank 50 lines
+    /// later, so the NTSC length of vblank works out.
+    pub fn vblank_scanline(&self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// Only the NTSC PPU skips a dot on odd frames.
+    pub fn skips_odd_frame_dot(&self) -> bool {
+        *self == Region::Ntsc
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        let (numerator, denominator) = self.ppu_clock_ratio();
+        let dots_per_second = self.cpu_clock_rate() * numerator as f64 / denominator as f64;
+        dots_per_second / (DOTS_PER_SCANLINE * self.scanlines_per_frame() as f64)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Region::Ntsc => "ntsc",
+            Region::Pal => "pal",
+            Region::Dendy => "dendy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Region> {
+        [Region::Ntsc, Region::Pal, Region::Dendy]
+            .into_iter()
+            .find(|region| region.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_rates() {
+        assert_eq!(Region::Ntsc.frame_rate().round(), 60.0);
+        assert_eq!(Region::Pal.frame_rate().round(), 50.0);
+        assert_eq!(Region::Dendy.frame_rate().round(), 50.0);
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Region::from_name("pal"), Some(Region::Pal));
+        assert_eq!(Region::from_name(Region::Dendy.name()), Some(Region::Dendy));
+        assert_eq!(Region::from_name("secam"), None);
+    }
+}
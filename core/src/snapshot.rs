@@ -0,0 +1,179 @@
+/// A part of the machine that can be saved to and restored from a save state.
+///
+/// Fields are written in a fixed order with no names or padding, `load_state` has to read them
+/// back in the same order. Any change to what a component saves needs a new save state version.
+pub trait Snapshot {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String>;
+}
+
+/// Little-endian encoder for save states.
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter { data: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    /// Writes the bytes as they are, the reader has to know the length.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.position + len;
+        if end > self.data.len() {
+            return Err("Save state is truncated".to_string());
+        }
+
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_usize(&mut self) -> Result<usize, String> {
+        Ok(self.read_u64()? as usize)
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    /// Fills `bytes` completely, the counterpart of `StateWriter::write_bytes`.
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<(), String> {
+        bytes.copy_from_slice(self.take(bytes.len())?);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+}
+
+/// CRC-32 (IEEE 802.3), the checksum used by zip and PNG.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_back_written_values() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0x12);
+        writer.write_bool(true);
+        writer.write_u16(0x3456);
+        writer.write_u32(0x789A_BCDE);
+        writer.write_usize(123_456_789);
+        writer.write_f32(-0.5);
+        writer.write_bytes(&[1, 2, 3]);
+        let data = writer.into_bytes();
+
+        let mut reader = StateReader::new(&data);
+        assert_eq!(reader.read_u8(), Ok(0x12));
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert_eq!(reader.read_u16(), Ok(0x3456));
+        assert_eq!(reader.read_u32(), Ok(0x789A_BCDE));
+        assert_eq!(reader.read_usize(), Ok(123_456_789));
+        assert_eq!(reader.read_f32(), Ok(-0.5));
+        let mut bytes = [0; 3];
+        assert_eq!(reader.read_bytes(&mut bytes), Ok(()));
+        assert_eq!(bytes, [1, 2, 3]);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_read_past_the_end() {
+        let mut reader = StateReader::new(&[1]);
+        assert_eq!(
+            reader.read_u16(),
+            Err("Save state is truncated".to_string())
+        );
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
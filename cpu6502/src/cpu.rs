@@ -1,12 +1,54 @@
 use crate::opcodes;
 use crate::opcodes::{is_addressing_absolute, AddressingMode, Instruction};
 use crate::register::{CpuFlags, Register, RegisterField, STACK};
-use core::bus::Bus;
-use core::mem::{Mem, VECTOR_NMI_INTERRUPT_HANDLER, VECTOR_RESET_HANDLER};
+use core::bus::{Bus, BusPeripheral};
+use core::mem::{
+    Mem, VECTOR_IRQ_INTERRUPT_HANDLER, VECTOR_NMI_INTERRUPT_HANDLER, VECTOR_RESET_HANDLER,
+};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+// XAA and ATX mix A with a value that depends on the chip and its temperature, this is the most
+// common one
+const UNSTABLE_MAGIC: u8 = 0xEE;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Interrupt {
+    Nmi,
+    Irq,
+}
+
+/// What `CPU::step` did.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Step {
+    /// Serviced before the instruction ran.
+    pub interrupt: Option<Interrupt>,
+    /// Where the instruction is, after servicing the interrupt.
+    pub pc: u16,
+    pub opcode: u8,
+    /// The bytes following the opcode, only the first `len - 1` belong to the instruction. They
+    /// are the values the CPU fetched while running it.
+    pub operand: [u8; 2],
+    pub len: u8,
+    /// CPU cycles taken by the instruction and the interrupt.
+    pub cycles: usize,
+}
 
 pub struct CPU<'a> {
     pub register: Register,
     pub bus: Box<dyn Bus<'a>>,
+    // The interrupt lines as seen at the end of the last cycle and of the one before. The CPU
+    // polls for interrupts before the last cycle of an instruction, so the earlier one decides.
+    nmi_pending: bool,
+    irq_pending: bool,
+    prev_nmi_pending: bool,
+    prev_irq_pending: bool,
+    // Set by KIL, only a reset gets the CPU going again
+    jammed: bool,
+    // The opcode and operand bytes of the current instruction, recorded by `read` as they are
+    // fetched, so `step` can report them without reading the bus again.
+    fetch_pc: u16,
+    fetched: [u8; 3],
+    fetched_len: u8,
 }
 
 impl<'a> Mem for CPU<'a> {
@@ -19,6 +61,37 @@ impl<'a> Mem for CPU<'a> {
     }
 }
 
+/// The whole machine, the CPU registers followed by everything on the bus.
+impl<'a> Snapshot for CPU<'a> {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.register.save_state(state);
+        state.write_bool(self.nmi_pending);
+        state.write_bool(self.irq_pending);
+        state.write_bool(self.prev_nmi_pending);
+        state.write_bool(self.prev_irq_pending);
+        self.bus.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.register.load_state(state)?;
+        self.nmi_pending = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        self.prev_nmi_pending = state.read_bool()?;
+        self.prev_irq_pending = state.read_bool()?;
+        // A jammed CPU was saved pointing at the KIL, running it again jams it again
+        self.jammed = false;
+        self.bus.load_state(state)
+    }
+}
+
+/// How an instruction uses the memory its operand points to. Indexed writes always spend the
+/// cycle that fixes up the high byte of the address, reads only when the index crosses a page.
+#[derive(PartialEq, Eq, Copy, Clone)]
+enum Access {
+    Read,
+    Write,
+}
+
 fn page_cross(a: u16, b: u16) -> bool {
     (a & 0xFF00) != (b & 0xFF00)
 }
@@ -28,12 +101,30 @@ impl<'a> CPU<'a> {
         CPU {
             register: Register::new(),
             bus,
+            nmi_pending: false,
+            irq_pending: false,
+            prev_nmi_pending: false,
+            prev_irq_pending: false,
+            jammed: false,
+            fetch_pc: 0,
+            fetched: [0; 3],
+            fetched_len: 0,
         }
     }
 
     pub fn reset(&mut self) {
         self.register = Register::new();
         self.register.pc = self.mem_read_u16(VECTOR_RESET_HANDLER);
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.prev_nmi_pending = false;
+        self.prev_irq_pending = false;
+        self.jammed = false;
+    }
+
+    /// The address of the KIL opcode that stopped the CPU, if it's stopped.
+    pub fn jammed(&self) -> Option<u16> {
+        self.jammed.then_some(self.register.pc)
     }
 
     #[cfg(test)]
@@ -44,153 +135,304 @@ impl<'a> CPU<'a> {
         }
         self.reset();
         self.register.pc = base as u16;
-        self.run()
+        // The test programs end with a BRK
+        self.run_until(|cpu| cpu.mem_read(cpu.register.pc) == 0x00 || cpu.jammed)
     }
 
     pub fn run(&mut self) {
         self.run_with_callback(|_| {});
     }
 
+    /// Runs forever, calling `callback` before every instruction.
     pub fn run_with_callback<F>(&mut self, mut callback: F)
     where
         F: FnMut(&mut CPU),
+    {
+        self.run_until(|cpu| {
+            callback(cpu);
+            false
+        })
+    }
+
+    /// Calls `callback` before every instruction and returns once it returns true, before running
+    /// that instruction.
+    pub fn run_until<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(&mut CPU) -> bool,
     {
         loop {
-            if let Some(_nmi) = self.bus.poll_nmi_status() {
-                self.interrupt_nmi();
+            self.service_interrupt();
+            if callback(self) {
+                return;
             }
+            self.execute_instruction();
+        }
+    }
+
+    /// Runs a single instruction, after servicing a pending interrupt.
+    pub fn step(&mut self) -> Step {
+        let start = self.cycles();
+        let interrupt = self.service_interrupt();
+
+        let pc = self.register.pc;
+        self.execute_instruction();
+
+        // A jammed CPU fetches nothing, the KIL it ran last is still recorded
+        let opcode = self.fetched[0];
+        let len = if self.jammed {
+            1
+        } else {
+            (*opcodes::OPCODES_LIST)[opcode as usize].len
+        };
+        let mut operand = [0; 2];
+        operand[..len as usize - 1].copy_from_slice(&self.fetched[1..len as usize]);
+        Step {
+            interrupt,
+            pc,
+            opcode,
+            operand,
+            len,
+            cycles: self.cycles() - start,
+        }
+    }
+
+    /// Runs whole instructions until at least `cycles` CPU cycles have passed, and returns how
+    /// many did.
+    pub fn run_for_cycles(&mut self, cycles: usize) -> usize {
+        let mut ran = 0;
+        while ran < cycles {
+            ran += self.step().cycles;
+        }
+        ran
+    }
+
+    /// Runs until the PPU has finished the frame and entered vblank.
+    pub fn run_until_frame(&mut self) {
+        let frame = self
+            .bus
+            .get_clock_cycles_for_peripheral(BusPeripheral::Frames);
+        while self
+            .bus
+            .get_clock_cycles_for_peripheral(BusPeripheral::Frames)
+            == frame
+        {
+            self.step();
+        }
+    }
+
+    fn cycles(&self) -> usize {
+        self.bus.get_clock_cycles_for_peripheral(BusPeripheral::Cpu)
+    }
+
+    fn service_interrupt(&mut self) -> Option<Interrupt> {
+        if self.jammed {
+            return None;
+        }
+
+        if self.prev_nmi_pending {
+            self.nmi_pending = false;
+            self.prev_nmi_pending = false;
+            self.interrupt(VECTOR_NMI_INTERRUPT_HANDLER);
+            Some(Interrupt::Nmi)
+        } else if self.prev_irq_pending {
+            self.interrupt(VECTOR_IRQ_INTERRUPT_HANDLER);
+            Some(Interrupt::Irq)
+        } else {
+            None
+        }
+    }
+
+    fn execute_instruction(&mut self) {
+        if self.jammed {
+            // The rest of the console keeps running
+            self.bus.tick(1);
+            return;
+        }
+
+        self.fetch_pc = self.register.pc;
+        self.fetched_len = 0;
+        let code = self.read(self.register.pc);
+        self.register.pc = self.register.pc.wrapping_add(1);
+        let program_counter_state = self.register.pc;
 
-            callback(self);
-
-            let code = self.mem_read(self.register.pc);
-            self.register.pc = self.register.pc.wrapping_add(1);
-            let program_counter_state = self.register.pc;
-
-            let opcode = (*opcodes::OPCODES_LIST)[code as usize];
-
-            match opcode.instruction {
-                Instruction::BRK => {
-                    return;
-                }
-                Instruction::NOP => {}
-                Instruction::DOP => {}
-                Instruction::TOP => {
-                    if self.page_crossed(&opcode.mode) {
-                        self.bus.tick(1)
-                    }
-                }
-
-                // Logical Operations
-                Instruction::AND => self
-                    .tick_on_page_cross(&opcode.mode, |cpu| cpu.logic(&opcode.mode, |a, b| a & b)),
-                Instruction::EOR => self
-                    .tick_on_page_cross(&opcode.mode, |cpu| cpu.logic(&opcode.mode, |a, b| a ^ b)),
-                Instruction::ORA => self
-                    .tick_on_page_cross(&opcode.mode, |cpu| cpu.logic(&opcode.mode, |a, b| a | b)),
-                Instruction::SAX => self.sax(&opcode.mode),
-
-                // Arithmetic Operations
-                Instruction::ADC => self.adc(&opcode.mode),
-                Instruction::SBC => self.sbc(&opcode.mode),
-                Instruction::ASL => self.arithmetic_shift(&opcode.mode, asl),
-                Instruction::BIT => self.bit(&opcode.mode),
-                Instruction::DEC => self.decrement_memory(&opcode.mode),
-                Instruction::DEX => self.decrement_register(RegisterField::X),
-                Instruction::DEY => self.decrement_register(RegisterField::Y),
-                Instruction::INC => self.increment_memory(&opcode.mode),
-                Instruction::INX => self.increment_register(RegisterField::X),
-                Instruction::INY => self.increment_register(RegisterField::Y),
-                Instruction::LSR => self.arithmetic_shift(&opcode.mode, lsr),
-                Instruction::ROL => self.arithmetic_shift(&opcode.mode, rol),
-                Instruction::ROR => self.arithmetic_shift(&opcode.mode, ror),
-
-                // Branch Operations
-                Instruction::BCC => self.branch(!self.register.status.contains(CpuFlags::CARRY)),
-                Instruction::BCS => self.branch(self.register.status.contains(CpuFlags::CARRY)),
-                Instruction::BNE => self.branch(!self.register.status.contains(CpuFlags::ZERO)),
-                Instruction::BEQ => self.branch(self.register.status.contains(CpuFlags::ZERO)),
-                Instruction::BPL => self.branch(!self.register.status.contains(CpuFlags::NEGATIVE)),
-                Instruction::BMI => self.branch(self.register.status.contains(CpuFlags::NEGATIVE)),
-                Instruction::BVC => self.branch(!self.register.status.contains(CpuFlags::OVERFLOW)),
-                Instruction::BVS => self.branch(self.register.status.contains(CpuFlags::OVERFLOW)),
-
-                // Jump
-                Instruction::JMP if is_addressing_absolute(opcode.mode) => {
-                    self.jmp_absolute();
-                }
-                Instruction::JMP => {
-                    self.jmp_indirect();
-                }
-                Instruction::JSR => self.jsr(),
-                Instruction::RTI => self.rti(),
-                Instruction::RTS => self.rts(),
-
-                // Stack
-                Instruction::PHA => self.pha(),
-                Instruction::PHP => self.php(),
-                Instruction::PLA => self.pla(),
-                Instruction::PLP => self.plp(),
-
-                // Compare Operations
-                Instruction::CMP => self.tick_on_page_cross(&opcode.mode, |cpu| {
-                    cpu.compare(RegisterField::A, &opcode.mode)
-                }),
-                Instruction::CPX => self.tick_on_page_cross(&opcode.mode, |cpu| {
-                    cpu.compare(RegisterField::X, &opcode.mode)
-                }),
-                Instruction::CPY => self.tick_on_page_cross(&opcode.mode, |cpu| {
-                    cpu.compare(RegisterField::Y, &opcode.mode)
-                }),
-
-                // Clear & Set Registers
-                Instruction::CLC => self.register.status.remove(CpuFlags::CARRY),
-                Instruction::CLD => self.register.status.remove(CpuFlags::DECIMAL_MODE),
-                Instruction::CLI => self.register.status.remove(CpuFlags::INTERRUPT_DISABLE),
-                Instruction::CLV => self.register.status.remove(CpuFlags::OVERFLOW),
-                Instruction::SEC => self.register.status.insert(CpuFlags::CARRY),
-                Instruction::SED => self.register.status.insert(CpuFlags::DECIMAL_MODE),
-                Instruction::SEI => self.register.status.insert(CpuFlags::INTERRUPT_DISABLE),
-
-                // Load Operations
-                Instruction::LDA => self.load(RegisterField::A, &opcode.mode),
-                Instruction::LDX => self.load(RegisterField::X, &opcode.mode),
-                Instruction::LDY => self.load(RegisterField::Y, &opcode.mode),
-                Instruction::LAX => self.lax(&opcode.mode),
-
-                // Store Operations
-                Instruction::STA => self.store(RegisterField::A, &opcode.mode),
-                Instruction::STX => self.store(RegisterField::X, &opcode.mode),
-                Instruction::STY => self.store(RegisterField::Y, &opcode.mode),
-
-                // Transfer Operations
-                Instruction::TAX => self.transfer(RegisterField::A, RegisterField::X),
-                Instruction::TAY => self.transfer(RegisterField::A, RegisterField::Y),
-                Instruction::TSX => self.transfer(RegisterField::SP, RegisterField::X),
-                Instruction::TXA => self.transfer(RegisterField::X, RegisterField::A),
-                Instruction::TXS => self.transfer(RegisterField::X, RegisterField::SP),
-                Instruction::TYA => self.transfer(RegisterField::Y, RegisterField::A),
-
-                Instruction::DCP => self.dcp(&opcode.mode),
-                Instruction::ISB => self.isb(&opcode.mode),
-                Instruction::SLO => self.slo(&opcode.mode),
-                Instruction::RLA => self.rla(&opcode.mode),
-                Instruction::SRE => self.sre(&opcode.mode),
-                Instruction::RRA => self.rra(&opcode.mode),
-
-                _ => {
-                    panic!(
-                        "Unknown opcode: {:#02X} instruction: {:#?}",
-                        code, opcode.instruction
-                    )
-                }
+        let opcode = (*opcodes::OPCODES_LIST)[code as usize];
+        if opcode.len == 1 {
+            // Single byte instructions read the next byte anyway, and throw it away
+            self.read(self.register.pc);
+        }
+
+        match opcode.instruction {
+            Instruction::BRK => self.brk(),
+            Instruction::NOP => {}
+            Instruction::DOP | Instruction::TOP => {
+                self.read_operand(&opcode.mode);
             }
 
-            self.bus.tick(opcode.cycles);
+            // Logical Operations
+            Instruction::AND => self.logic(&opcode.mode, |a, b| a & b),
+            Instruction::EOR => self.logic(&opcode.mode, |a, b| a ^ b),
+            Instruction::ORA => self.logic(&opcode.mode, |a, b| a | b),
+            Instruction::SAX => self.sax(&opcode.mode),
+
+            // Arithmetic Operations
+            Instruction::ADC => self.adc(&opcode.mode),
+            Instruction::SBC => self.sbc(&opcode.mode),
+            Instruction::ASL => {
+                self.arithmetic_shift(&opcode.mode, asl);
+            }
+            Instruction::BIT => self.bit(&opcode.mode),
+            Instruction::DEC => {
+                self.decrement_memory(&opcode.mode);
+            }
+            Instruction::DEX => self.decrement_register(RegisterField::X),
+            Instruction::DEY => self.decrement_register(RegisterField::Y),
+            Instruction::INC => {
+                self.increment_memory(&opcode.mode);
+            }
+            Instruction::INX => self.increment_register(RegisterField::X),
+            Instruction::INY => self.increment_register(RegisterField::Y),
+            Instruction::LSR => {
+                self.arithmetic_shift(&opcode.mode, lsr);
+            }
+            Instruction::ROL => {
+                self.arithmetic_shift(&opcode.mode, rol);
+            }
+            Instruction::ROR => {
+                self.arithmetic_shift(&opcode.mode, ror);
+            }
 
-            if program_counter_state == self.register.pc {
-                self.register.pc = self.register.pc.wrapping_add((opcode.len - 1) as u16);
+            // Branch Operations
+            Instruction::BCC => self.branch(!self.register.status.contains(CpuFlags::CARRY)),
+            Instruction::BCS => self.branch(self.register.status.contains(CpuFlags::CARRY)),
+            Instruction::BNE => self.branch(!self.register.status.contains(CpuFlags::ZERO)),
+            Instruction::BEQ => self.branch(self.register.status.contains(CpuFlags::ZERO)),
+            Instruction::BPL => self.branch(!self.register.status.contains(CpuFlags::NEGATIVE)),
+            Instruction::BMI => self.branch(self.register.status.contains(CpuFlags::NEGATIVE)),
+            Instruction::BVC => self.branch(!self.register.status.contains(CpuFlags::OVERFLOW)),
+            Instruction::BVS => self.branch(self.register.status.contains(CpuFlags::OVERFLOW)),
+
+            // Jump
+            Instruction::JMP if is_addressing_absolute(opcode.mode) => {
+                self.jmp_absolute();
+            }
+            Instruction::JMP => {
+                self.jmp_indirect();
+            }
+            Instruction::JSR => self.jsr(),
+            Instruction::RTI => self.rti(),
+            Instruction::RTS => self.rts(),
+
+            // Stack
+            Instruction::PHA => self.pha(),
+            Instruction::PHP => self.php(),
+            Instruction::PLA => self.pla(),
+            Instruction::PLP => self.plp(),
+
+            // Compare Operations
+            Instruction::CMP => self.compare(RegisterField::A, &opcode.mode),
+            Instruction::CPX => self.compare(RegisterField::X, &opcode.mode),
+            Instruction::CPY => self.compare(RegisterField::Y, &opcode.mode),
+
+            // Clear & Set Registers
+            Instruction::CLC => self.register.status.remove(CpuFlags::CARRY),
+            Instruction::CLD => self.register.status.remove(CpuFlags::DECIMAL_MODE),
+            Instruction::CLI => self.register.status.remove(CpuFlags::INTERRUPT_DISABLE),
+            Instruction::CLV => self.register.status.remove(CpuFlags::OVERFLOW),
+            Instruction::SEC => self.register.status.insert(CpuFlags::CARRY),
+            Instruction::SED => self.register.status.insert(CpuFlags::DECIMAL_MODE),
+            Instruction::SEI => self.register.status.insert(CpuFlags::INTERRUPT_DISABLE),
+
+            // Load Operations
+            Instruction::LDA => self.load(RegisterField::A, &opcode.mode),
+            Instruction::LDX => self.load(RegisterField::X, &opcode.mode),
+            Instruction::LDY => self.load(RegisterField::Y, &opcode.mode),
+            Instruction::LAX => self.lax(&opcode.mode),
+
+            // Store Operations
+            Instruction::STA => self.store(RegisterField::A, &opcode.mode),
+            Instruction::STX => self.store(RegisterField::X, &opcode.mode),
+            Instruction::STY => self.store(RegisterField::Y, &opcode.mode),
+
+            // Transfer Operations
+            Instruction::TAX => self.transfer(RegisterField::A, RegisterField::X),
+            Instruction::TAY => self.transfer(RegisterField::A, RegisterField::Y),
+            Instruction::TSX => self.transfer(RegisterField::SP, RegisterField::X),
+            Instruction::TXA => self.transfer(RegisterField::X, RegisterField::A),
+            Instruction::TXS => self.transfer(RegisterField::X, RegisterField::SP),
+            Instruction::TYA => self.transfer(RegisterField::Y, RegisterField::A),
+
+            Instruction::DCP => self.dcp(&opcode.mode),
+            Instruction::ISB => self.isb(&opcode.mode),
+            Instruction::SLO => self.slo(&opcode.mode),
+            Instruction::RLA => self.rla(&opcode.mode),
+            Instruction::SRE => self.sre(&opcode.mode),
+            Instruction::RRA => self.rra(&opcode.mode),
+            Instruction::AAC => self.aac(&opcode.mode),
+            Instruction::ASR => self.asr(&opcode.mode),
+            Instruction::ARR => self.arr(&opcode.mode),
+            Instruction::ATX => self.atx(&opcode.mode),
+            Instruction::AXS => self.axs(&opcode.mode),
+            Instruction::XAA => self.xaa(&opcode.mode),
+            Instruction::LAR => self.lar(&opcode.mode),
+            Instruction::AXA => {
+                let value =
+                    self.register.read(RegisterField::A) & self.register.read(RegisterField::X);
+                self.store_and_high_byte(&opcode.mode, value)
             }
+            Instruction::SXA => {
+                self.store_and_high_byte(&opcode.mode, self.register.read(RegisterField::X))
+            }
+            Instruction::SYA => {
+                self.store_and_high_byte(&opcode.mode, self.register.read(RegisterField::Y))
+            }
+            Instruction::XAS => self.xas(&opcode.mode),
+            Instruction::KIL => {
+                self.register.pc = self.register.pc.wrapping_sub(1);
+                self.jammed = true;
+            }
+        }
+
+        if program_counter_state == self.register.pc {
+            self.register.pc = self.register.pc.wrapping_add((opcode.len - 1) as u16);
+        }
+    }
+
+    /// Ends a CPU cycle, the rest of the console runs for the cycle and the interrupt lines are
+    /// sampled.
+    fn tick(&mut self) {
+        self.bus.tick(1);
+
+        self.prev_nmi_pending = self.nmi_pending;
+        self.prev_irq_pending = self.irq_pending;
+        if self.bus.poll_nmi_status().is_some() {
+            self.nmi_pending = true;
+        }
+        self.irq_pending = self.bus.poll_irq_status()
+            && !self.register.status.contains(CpuFlags::INTERRUPT_DISABLE);
+    }
+
+    /// Every read and write takes a cycle, including the ones the CPU throws away.
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.bus.mem_read(addr);
+        // The opcode and operand are the first reads of an instruction, in order
+        let fetched = self.fetched_len as usize;
+        if fetched < self.fetched.len() && addr == self.fetch_pc.wrapping_add(fetched as u16) {
+            self.fetched[fetched] = value;
+            self.fetched_len += 1;
         }
+        self.tick();
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.bus.mem_write(addr, value);
+        self.tick();
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        let lo = self.read(addr);
+        let hi = self.read(addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
     }
 
     fn transfer(&mut self, source: RegisterField, target: RegisterField) {
@@ -198,14 +440,8 @@ impl<'a> CPU<'a> {
     }
 
     fn load(&mut self, target: RegisterField, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-
+        let value = self.read_operand(mode);
         self.register.write(target, value);
-
-        if self.page_crossed(mode) {
-            self.bus.tick(1)
-        }
     }
 
     fn increment_register(&mut self, target: RegisterField) {
@@ -213,14 +449,10 @@ impl<'a> CPU<'a> {
         self.register.write(target, value);
     }
 
-    fn increment_memory(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut value = self.mem_read(addr);
-
-        value = value.wrapping_add(1);
-
-        self.mem_write(addr, value);
+    fn increment_memory(&mut self, mode: &AddressingMode) -> u8 {
+        let value = self.modify_operand(mode, |_, value| value.wrapping_add(1));
         self.register.update_zero_and_negative_flags(value);
+        value
     }
 
     fn decrement_register(&mut self, target: RegisterField) {
@@ -228,25 +460,23 @@ impl<'a> CPU<'a> {
         self.register.write(target, value);
     }
 
-    fn decrement_memory(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut value = self.mem_read(addr);
-
-        value = value.wrapping_sub(1);
-
-        self.mem_write(addr, value);
+    fn decrement_memory(&mut self, mode: &AddressingMode) -> u8 {
+        let value = self.modify_operand(mode, |_, value| value.wrapping_sub(1));
         self.register.update_zero_and_negative_flags(value);
+        value
     }
 
     fn store(&mut self, source: RegisterField, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.mem_write(addr, self.register.read(source))
+        let addr = self.fetch_operand_address(mode, Access::Write);
+        self.write(addr, self.register.read(source))
     }
 
     fn compare(&mut self, source: RegisterField, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+        let data = self.read_operand(mode);
+        self.compare_with(source, data);
+    }
 
+    fn compare_with(&mut self, source: RegisterField, data: u8) {
         let compare_with = self.register.read(source);
 
         let result = compare_with.wrapping_sub(data);
@@ -257,34 +487,36 @@ impl<'a> CPU<'a> {
         self.register.update_zero_and_negative_flags(result);
     }
 
-    fn tick_on_page_cross<F>(&mut self, mode: &AddressingMode, function: F)
+    fn logic<F>(&mut self, mode: &AddressingMode, op: F)
     where
-        F: FnOnce(&mut CPU),
+        F: Fn(u8, u8) -> u8,
     {
-        function(self);
-
-        if self.page_crossed(mode) {
-            self.bus.tick(1);
-        }
+        let data = self.read_operand(mode);
+        self.logic_with(data, op);
     }
 
-    fn logic<F>(&mut self, mode: &AddressingMode, op: F)
+    fn logic_with<F>(&mut self, data: u8, op: F)
     where
         F: Fn(u8, u8) -> u8,
     {
-        let addr = self.get_operand_address(mode);
-        let value = op(self.register.read(RegisterField::A), self.mem_read(addr));
+        let value = op(self.register.read(RegisterField::A), data);
         self.register.write(RegisterField::A, value);
     }
 
     fn stack_push(&mut self, value: u8) {
-        self.mem_write(STACK + self.register.sp as u16, value);
+        self.write(STACK + self.register.sp as u16, value);
         self.register.sp = self.register.sp.wrapping_sub(1);
     }
 
     fn stack_pop(&mut self) -> u8 {
         self.register.sp = self.register.sp.wrapping_add(1);
-        self.mem_read(STACK + self.register.sp as u16)
+        self.read(STACK + self.register.sp as u16)
+    }
+
+    /// Pulling from the stack takes an extra cycle to increment the stack pointer, meanwhile the
+    /// CPU reads the top of the stack.
+    fn stack_peek(&mut self) {
+        self.read(STACK + self.register.sp as u16);
     }
 
     fn stack_push_u16(&mut self, value: u16) {
@@ -302,8 +534,8 @@ impl<'a> CPU<'a> {
     }
 
     fn dcp(&mut self, mode: &AddressingMode) {
-        self.decrement_memory(mode);
-        self.compare(RegisterField::A, mode);
+        let value = self.decrement_memory(mode);
+        self.compare_with(RegisterField::A, value);
     }
 
     fn lax(&mut self, mode: &AddressingMode) {
@@ -313,6 +545,7 @@ impl<'a> CPU<'a> {
     }
 
     fn pla(&mut self) {
+        self.stack_peek();
         let value = self.stack_pop();
         self.register.write(RegisterField::A, value);
     }
@@ -322,6 +555,11 @@ impl<'a> CPU<'a> {
     }
 
     fn plp(&mut self) {
+        self.stack_peek();
+        self.pull_status();
+    }
+
+    fn pull_status(&mut self) {
         let new_status = self.stack_pop();
         self.register.write(RegisterField::Status, new_status);
         self.register.status.remove(CpuFlags::BREAK);
@@ -336,14 +574,16 @@ impl<'a> CPU<'a> {
     }
 
     fn adc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+        let data = self.read_operand(mode);
         self.add_to_register_a(data);
     }
 
     fn sbc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+        let data = self.read_operand(mode);
+        self.subtract_from_register_a(data);
+    }
+
+    fn subtract_from_register_a(&mut self, data: u8) {
         self.add_to_register_a(((data as i8).wrapping_neg().wrapping_sub(1)) as u8);
     }
 
@@ -367,14 +607,16 @@ impl<'a> CPU<'a> {
         self.register.write(RegisterField::A, result);
     }
 
-    fn arithmetic_shift<F>(&mut self, mode: &AddressingMode, op: F)
+    /// Returns the shifted value.
+    fn arithmetic_shift<F>(&mut self, mode: &AddressingMode, op: F) -> u8
     where
         F: Fn(u8, bool) -> (u8, bool),
     {
         if matches!(mode, AddressingMode::Accumulator) {
             self.arithmetic_accumulator(&op);
+            self.register.read(RegisterField::A)
         } else {
-            self.arithmetic_mem(mode, op);
+            self.arithmetic_mem(mode, op)
         }
     }
 
@@ -391,24 +633,23 @@ impl<'a> CPU<'a> {
         self.register.write(RegisterField::A, data);
     }
 
-    fn arithmetic_mem<F>(&mut self, mode: &AddressingMode, op: F)
+    fn arithmetic_mem<F>(&mut self, mode: &AddressingMode, op: F) -> u8
     where
         F: Fn(u8, bool) -> (u8, bool),
     {
-        let addr = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
-        let carry = self.register.status.contains(CpuFlags::CARRY);
+        let data = self.modify_operand(mode, |cpu, data| {
+            let carry = cpu.register.status.contains(CpuFlags::CARRY);
+            let (data, carry) = op(data, carry);
+            cpu.register.status.set(CpuFlags::CARRY, carry);
+            data
+        });
 
-        let (data, carry) = op(data, carry);
-        self.register.status.set(CpuFlags::CARRY, carry);
-
-        self.mem_write(addr, data);
         self.register.update_zero_and_negative_flags(data);
+        data
     }
 
     fn bit(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+        let data = self.read_operand(mode);
 
         let mask = self.register.read(RegisterField::A) & data;
         self.register.status.set(CpuFlags::ZERO, mask == 0);
@@ -422,44 +663,133 @@ impl<'a> CPU<'a> {
     }
 
     fn sax(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
+        let addr = self.fetch_operand_address(mode, Access::Write);
         let data = self.register.read(RegisterField::X) & self.register.read(RegisterField::A);
-        self.mem_write(addr, data);
+        self.write(addr, data);
     }
 
     fn isb(&mut self, mode: &AddressingMode) {
-        self.increment_memory(mode);
-        self.sbc(mode);
+        let value = self.increment_memory(mode);
+        self.subtract_from_register_a(value);
     }
 
     fn slo(&mut self, mode: &AddressingMode) {
-        self.arithmetic_shift(mode, asl);
-        self.logic(mode, |a, b| a | b);
+        let value = self.arithmetic_shift(mode, asl);
+        self.logic_with(value, |a, b| a | b);
     }
     fn rla(&mut self, mode: &AddressingMode) {
-        self.arithmetic_shift(mode, rol);
-        self.logic(mode, |a, b| a & b);
+        let value = self.arithmetic_shift(mode, rol);
+        self.logic_with(value, |a, b| a & b);
     }
 
     fn sre(&mut self, mode: &AddressingMode) {
-        self.arithmetic_shift(mode, lsr);
-        self.logic(mode, |a, b| a ^ b);
+        let value = self.arithmetic_shift(mode, lsr);
+        self.logic_with(value, |a, b| a ^ b);
     }
 
     fn rra(&mut self, mode: &AddressingMode) {
-        self.arithmetic_shift(mode, ror);
-        self.adc(mode);
+        let value = self.arithmetic_shift(mode, ror);
+        self.add_to_register_a(value);
+    }
+
+    fn aac(&mut self, mode: &AddressingMode) {
+        self.logic(mode, |a, b| a & b);
+        let negative = self.register.status.contains(CpuFlags::NEGATIVE);
+        self.register.status.set(CpuFlags::CARRY, negative);
+    }
+
+    fn asr(&mut self, mode: &AddressingMode) {
+        self.logic(mode, |a, b| a & b);
+        self.arithmetic_accumulator(&lsr);
+    }
+
+    fn arr(&mut self, mode: &AddressingMode) {
+        self.logic(mode, |a, b| a & b);
+        self.arithmetic_accumulator(&ror);
+
+        // The carry comes from bit 6 and the overflow from bit 6 xor bit 5, as if added
+        let result = self.register.read(RegisterField::A);
+        self.register
+            .status
+            .set(CpuFlags::CARRY, result & 0b0100_0000 != 0);
+        self.register
+            .status
+            .set(CpuFlags::OVERFLOW, ((result >> 6) ^ (result >> 5)) & 1 != 0);
+    }
+
+    fn atx(&mut self, mode: &AddressingMode) {
+        let data = self.read_operand(mode);
+        let value = (self.register.read(RegisterField::A) | UNSTABLE_MAGIC) & data;
+        self.register.write(RegisterField::A, value);
+        self.register.write(RegisterField::X, value);
+    }
+
+    fn axs(&mut self, mode: &AddressingMode) {
+        let data = self.read_operand(mode);
+        let value = self.register.read(RegisterField::A) & self.register.read(RegisterField::X);
+
+        // Like CMP, without the borrow of SBC
+        self.register.status.set(CpuFlags::CARRY, value >= data);
+        self.register
+            .write(RegisterField::X, value.wrapping_sub(data));
+    }
+
+    fn xaa(&mut self, mode: &AddressingMode) {
+        let data = self.read_operand(mode);
+        let value = (self.register.read(RegisterField::A) | UNSTABLE_MAGIC)
+            & self.register.read(RegisterField::X)
+            & data;
+        self.register.write(RegisterField::A, value);
+    }
+
+    fn lar(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode) & self.register.read(RegisterField::SP);
+        self.register.write(RegisterField::A, value);
+        self.register.write(RegisterField::X, value);
+        self.register.write(RegisterField::SP, value);
+    }
+
+    fn xas(&mut self, mode: &AddressingMode) {
+        let value = self.register.read(RegisterField::A) & self.register.read(RegisterField::X);
+        self.register.write(RegisterField::SP, value);
+        self.store_and_high_byte(mode, value);
+    }
+
+    /// AXA, SXA, SYA and XAS store the value ANDed with the high byte of the base address plus
+    /// one. When indexing crosses a page, the stored value also replaces the high byte of the
+    /// address.
+    fn store_and_high_byte(&mut self, mode: &AddressingMode, value: u8) {
+        let addr = self.fetch_operand_address(mode, Access::Write);
+        let index = match mode {
+            AddressingMode::Absolute_X => self.register.read(RegisterField::X),
+            _ => self.register.read(RegisterField::Y),
+        };
+        let base = addr.wrapping_sub(index as u16);
+
+        let value = value & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if page_cross(base, addr) {
+            (value as u16) << 8 | (addr & 0x00FF)
+        } else {
+            addr
+        };
+        self.write(addr, value);
     }
 
     fn branch(&mut self, condition: bool) {
+        let jump = self.read(self.register.pc) as i8;
         if condition {
-            self.bus.tick(1);
+            // A taken branch doesn't poll for interrupts on its third cycle, an IRQ that shows
+            // up then waits for the next instruction
+            if self.irq_pending && !self.prev_irq_pending {
+                self.irq_pending = false;
+            }
 
-            let jump: i8 = self.mem_read(self.register.pc) as i8;
-            let jump_addr = self.register.pc.wrapping_add(1).wrapping_add(jump as u16);
+            let next = self.register.pc.wrapping_add(1);
+            self.read(next);
 
-            if page_cross(self.register.pc.wrapping_add(1), jump_addr) {
-                self.bus.tick(1);
+            let jump_addr = next.wrapping_add(jump as u16);
+            if page_cross(next, jump_addr) {
+                self.read((next & 0xFF00) | (jump_addr & 0x00FF));
             }
 
             self.register.pc = jump_addr
@@ -467,72 +797,133 @@ impl<'a> CPU<'a> {
     }
 
     fn jmp_absolute(&mut self) {
-        let addr = self.get_operand_address(&AddressingMode::Absolute);
+        let addr = self.read_u16(self.register.pc);
         self.register.pc = addr;
     }
 
     fn jmp_indirect(&mut self) {
-        let addr = self.get_operand_address(&AddressingMode::Absolute);
+        let addr = self.read_u16(self.register.pc);
 
         // 6502 bug mode with with page boundary:
         //  if address $3000 contains $40, $30FF contains $80, and $3100 contains $50,
         // the result of JMP ($30FF) will be a transfer of control to $4080 rather than $5080 as you intended
         // i.e. the 6502 took the low byte of the address from $30FF and the high byte from $3000
 
-        let indirect_ref = if addr & 0x00FF == 0x00FF {
-            let lo = self.mem_read(addr);
-            let hi = self.mem_read(addr & 0xFF00);
-            (hi as u16) << 8 | (lo as u16)
-        } else {
-            self.mem_read_u16(addr)
-        };
-
-        self.register.pc = indirect_ref;
+        let lo = self.read(addr);
+        let hi = self.read((addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF));
+        self.register.pc = (hi as u16) << 8 | (lo as u16);
     }
 
     fn jsr(&mut self) {
+        let lo = self.read(self.register.pc);
+        self.stack_peek();
         self.stack_push_u16(self.register.pc + 2 /* op arg */ - 1 /* spec */);
-        let addr = self.get_operand_address(&AddressingMode::Absolute);
-        self.register.pc = addr;
+        let hi = self.read(self.register.pc.wrapping_add(1));
+        self.register.pc = (hi as u16) << 8 | (lo as u16);
     }
 
     fn rti(&mut self) {
-        self.plp();
+        self.stack_peek();
+        self.pull_status();
         self.register.pc = self.stack_pop_u16();
     }
 
     fn rts(&mut self) {
-        let addr = self.stack_pop_u16() + 1;
-        self.register.pc = addr;
+        self.stack_peek();
+        let addr = self.stack_pop_u16();
+        // The cycle that increments the return address
+        self.read(addr);
+        self.register.pc = addr.wrapping_add(1);
+    }
+
+    /// Reads the value the instruction works on.
+    fn read_operand(&mut self, mode: &AddressingMode) -> u8 {
+        let addr = self.fetch_operand_address(mode, Access::Read);
+        self.read(addr)
+    }
+
+    /// Read-modify-write instructions write the value back unchanged, before they write the
+    /// result. Returns the result.
+    fn modify_operand<F>(&mut self, mode: &AddressingMode, op: F) -> u8
+    where
+        F: FnOnce(&mut CPU, u8) -> u8,
+    {
+        let addr = self.fetch_operand_address(mode, Access::Write);
+        let data = self.read(addr);
+        self.write(addr, data);
+
+        let result = op(self, data);
+        self.write(addr, result);
+        result
     }
 
-    fn page_crossed(&mut self, mode: &AddressingMode) -> bool {
-        let addr = self.register.pc;
+    /// Like `get_operand_address`, reading the operand and the pointers one cycle at a time,
+    /// along with the dummy reads of the addressing mode.
+    fn fetch_operand_address(&mut self, mode: &AddressingMode, access: Access) -> u16 {
+        let pc = self.register.pc;
 
         match mode {
-            AddressingMode::Absolute_X => {
-                let base = self.mem_read_u16(addr);
-                let addr = base.wrapping_add(self.register.read(RegisterField::X) as u16);
-                page_cross(base, addr)
+            AddressingMode::Immediate => pc,
+
+            AddressingMode::ZeroPage => self.read(pc) as u16,
+
+            AddressingMode::Absolute => self.read_u16(pc),
+
+            AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+                let index = match mode {
+                    AddressingMode::ZeroPage_X => self.register.read(RegisterField::X),
+                    _ => self.register.read(RegisterField::Y),
+                };
+                let pos = self.read(pc);
+                // Read while adding the index
+                self.read(pos as u16);
+                pos.wrapping_add(index) as u16
             }
-            AddressingMode::Absolute_Y => {
-                let base = self.mem_read_u16(addr);
-                let addr = base.wrapping_add(self.register.read(RegisterField::Y) as u16);
-                page_cross(base, addr)
+
+            AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+                let index = match mode {
+                    AddressingMode::Absolute_X => self.register.read(RegisterField::X),
+                    _ => self.register.read(RegisterField::Y),
+                };
+                let base = self.read_u16(pc);
+                self.index_address(base, index, access)
             }
+
+            AddressingMode::Indirect_X => {
+                let base = self.read(pc);
+                self.read(base as u16);
+
+                let ptr: u8 = base.wrapping_add(self.register.read(RegisterField::X));
+                let lo = self.read(ptr as u16);
+                let hi = self.read(ptr.wrapping_add(1) as u16);
+                (hi as u16) << 8 | (lo as u16)
+            }
+
             AddressingMode::Indirect_Y => {
-                let base = self.mem_read(addr);
+                let base = self.read(pc);
 
-                let lo = self.mem_read(base as u16);
-                let hi = self.mem_read(base.wrapping_add(1) as u16);
+                let lo = self.read(base as u16);
+                let hi = self.read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
-                let deref = deref_base.wrapping_add(self.register.read(RegisterField::Y) as u16);
-                page_cross(deref, deref_base)
+                self.index_address(deref_base, self.register.read(RegisterField::Y), access)
+            }
+
+            _ => {
+                panic!("mode {:?} is not supported", mode);
             }
-            _ => false,
         }
     }
 
+    /// The CPU adds the index to the low byte first and reads from there, before it fixes up
+    /// the high byte.
+    fn index_address(&mut self, base: u16, index: u8, access: Access) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        if access == Access::Write || page_cross(base, addr) {
+            self.read((base & 0xFF00) | (addr & 0x00FF));
+        }
+        addr
+    }
+
     pub fn get_absolute_address(&mut self, mode: &AddressingMode, addr: u16) -> u16 {
         match mode {
             AddressingMode::ZeroPage => self.mem_read(addr) as u16,
@@ -580,6 +971,7 @@ impl<'a> CPU<'a> {
         }
     }
 
+    /// The address of the operand, without spending any cycles.
     pub fn get_operand_address(&mut self, mode: &AddressingMode) -> u16 {
         match mode {
             AddressingMode::Immediate => self.register.pc,
@@ -587,17 +979,44 @@ impl<'a> CPU<'a> {
         }
     }
 
-    fn interrupt_nmi(&mut self) {
-        self.stack_push_u16(self.register.pc);
+    /// NMI and IRQ, the status is pushed without the break flag. Like BRK, the CPU reads the
+    /// next byte twice and throws it away first.
+    fn interrupt(&mut self, vector: u16) {
+        self.read(self.register.pc);
+        self.read(self.register.pc);
+        self.interrupt_sequence(self.register.pc, vector, false);
+    }
+
+    /// The software interrupt. BRK skips the byte after the opcode, so the handler returns two
+    /// bytes after the BRK, and goes through the IRQ vector with the break flag pushed.
+    fn brk(&mut self) {
+        self.interrupt_sequence(
+            self.register.pc.wrapping_add(1),
+            VECTOR_IRQ_INTERRUPT_HANDLER,
+            true,
+        );
+    }
+
+    /// Pushes the return address and the status, and jumps through the vector. An NMI that comes
+    /// in while BRK or an IRQ push the return address takes over their vector.
+    fn interrupt_sequence(&mut self, return_address: u16, vector: u16, brk: bool) {
+        self.stack_push_u16(return_address);
+
+        let vector = if vector == VECTOR_IRQ_INTERRUPT_HANDLER && self.nmi_pending {
+            self.nmi_pending = false;
+            VECTOR_NMI_INTERRUPT_HANDLER
+        } else {
+            vector
+        };
+
         let mut flag = self.register.status;
-        flag.set(CpuFlags::BREAK, false);
+        flag.set(CpuFlags::BREAK, brk);
         flag.set(CpuFlags::BREAK2, true);
 
         self.stack_push(flag.bits());
         self.register.status.insert(CpuFlags::INTERRUPT_DISABLE);
 
-        self.bus.tick(2);
-        self.register.pc = self.mem_read_u16(VECTOR_NMI_INTERRUPT_HANDLER);
+        self.register.pc = self.read_u16(vector);
     }
 }
 
@@ -627,16 +1046,18 @@ fn ror(data: u8, carry: bool) -> (u8, bool) {
 
 #[cfg(test)]
 mod test {
-    use crate::cpu::{CpuFlags, CPU};
-    use crate::mock_bus::MockBus;
+    use crate::cpu::{CpuFlags, Interrupt, Step, CPU};
+    use crate::mock_bus::{BusAccess, MockBus};
     use crate::opcodes;
-    use crate::opcodes::AddressingMode;
+    use crate::opcodes::{AddressingMode, Instruction};
     use crate::register::{RegisterField, STACK_RESET};
     use core::mem::Mem;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
 
     fn create() -> CPU<'static> {
         let bus = MockBus::new();
-        return CPU::new(Box::new(bus));
+        CPU::new(Box::new(bus))
     }
 
     #[test]
@@ -874,6 +1295,241 @@ mod test {
         assert!(!cpu.register.status.contains(CpuFlags::INTERRUPT_DISABLE));
     }
 
+    fn create_with_irq_line() -> (CPU<'static>, Rc<Cell<bool>>) {
+        let irq_line = Rc::new(Cell::new(false));
+        let bus = MockBus::new_with_irq_line(irq_line.clone());
+        let mut cpu = CPU::new(Box::new(bus));
+        cpu.mem_write_u16(0xFFFE, 0x0700);
+        cpu.mem_write(0x0700, 0xE8); // INX
+        cpu.mem_write(0x0701, 0x00); // BRK
+        (cpu, irq_line)
+    }
+
+    #[test]
+    fn test_irq_is_masked_by_interrupt_disable_flag() {
+        let (mut cpu, irq_line) = create_with_irq_line();
+        irq_line.set(true);
+        cpu.eval(&[0xA9, 0x01, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::X), 0);
+        assert_eq!(cpu.register.pc, 0x0602);
+    }
+
+    #[test]
+    fn test_irq_jumps_to_vector_once_interrupts_are_enabled() {
+        let (mut cpu, irq_line) = create_with_irq_line();
+        irq_line.set(true);
+        cpu.eval(&[0x58, 0xA9, 0x01, 0xA9, 0x02, 0x00]);
+
+        // CLI takes effect after the next instruction, so the first LDA still runs
+        assert_eq!(cpu.register.read(RegisterField::X), 1);
+        assert_eq!(cpu.register.read(RegisterField::A), 1);
+        assert_eq!(cpu.register.pc, 0x0701);
+        assert!(cpu.register.status.contains(CpuFlags::INTERRUPT_DISABLE));
+
+        // The return address and the status without the break flag were pushed
+        assert_eq!(cpu.stack_pop() & 0b0011_0000, 0b0010_0000);
+        assert_eq!(cpu.stack_pop_u16(), 0x0603);
+    }
+
+    #[test]
+    fn test_sei_lets_one_more_irq_through() {
+        let (mut cpu, irq_line) = create_with_irq_line();
+        cpu.eval(&[0x58, 0xEA, 0x78, 0xEA, 0x00]);
+        cpu.register.pc = 0x0600;
+        cpu.run_until(|cpu| {
+            // Raise the IRQ just as SEI runs
+            irq_line.set(cpu.register.pc == 0x0602 || irq_line.get());
+            cpu.mem_read(cpu.register.pc) == 0x00
+        });
+
+        assert_eq!(cpu.register.read(RegisterField::X), 1);
+        assert_eq!(cpu.register.pc, 0x0701);
+        cpu.stack_pop();
+        assert_eq!(cpu.stack_pop_u16(), 0x0603);
+    }
+
+    #[test]
+    fn test_0x00_brk_jumps_to_irq_vector() {
+        let mut cpu = create();
+        cpu.mem_write_u16(0xFFFE, 0x0700);
+        cpu.mem_write(0x0700, 0xE8); // INX
+        cpu.mem_write(0x0701, 0x40); // RTI
+
+        // The INX after BRK is skipped
+        cpu.eval(&[0x58, 0xEA, 0x00, 0xE8, 0xC8]);
+        cpu.run_until(|cpu| cpu.register.pc == 0x0605);
+
+        assert_eq!(cpu.register.read(RegisterField::X), 1);
+        assert_eq!(cpu.register.read(RegisterField::Y), 1);
+        assert!(!cpu.register.status.contains(CpuFlags::INTERRUPT_DISABLE));
+    }
+
+    #[test]
+    fn test_0x00_brk_pushes_break_flag() {
+        let mut cpu = create();
+        cpu.mem_write_u16(0xFFFE, 0x0700);
+        cpu.mem_write(0x0700, 0xEA); // NOP
+        cpu.eval(&[0xEA, 0x00]);
+        cpu.run_until(|cpu| cpu.register.pc == 0x0700);
+
+        assert!(cpu.register.status.contains(CpuFlags::INTERRUPT_DISABLE));
+        assert_eq!(cpu.stack_pop() & 0b0011_0000, 0b0011_0000);
+        assert_eq!(cpu.stack_pop_u16(), 0x0603);
+    }
+
+    #[test]
+    fn test_step_returns_the_instruction() {
+        let mut cpu = create();
+        cpu.mem_write(0x1234, 0x42);
+        cpu.eval(&[0x00]);
+        cpu.mem_write(0x0600, 0xAD); // LDA $1234
+        cpu.mem_write_u16(0x0601, 0x1234);
+
+        let step = cpu.step();
+        assert_eq!(
+            step,
+            Step {
+                interrupt: None,
+                pc: 0x0600,
+                opcode: 0xAD,
+                operand: [0x34, 0x12],
+                len: 3,
+                cycles: 4,
+            }
+        );
+        assert_eq!(cpu.register.read(RegisterField::A), 0x42);
+        assert_eq!(cpu.register.pc, 0x0603);
+    }
+
+    #[test]
+    fn test_step_services_interrupt_first() {
+        let (mut cpu, irq_line) = create_with_irq_line();
+        cpu.eval(&[0x58, 0xEA, 0xEA, 0x00]);
+        irq_line.set(true);
+        cpu.register.pc = 0x0601;
+
+        // The IRQ is seen while the NOP runs, and serviced before the next instruction
+        assert_eq!(cpu.step().interrupt, None);
+        let step = cpu.step();
+        assert_eq!(step.interrupt, Some(Interrupt::Irq));
+        assert_eq!(step.pc, 0x0700);
+        assert_eq!(step.opcode, 0xE8);
+        assert_eq!(step.len, 1);
+        assert_eq!(step.cycles, 9);
+        assert_eq!(cpu.register.read(RegisterField::X), 1);
+    }
+
+    #[test]
+    fn test_run_for_cycles_runs_whole_instructions() {
+        let mut cpu = create();
+        cpu.eval(&[0x00]);
+        for addr in 0x0600..0x0610 {
+            cpu.mem_write(addr, 0xEA); // NOP
+        }
+
+        assert_eq!(cpu.run_for_cycles(5), 6);
+        assert_eq!(cpu.register.pc, 0x0603);
+    }
+
+    fn create_with_access_log(program: &[u8]) -> (CPU<'static>, Rc<RefCell<Vec<BusAccess>>>) {
+        let accesses = Rc::new(RefCell::new(vec![]));
+        let mut cpu = CPU::new(Box::new(MockBus::new_with_access_log(accesses.clone())));
+        for (pos, &e) in program.iter().enumerate() {
+            cpu.mem_write(0x0600 + pos as u16, e)
+        }
+        cpu.register.pc = 0x0600;
+        accesses.borrow_mut().clear();
+        (cpu, accesses)
+    }
+
+    #[test]
+    fn test_cycles_match_opcode_table() {
+        for op in opcodes::CPU_OPCODES.iter() {
+            let is_branch = matches!(
+                op.instruction,
+                Instruction::BCC
+                    | Instruction::BCS
+                    | Instruction::BEQ
+                    | Instruction::BMI
+                    | Instruction::BNE
+                    | Instruction::BPL
+                    | Instruction::BVC
+                    | Instruction::BVS
+            );
+            if is_branch || matches!(op.instruction, Instruction::KIL) {
+                continue;
+            }
+
+            let (mut cpu, _) = create_with_access_log(&[op.code, 0x10, 0x02]);
+            assert_eq!(cpu.step().cycles, op.cycles as usize, "{:#04X}", op.code);
+        }
+    }
+
+    #[test]
+    fn test_read_modify_write_writes_the_value_twice() {
+        // INC $10
+        let (mut cpu, accesses) = create_with_access_log(&[0xE6, 0x10]);
+        cpu.mem_write(0x10, 0x41);
+        accesses.borrow_mut().clear();
+        cpu.step();
+
+        assert_eq!(
+            *accesses.borrow(),
+            vec![
+                BusAccess::Read(0x0600),
+                BusAccess::Read(0x0601),
+                BusAccess::Read(0x0010),
+                BusAccess::Write(0x0010, 0x41),
+                BusAccess::Write(0x0010, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexed_dummy_reads() {
+        // STA $12FF,X
+        let (mut cpu, accesses) = create_with_access_log(&[0x9D, 0xFF, 0x12]);
+        cpu.register.write(RegisterField::X, 0x01);
+        cpu.step();
+        assert_eq!(accesses.borrow()[3], BusAccess::Read(0x1200));
+        assert_eq!(accesses.borrow()[4], BusAccess::Write(0x1300, 0x00));
+
+        // LDA $1200,X stays on the page, so there's no dummy read
+        let (mut cpu, accesses) = create_with_access_log(&[0xBD, 0x00, 0x12]);
+        cpu.register.write(RegisterField::X, 0x01);
+        cpu.step();
+        assert_eq!(
+            *accesses.borrow(),
+            vec![
+                BusAccess::Read(0x0600),
+                BusAccess::Read(0x0601),
+                BusAccess::Read(0x0602),
+                BusAccess::Read(0x1201),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_taken_branch_delays_irq() {
+        // The IRQ shows up while the branch reads its offset
+        let mut cpu = CPU::new(Box::new(MockBus::new_with_irq_from_cycle(2)));
+        cpu.mem_write_u16(0xFFFE, 0x0700);
+        cpu.mem_write(0x0600, 0xF0); // BEQ +0, taken and on the same page
+        cpu.mem_write(0x0601, 0x00);
+        cpu.mem_write(0x0602, 0xEA); // NOP
+        cpu.register.pc = 0x0600;
+        cpu.register.status.remove(CpuFlags::INTERRUPT_DISABLE);
+        cpu.register.status.insert(CpuFlags::ZERO);
+
+        assert_eq!(cpu.step().cycles, 3);
+
+        // The NOP after the branch runs before the IRQ
+        let step = cpu.step();
+        assert_eq!(step.interrupt, None);
+        assert_eq!(step.pc, 0x0602);
+        assert_eq!(cpu.step().interrupt, Some(Interrupt::Irq));
+    }
+
     #[test]
     fn test_0xb8_clear_overflow_flag() {
         let mut cpu = create();
@@ -1441,6 +2097,119 @@ mod test {
         assert!(!cpu.register.status.contains(CpuFlags::NEGATIVE));
     }
 
+    #[test]
+    fn test_0x0b_aac_copies_negative_to_carry() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0xF0, 0x0B, 0x80, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0x80);
+        assert!(cpu.register.status.contains(CpuFlags::NEGATIVE));
+        assert!(cpu.register.status.contains(CpuFlags::CARRY));
+    }
+
+    #[test]
+    fn test_0x4b_asr() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0xFF, 0x4B, 0x03, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0x01);
+        assert!(cpu.register.status.contains(CpuFlags::CARRY));
+    }
+
+    #[test]
+    fn test_0x6b_arr() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0xFF, 0x38, 0x6B, 0xFF, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0xFF);
+        assert!(cpu.register.status.contains(CpuFlags::CARRY));
+        assert!(!cpu.register.status.contains(CpuFlags::OVERFLOW));
+
+        cpu.eval(&[0xA9, 0x40, 0x18, 0x6B, 0xFF, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0x20);
+        assert!(!cpu.register.status.contains(CpuFlags::CARRY));
+        assert!(cpu.register.status.contains(CpuFlags::OVERFLOW));
+    }
+
+    #[test]
+    fn test_0xab_atx_uses_magic_constant() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0x00, 0xAB, 0xF0, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0xE0);
+        assert_eq!(cpu.register.read(RegisterField::X), 0xE0);
+    }
+
+    #[test]
+    fn test_0x8b_xaa_uses_magic_constant() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0x00, 0xA2, 0x0F, 0x8B, 0xFF, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0x0E);
+    }
+
+    #[test]
+    fn test_0xcb_axs() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0x0F, 0xA2, 0x07, 0xCB, 0x02, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::X), 0x05);
+        assert!(cpu.register.status.contains(CpuFlags::CARRY));
+
+        cpu.eval(&[0xA9, 0x0F, 0xA2, 0x07, 0xCB, 0x08, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::X), 0xFF);
+        assert!(!cpu.register.status.contains(CpuFlags::CARRY));
+        assert!(cpu.register.status.contains(CpuFlags::NEGATIVE));
+    }
+
+    #[test]
+    fn test_0xbb_lar() {
+        let mut cpu = create();
+        cpu.mem_write(0x0310, 0xF3);
+        cpu.eval(&[0xA0, 0x10, 0xBB, 0x00, 0x03, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::A), 0xF1);
+        assert_eq!(cpu.register.read(RegisterField::X), 0xF1);
+        assert_eq!(cpu.register.read(RegisterField::SP), 0xF1);
+    }
+
+    #[test]
+    fn test_0x9e_sxa_ands_high_byte_plus_one() {
+        let mut cpu = create();
+        cpu.eval(&[0xA2, 0xFF, 0xA0, 0x01, 0x9E, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0201), 0x03);
+    }
+
+    #[test]
+    fn test_0x9e_sxa_page_cross_replaces_high_byte() {
+        let mut cpu = create();
+        cpu.eval(&[0xA2, 0x01, 0xA0, 0x01, 0x9E, 0xFF, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0300), 0x00);
+        assert_eq!(cpu.mem_read(0x0100), 0x01);
+    }
+
+    #[test]
+    fn test_0x9b_xas() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0xFF, 0xA2, 0x33, 0xA0, 0x00, 0x9B, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.register.read(RegisterField::SP), 0x33);
+        assert_eq!(cpu.mem_read(0x0200), 0x03);
+    }
+
+    #[test]
+    fn test_0x02_kil_jams_until_reset() {
+        let mut cpu = create();
+        cpu.eval(&[0xA9, 0x01, 0x02, 0xA9, 0x02, 0x00]);
+        assert_eq!(cpu.jammed(), Some(0x0602));
+
+        let mut calls = 0;
+        cpu.run_until(|_| {
+            calls += 1;
+            calls == 10
+        });
+        assert_eq!(cpu.jammed(), Some(0x0602));
+        assert_eq!(cpu.register.read(RegisterField::A), 0x01);
+
+        let step = cpu.step();
+        assert_eq!((step.pc, step.opcode, step.len), (0x0602, 0x02, 1));
+
+        cpu.reset();
+        assert_eq!(cpu.jammed(), None);
+    }
+
     #[test]
     fn test_stack_program_multiple_loops() {
         /*
@@ -1477,10 +2246,22 @@ mod test {
     #[test]
     fn test_all_official_operations_implemented() {
         let mut cpu = create();
-        let ref opcodes = *opcodes::CPU_OPCODES;
+        let opcodes = &*opcodes::CPU_OPCODES;
+
+        for op in opcodes {
+            if op.unofficial_name.is_none() {
+                cpu.eval(&[op.code, 0x00, 0x00, 0x00, 0x00]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_unofficial_operations_implemented() {
+        let mut cpu = create();
+        let opcodes = &*opcodes::CPU_OPCODES;
 
         for op in opcodes {
-            if op.unofficial_name == None {
+            if op.unofficial_name.is_some() {
                 cpu.eval(&[op.code, 0x00, 0x00, 0x00, 0x00]);
             }
         }
@@ -1,38 +1,99 @@
-use core::bus::Bus;
+use core::bus::{Bus, BusPeripheral};
 use core::mem::Mem;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub(crate) enum BusAccess {
+    Read(u16),
+    Write(u16, u8),
+}
 
 pub(crate) struct MockBus {
-    memory: [u8; 0xFFFF],
+    memory: [u8; 0x10000],
+    irq_line: Rc<Cell<bool>>,
+    accesses: Rc<RefCell<Vec<BusAccess>>>,
+    irq_from_cycle: Option<usize>,
+    cycles: usize,
 }
 
 impl MockBus {
     pub fn new() -> Self {
+        MockBus::new_with_irq_line(Rc::new(Cell::new(false)))
+    }
+
+    pub fn new_with_irq_line(irq_line: Rc<Cell<bool>>) -> Self {
+        MockBus {
+            memory: [0; 0x10000],
+            irq_line,
+            accesses: Rc::new(RefCell::new(vec![])),
+            irq_from_cycle: None,
+            cycles: 0,
+        }
+    }
+
+    /// The IRQ line goes up at the end of the given cycle.
+    pub fn new_with_irq_from_cycle(cycle: usize) -> Self {
+        MockBus {
+            irq_from_cycle: Some(cycle),
+            ..MockBus::new()
+        }
+    }
+
+    /// Every read and write on the bus is added to `accesses`.
+    pub fn new_with_access_log(accesses: Rc<RefCell<Vec<BusAccess>>>) -> Self {
         MockBus {
-            memory: [0; 0xFFFF],
+            accesses,
+            ..MockBus::new()
         }
     }
 }
 
 impl Mem for MockBus {
     fn mem_read(&mut self, addr: u16) -> u8 {
+        self.accesses.borrow_mut().push(BusAccess::Read(addr));
         self.memory[addr as usize]
     }
 
     fn mem_write(&mut self, addr: u16, value: u8) {
+        self.accesses
+            .borrow_mut()
+            .push(BusAccess::Write(addr, value));
         self.memory[addr as usize] = value
     }
 }
 
+impl Snapshot for MockBus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.memory);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.memory)
+    }
+}
+
 impl Bus<'static> for MockBus {
-    fn tick(&mut self, _: u8) {
-        // Do nothing
+    fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as usize;
     }
 
     fn poll_nmi_status(&mut self) -> Option<u8> {
         None
     }
 
-    fn get_clock_cycles_for_peripheral(&self, _: core::bus::BusPeripheral) -> usize {
-        123456
+    fn poll_irq_status(&self) -> bool {
+        self.irq_line.get()
+            || self
+                .irq_from_cycle
+                .is_some_and(|cycle| self.cycles >= cycle)
+    }
+
+    fn get_clock_cycles_for_peripheral(&self, peripheral: BusPeripheral) -> usize {
+        match peripheral {
+            BusPeripheral::Cpu => self.cycles,
+            _ => 0,
+        }
     }
 }
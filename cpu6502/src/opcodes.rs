@@ -341,7 +341,7 @@ lazy_static! {
         OpCode::new_unofficial(0x6B, Instruction::ARR, 2, 2, AddressingMode::Immediate, "*ARR"),
         OpCode::new_unofficial(0x4B, Instruction::ASR, 2, 2, AddressingMode::Immediate, "*ASR"),
         OpCode::new_unofficial(0xAB, Instruction::ATX, 2, 2, AddressingMode::Immediate, "*ATX"),
-        OpCode::new_unofficial(0x9F, Instruction::AXA, 2, 5, AddressingMode::Absolute_Y, "*AXA"),
+        OpCode::new_unofficial(0x9F, Instruction::AXA, 3, 5, AddressingMode::Absolute_Y, "*AXA"),
         OpCode::new_unofficial(0x93, Instruction::AXA, 2, 6, AddressingMode::Indirect_Y, "*AXA"),
         OpCode::new_unofficial(0xCB, Instruction::AXS, 2, 2, AddressingMode::Immediate, "*AXS"),
 
@@ -440,7 +440,7 @@ lazy_static! {
         OpCode::new_unofficial(0x53, Instruction::SRE, 2, 8, AddressingMode::Indirect_Y, "*SRE"),
 
         OpCode::new_unofficial(0x9E, Instruction::SXA, 3, 5, AddressingMode::Absolute_Y, "*SXA"),
-        OpCode::new_unofficial(0x9C, Instruction::SYA, 3, 5, AddressingMode::Absolute_X, "*SXA"),
+        OpCode::new_unofficial(0x9C, Instruction::SYA, 3, 5, AddressingMode::Absolute_X, "*SYA"),
 
         OpCode::new_unofficial(0x0C, Instruction::TOP, 3, 4, AddressingMode::Absolute, "*NOP"),
         OpCode::new_unofficial(0x1C, Instruction::TOP, 3, 4 /* +1 if PC */, AddressingMode::Absolute_X, "*NOP"),
@@ -451,7 +451,7 @@ lazy_static! {
         OpCode::new_unofficial(0xFC, Instruction::TOP, 3, 4 /* +1 if PC */, AddressingMode::Absolute_X, "*NOP"),
 
         OpCode::new_unofficial(0x8B, Instruction::XAA, 2, 2, AddressingMode::Immediate, "*XAA"),
-        OpCode::new_unofficial(0x9B, Instruction::XAS, 3, 2, AddressingMode::Absolute_Y, "*XAS"),
+        OpCode::new_unofficial(0x9B, Instruction::XAS, 3, 5, AddressingMode::Absolute_Y, "*XAS"),
     ];
 
     pub static ref OPCODES_MAP: HashMap<u8, &'static OpCode> = {
@@ -1,4 +1,5 @@
 use bitflags::bitflags;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
 
 bitflags! {
     /// # Status Register (P) http://wiki.nesdev.com/w/index.php/Status_flags
@@ -106,3 +107,24 @@ impl Default for Register {
         Register::new()
     }
 }
+
+impl Snapshot for Register {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.a);
+        state.write_u8(self.x);
+        state.write_u8(self.y);
+        state.write_u16(self.pc);
+        state.write_u8(self.sp);
+        state.write_u8(self.status.bits);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.a = state.read_u8()?;
+        self.x = state.read_u8()?;
+        self.y = state.read_u8()?;
+        self.pc = state.read_u16()?;
+        self.sp = state.read_u8()?;
+        self.status = CpuFlags::from_bits_truncate(state.read_u8()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use core::mapper::Mapper;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The battery-backed PRG-RAM of the cartridge, where games keep their saves.
+///
+/// The frontend writes it to a file so saves survive restarts. Writes only happen when the
+/// contents changed since the last one, and at most every `interval` frames while playing.
+pub struct BatteryRam {
+    mapper: Rc<RefCell<dyn Mapper>>,
+    interval: usize,
+    frames: usize,
+    saved: Vec<u8>,
+}
+
+impl BatteryRam {
+    /// None when the board has no PRG-RAM to keep.
+    pub fn new(mapper: Rc<RefCell<dyn Mapper>>, interval: usize) -> Option<Self> {
+        let saved = mapper.borrow().prg_ram()?.to_vec();
+        Some(BatteryRam {
+            mapper,
+            interval: interval.max(1),
+            frames: 0,
+            saved,
+        })
+    }
+
+    /// Fills the PRG-RAM with a save read from a file.
+    pub fn load(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() != self.saved.len() {
+            return Err(format!(
+                "Save is {} bytes, expected {}",
+                data.len(),
+                self.saved.len()
+            ));
+        }
+
+        self.saved = data.to_vec();
+        self.restore();
+        Ok(())
+    }
+
+    /// Called once per frame, at every vblank whether or not the game has NMI on, so saves
+    /// written with NMI off still reach the file. Every `interval` frames returns what needs to
+    /// be written.
+    pub fn on_frame(&mut self) -> Option<Vec<u8>> {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return None;
+        }
+        self.frames = 0;
+        self.take_changes()
+    }
+
+    /// The contents of the PRG-RAM if they changed since they were last taken or loaded.
+    pub fn take_changes(&mut self) -> Option<Vec<u8>> {
+        let mapper = self.mapper.borrow();
+        let ram = mapper.prg_ram()?;
+        if ram == self.saved.as_slice() {
+            return None;
+        }
+
+        self.saved = ram.to_vec();
+        Some(self.saved.clone())
+    }
+
+    /// Puts the last saved contents back, the battery keeps them across a power cycle.
+    pub fn restore(&self) {
+        if let Some(ram) = self.mapper.borrow_mut().prg_ram_mut() {
+            ram.copy_from_slice(&self.saved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::NESBus;
+    use crate::cartridge::test::create_example_rom;
+    use crate::mapper::{create_mapper, MAPPER_CNROM, MAPPER_NROM};
+    use core::bus::Bus;
+    use core::mapper::PRG_RAM_START;
+    use core::mem::Mem;
+    use k9::assert_equal;
+    use ppu::PPU;
+
+    fn battery_ram(interval: usize) -> (Rc<RefCell<dyn Mapper>>, BatteryRam) {
+        let mut rom = create_example_rom();
+        rom.mapper = MAPPER_NROM;
+        let mapper = create_mapper(rom);
+        let battery = BatteryRam::new(mapper.clone(), interval).unwrap();
+        (mapper, battery)
+    }
+
+    #[test]
+    fn test_changes_are_taken_once() {
+        let (mapper, mut battery) = battery_ram(1);
+        assert_equal!(battery.take_changes(), None);
+
+        mapper.borrow_mut().write_prg(PRG_RAM_START + 2, 0x42);
+        let save = battery.take_changes().unwrap();
+        assert_equal!(save.len(), 0x2000);
+        assert_equal!(save[2], 0x42);
+        assert_equal!(battery.take_changes(), None);
+    }
+
+    #[test]
+    fn test_on_frame_waits_for_interval() {
+        let (mapper, mut battery) = battery_ram(3);
+        mapper.borrow_mut().write_prg(PRG_RAM_START, 1);
+
+        assert_equal!(battery.on_frame(), None);
+        assert_equal!(battery.on_frame(), None);
+        assert!(battery.on_frame().is_some());
+    }
+
+    #[test]
+    fn test_on_frame_runs_with_nmi_off() {
+        let battery: Rc<RefCell<Option<BatteryRam>>> = Rc::new(RefCell::new(None));
+        let saves = Rc::new(RefCell::new(vec![]));
+        let gameloop_battery = battery.clone();
+        let gameloop_saves = saves.clone();
+        let mut bus = NESBus::new_with_callback(
+            PPU::new_empty_rom(),
+            Box::new(move |_bus| {
+                if let Some(battery) = gameloop_battery.borrow_mut().as_mut() {
+                    gameloop_saves.borrow_mut().push(battery.on_frame());
+                }
+            }),
+        );
+        let mut rom = create_example_rom();
+        rom.mapper = MAPPER_NROM;
+        bus.load_rom(rom);
+        *battery.borrow_mut() = BatteryRam::new(bus.mapper().unwrap(), 2);
+
+        // The game writes its save with NMI off, like save routines tend to do
+        bus.mem_write(0x2000, 0);
+        bus.mem_write(PRG_RAM_START, 0x42);
+        while saves.borrow().len() < 2 {
+            bus.tick(1);
+        }
+        assert_equal!(saves.borrow()[0], None);
+        assert_equal!(saves.borrow()[1].as_ref().unwrap()[0], 0x42);
+    }
+
+    #[test]
+    fn test_load_and_restore() {
+        let (mapper, mut battery) = battery_ram(1);
+        let mut save = vec![0; 0x2000];
+        save[0x1FFF] = 0x99;
+        battery.load(&save).unwrap();
+        assert_equal!(mapper.borrow().read_prg(0x7FFF), 0x99);
+        assert_equal!(battery.take_changes(), None);
+
+        // A power cycle brings back the RAM of power on, the battery kept the save
+        mapper.borrow_mut().write_prg(0x7FFF, 0);
+        battery.restore();
+        assert_equal!(mapper.borrow().read_prg(0x7FFF), 0x99);
+
+        assert!(battery.load(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_boards_without_prg_ram() {
+        let mut rom = create_example_rom();
+        rom.mapper = MAPPER_CNROM;
+        assert!(BatteryRam::new(create_mapper(rom), 1).is_none());
+    }
+}
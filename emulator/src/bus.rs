@@ -27,10 +27,19 @@
 // |_______________| $0000 |_______________|
 
 use crate::cartridge::Rom;
+use crate::controller::{ControllerDevice, CONTROLLER_PORTS};
 use crate::joypad::Joypad;
+use crate::mapper::create_mapper;
+use crate::zapper::Zapper;
+use apu::{APU, APU_FRAME_COUNTER, APU_STATUS};
 use core::bus::{Bus, BusPeripheral};
+use core::mapper::{Mapper, PRG_RAM_START, PRG_ROM_END};
 use core::mem::Mem;
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
 use ppu::{OAM_DATA_SIZE, PPU};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 const CPU_VRAM_SIZE: usize = 0x800;
 const RAM_START: u16 = 0x0000;
@@ -42,63 +51,183 @@ const PPU_REGISTERS_SIZE: usize = 0x08;
 const PPU_REGISTERS_END: u16 = PPU_REGISTERS_START + (PPU_REGISTERS_SIZE as u16) - 1;
 const PPU_REGISTERS_MIRRORS_START: u16 = PPU_REGISTERS_END + 1;
 const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const PPU_REGISTER_OAM_DATA: u16 = 0x2004;
 const PPU_REGISTER_OAM_DMA: u16 = 0x4014;
 
+const APU_REGISTERS_START: u16 = 0x4000;
+const APU_REGISTERS_END: u16 = 0x4013;
+
 const JOYPAD_1_ADDR: u16 = 0x4016;
 const JOYPAD_2_ADDR: u16 = 0x4017;
 
-const PRG_START: u16 = 0x8000;
-const PRG_END: u16 = 0xFFFF;
-
-pub type GameloopCallback<'call> = Box<dyn FnMut(&PPU, &mut Joypad) + 'call>;
+/// Called once per frame, when the PPU enters vblank. The callback gets the whole bus so the
+/// frontend can render the frame, drain the audio samples and update the controllers.
+pub type GameloopCallback<'call> = Box<dyn FnMut(&mut NESBus<'call>) + 'call>;
 
 pub struct NESBus<'call> {
     cpu_vram: [u8; CPU_VRAM_SIZE],
     pub ppu: PPU,
-    pub rom: Option<Box<Rom>>,
-    pub joypad1: Joypad,
+    pub apu: APU,
+    mapper: Option<Rc<RefCell<dyn Mapper>>>,
+    controllers: [Box<dyn ControllerDevice>; CONTROLLER_PORTS],
 
     pub cycles: usize,
+    // The last value on the CPU data bus, what reads of write-only registers see
+    open_bus: u8,
+    // Written to $4014, the copy runs once the CPU is done with the write cycle
+    oam_dma_page: Option<u8>,
+    oam_dma_active: bool,
+    region: Region,
+    // Left over PPU dots, a fraction of a CPU cycle on PAL
+    ppu_dots_remainder: u16,
     gameloop_callback: GameloopCallback<'call>,
 }
 
 impl<'a> NESBus<'a> {
     pub fn new(ppu: PPU) -> Self {
-        NESBus::new_with_callback(ppu, Box::new(|_ppu, _joypad| {}))
+        NESBus::new_with_callback(ppu, Box::new(|_bus| {}))
     }
 
     pub fn new_with_callback(ppu: PPU, gameloop_callback: GameloopCallback) -> NESBus {
         NESBus {
             cpu_vram: [0; CPU_VRAM_SIZE],
             ppu,
-            rom: None,
-            joypad1: Joypad::new(),
+            apu: APU::new(),
+            mapper: None,
+            controllers: [Box::new(Joypad::new()), Box::new(Joypad::new())],
 
             cycles: 0,
+            open_bus: 0,
+            oam_dma_page: None,
+            oam_dma_active: false,
+            region: Region::Ntsc,
+            ppu_dots_remainder: 0,
             gameloop_callback,
         }
     }
 
-    fn read_prg_rom(&self, mut addr: u16) -> u8 {
-        if let Some(rom) = &self.rom {
-            addr -= PRG_START;
-            if rom.prg_rom.len() == 0x4000 && addr >= 0x4000 {
-                addr %= 0x4000;
-            }
-            rom.prg_rom[addr as usize]
+    /// Inserts the cartridge, the CPU and the PPU access it through its mapper from now on.
+    pub fn load_rom(&mut self, rom: Rom) {
+        let mapper = create_mapper(rom);
+        self.ppu.load_mapper(mapper.clone());
+        self.mapper = Some(mapper);
+    }
+
+    /// The board of the inserted cartridge, shared with the PPU.
+    pub fn mapper(&self) -> Option<Rc<RefCell<dyn Mapper>>> {
+        self.mapper.clone()
+    }
+
+    /// Runs the machine at the speed of the console of `region`. NTSC to begin with.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.set_region(region);
+        self.apu.set_region(region);
+        self.ppu_dots_remainder = 0;
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Plugs a device into the first ($4016) or second ($4017) controller port. Both have a
+    /// joypad to begin with.
+    pub fn plug_controller(&mut self, port: usize, device: Box<dyn ControllerDevice>) {
+        self.controllers[port] = device;
+    }
+
+    /// The joypad of the zero-based player. Players 1 and 2 go into the ports directly, 3 and 4
+    /// are there with a Four Score.
+    pub fn joypad(&self, player: usize) -> Option<&Joypad> {
+        self.controllers
+            .get(player % CONTROLLER_PORTS)?
+            .joypad(player / CONTROLLER_PORTS)
+    }
+
+    pub fn joypad_mut(&mut self, player: usize) -> Option<&mut Joypad> {
+        self.controllers
+            .get_mut(player % CONTROLLER_PORTS)?
+            .joypad_mut(player / CONTROLLER_PORTS)
+    }
+
+    pub fn zapper_mut(&mut self) -> Option<&mut Zapper> {
+        self.controllers
+            .iter_mut()
+            .find_map(|controller| controller.zapper_mut())
+    }
+
+    /// Runs the rest of the console for one CPU cycle.
+    fn clock(&mut self) {
+        self.cycles += 1;
+        self.apu.tick(1);
+
+        let (numerator, denominator) = self.region.ppu_clock_ratio();
+        let dots = numerator + self.ppu_dots_remainder;
+        self.ppu_dots_remainder = dots % denominator;
+        let new_frame = self.ppu.tick((dots / denominator) as u8);
+        if new_frame {
+            // Move the callback out of the bus while it runs, so it can borrow the bus mutably
+            let mut callback = std::mem::replace(&mut self.gameloop_callback, Box::new(|_bus| {}));
+            callback(self);
+            self.gameloop_callback = callback;
+        }
+    }
+
+    /// Like `clock`, and lets the DMC fetch a sample byte if it wants one.
+    fn clock_with_dmc(&mut self) {
+        self.clock();
+        if let Some(addr) = self.apu.poll_dmc_read() {
+            self.dmc_dma(addr);
+        }
+    }
+
+    /// The DMC fetches its sample bytes by halting the CPU: a halt cycle, a dummy cycle, an
+    /// alignment cycle if the fetch would land on a put (even) cycle, and the fetch. In the
+    /// middle of an OAM DMA it only takes the fetch and one cycle to line it up.
+    fn dmc_dma(&mut self, addr: u16) {
+        let stolen = if self.oam_dma_active {
+            2
+        } else if self.cycles % 2 == 1 {
+            3
         } else {
-            0xFF
+            4
+        };
+        for _ in 1..stolen {
+            self.clock();
+        }
+        let data = self.mem_read(addr);
+        self.clock();
+        self.apu.fill_dmc_sample(data);
+    }
+
+    /// Copies a page of CPU memory to OAM through $2004, halting the CPU for 513 cycles, or 514
+    /// when the halt cycle is a get (odd) cycle and the reads need one more to line up.
+    fn oam_dma(&mut self, page: u8) {
+        self.oam_dma_active = true;
+        let halt = if self.cycles % 2 == 1 { 2 } else { 1 };
+        for _ in 0..halt {
+            self.clock_with_dmc();
         }
+
+        let hi = (page as u16) << 8;
+        for i in 0..OAM_DATA_SIZE as u16 {
+            let value = self.mem_read(hi + i);
+            self.clock_with_dmc();
+            self.ppu.mem_write(PPU_REGISTER_OAM_DATA, value);
+            self.clock_with_dmc();
+        }
+        self.oam_dma_active = false;
     }
 }
 
 impl Bus<'static> for NESBus<'static> {
     fn tick(&mut self, cycles: u8) {
-        self.cycles += cycles as usize;
+        for _ in 0..cycles {
+            self.clock_with_dmc();
+        }
 
-        let new_frame = self.ppu.tick(cycles * 3);
-        if new_frame {
-            (self.gameloop_callback)(&self.ppu, &mut self.joypad1);
+        if let Some(page) = self.oam_dma_page.take() {
+            self.oam_dma(page);
         }
     }
 
@@ -106,19 +235,28 @@ impl Bus<'static> for NESBus<'static> {
         self.ppu.nmi_interrupt.take()
     }
 
+    fn poll_irq_status(&self) -> bool {
+        let mapper_irq = self
+            .mapper
+            .as_ref()
+            .is_some_and(|mapper| mapper.borrow().irq());
+        self.apu.irq() || mapper_irq
+    }
+
     fn get_clock_cycles_for_peripheral(&self, peripheral: BusPeripheral) -> usize {
         match peripheral {
             BusPeripheral::Cpu => self.cycles,
             BusPeripheral::Ppu => self.ppu.cycles,
             BusPeripheral::PpuScanlines => self.ppu.scanline as usize,
-            BusPeripheral::Apu => panic!("APU is not supported yet"),
+            BusPeripheral::Apu => self.apu.cycles,
+            BusPeripheral::Frames => self.ppu.frames,
         }
     }
 }
 
 impl Mem for NESBus<'_> {
     fn mem_read(&mut self, addr: u16) -> u8 {
-        match addr {
+        let value = match addr {
             RAM_START..=RAM_MIRRORS_END => {
                 let mirror_down_addr = addr & RAM_MIRRORS_MASK;
                 self.cpu_vram[mirror_down_addr as usize]
@@ -127,49 +265,51 @@ impl Mem for NESBus<'_> {
             PPU_REGISTERS_MIRRORS_START..=PPU_REGISTERS_MIRRORS_END => {
                 self.mem_read(addr & PPU_REGISTERS_END)
             }
-            0x4000..=0x4015 => {
-                // Ignore APU
-                0xFF
-            }
-            JOYPAD_1_ADDR => self.joypad1.read(),
-            JOYPAD_2_ADDR => 0x00,
-            PRG_START..=PRG_END => self.read_prg_rom(addr),
+            // Write-only registers, nothing drives the data bus
+            APU_REGISTERS_START..=PPU_REGISTER_OAM_DMA => self.open_bus,
+            APU_STATUS => self.apu.mem_read(addr),
+            JOYPAD_1_ADDR => self.controllers[0].read(&self.ppu),
+            JOYPAD_2_ADDR => self.controllers[1].read(&self.ppu),
+            PRG_RAM_START..=PRG_ROM_END => match &self.mapper {
+                Some(mapper) => mapper.borrow().read_prg(addr),
+                None => 0xFF,
+            },
             _ => {
                 println!("WARN: Ignoring read 0x{:X}", addr);
                 0x00
             }
-        }
+        };
+        self.open_bus = value;
+        value
     }
 
     fn mem_write(&mut self, addr: u16, value: u8) {
+        self.open_bus = value;
         match addr {
             RAM_START..=RAM_MIRRORS_END => {
                 let mirror_down_addr = addr & RAM_MIRRORS_MASK;
                 self.cpu_vram[mirror_down_addr as usize] = value;
             }
             PPU_REGISTERS_START..=PPU_REGISTERS_END => self.ppu.mem_write(addr, value),
-            PPU_REGISTER_OAM_DMA => {
-                let mut buffer: [u8; OAM_DATA_SIZE] = [0; OAM_DATA_SIZE];
-                let hi: u16 = (value as u16) << 8;
-
-                for (i, item) in buffer.iter_mut().enumerate() {
-                    *item = self.mem_read(hi + (i as u16));
-                }
-
-                self.ppu.write_oam_dma(&buffer);
-            }
+            PPU_REGISTER_OAM_DMA => self.oam_dma_page = Some(value),
             PPU_REGISTERS_MIRRORS_START..=PPU_REGISTERS_MIRRORS_END => {
                 self.mem_write(addr & PPU_REGISTERS_END, value)
             }
-            0x4000..=0x4013 | 0x4015 => {
-                // Ignore APU
-            }
-            JOYPAD_1_ADDR => self.joypad1.write(value),
-            JOYPAD_2_ADDR => {
-                // We only use 1 joy pad
+            APU_REGISTERS_START..=APU_REGISTERS_END | APU_STATUS => self.apu.mem_write(addr, value),
+            JOYPAD_1_ADDR => {
+                // Both controllers share the strobe line
+                for controller in self.controllers.iter_mut() {
+                    controller.write(value);
+                }
             }
-            PRG_START..=PRG_END => {
-                // Ignore writes to PRG for now, it'll be used when we implement mappers.
+            // Reads from $4017 go to the second joy pad, writes to the APU frame counter
+            APU_FRAME_COUNTER => self.apu.mem_write(addr, value),
+            PRG_RAM_START..=PRG_ROM_END => {
+                if let Some(mapper) = &self.mapper {
+                    let mut mapper = mapper.borrow_mut();
+                    mapper.notify_cpu_cycle(self.cycles);
+                    mapper.write_prg(addr, value);
+                }
             }
             _ => {
                 println!("WARN: Ignoring write 0x{:X} = 0x{:X}", addr, value);
@@ -178,9 +318,46 @@ impl Mem for NESBus<'_> {
     }
 }
 
+impl Snapshot for NESBus<'_> {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.cpu_vram);
+        state.write_usize(self.cycles);
+        state.write_u8(self.open_bus);
+        state.write_u16(self.ppu_dots_remainder);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        for controller in &self.controllers {
+            controller.save_state(state);
+        }
+        if let Some(mapper) = &self.mapper {
+            mapper.borrow().save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.cpu_vram)?;
+        self.cycles = state.read_usize()?;
+        self.open_bus = state.read_u8()?;
+        self.ppu_dots_remainder = state.read_u16()?;
+        self.ppu.load_state(state)?;
+        self.apu.load_state(state)?;
+        for controller in self.controllers.iter_mut() {
+            controller.load_state(state)?;
+        }
+        match &self.mapper {
+            Some(mapper) => mapper.borrow_mut().load_state(state),
+            None => Ok(()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::controller::FourScore;
+    use crate::joypad::JoypadButton;
+    use cpu6502::cpu::CPU;
+    use cpu6502::register::RegisterField;
 
     #[test]
     fn test_ram_read() {
@@ -253,11 +430,301 @@ mod tests {
         assert_eq!(bus.mem_read(0x2007), 0xBB);
     }
 
+    #[test]
+    fn test_apu_status_read() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.mem_write(APU_STATUS, 0b0000_0001);
+        bus.mem_write(0x4003, 0b0000_1000);
+        assert_eq!(bus.mem_read(APU_STATUS), 0b0000_0001);
+    }
+
+    #[test]
+    fn test_apu_frame_counter_write() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.mem_write(APU_FRAME_COUNTER, 0b0100_0000);
+        for _ in 0..10000 {
+            bus.tick(3);
+        }
+        assert_eq!(bus.mem_read(APU_STATUS) & 0b0100_0000, 0);
+        assert_eq!(
+            bus.get_clock_cycles_for_peripheral(BusPeripheral::Apu),
+            30000
+        );
+    }
+
+    #[test]
+    fn test_joypads_share_strobe_and_read_separately() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.joypad_mut(0)
+            .unwrap()
+            .set_pressed(JoypadButton::BUTTON_A);
+        bus.joypad_mut(1)
+            .unwrap()
+            .set_pressed(JoypadButton::BUTTON_B);
+        assert!(bus.joypad_mut(2).is_none());
+
+        bus.mem_write(JOYPAD_1_ADDR, 1);
+        bus.mem_write(JOYPAD_1_ADDR, 0);
+        assert_eq!(bus.mem_read(JOYPAD_1_ADDR), 1);
+        assert_eq!(bus.mem_read(JOYPAD_2_ADDR), 0);
+        assert_eq!(bus.mem_read(JOYPAD_1_ADDR), 0);
+        assert_eq!(bus.mem_read(JOYPAD_2_ADDR), 1);
+    }
+
+    #[test]
+    fn test_zapper_replaces_second_joypad() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.plug_controller(1, Box::new(Zapper::new()));
+        bus.zapper_mut().unwrap().set_trigger(true);
+        assert!(bus.joypad_mut(1).is_none());
+
+        bus.mem_write(JOYPAD_1_ADDR, 1);
+        bus.mem_write(JOYPAD_1_ADDR, 0);
+        assert_eq!(bus.mem_read(JOYPAD_2_ADDR), 0b0001_1000);
+        assert_eq!(bus.mem_read(JOYPAD_2_ADDR), 0b0001_1000);
+    }
+
+    #[test]
+    fn test_four_score_adds_players_three_and_four() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.plug_controller(0, Box::new(FourScore::new(0)));
+        bus.plug_controller(1, Box::new(FourScore::new(1)));
+        bus.joypad_mut(3)
+            .unwrap()
+            .set_pressed(JoypadButton::BUTTON_A);
+
+        bus.mem_write(JOYPAD_1_ADDR, 1);
+        bus.mem_write(JOYPAD_1_ADDR, 0);
+        let report: Vec<u8> = (0..9).map(|_| bus.mem_read(JOYPAD_2_ADDR)).collect();
+        assert_eq!(report, [0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(bus.mem_read(JOYPAD_1_ADDR), 0);
+    }
+
+    #[test]
+    fn test_apu_dmc_reads_sample_through_bus() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(crate::cartridge::test::create_example_rom());
+        bus.mem_write(0x4013, 0x00);
+        bus.mem_write(APU_STATUS, 0b0001_0000);
+        assert_eq!(bus.apu.poll_dmc_read(), Some(0xC000));
+
+        bus.tick(1);
+        assert_eq!(bus.apu.poll_dmc_read(), None);
+        assert_eq!(bus.mem_read(APU_STATUS) & 0b0001_0000, 0);
+    }
+
+    #[test]
+    fn test_gameloop_callback_can_drain_audio_samples() {
+        let samples = std::rc::Rc::new(std::cell::Cell::new(0));
+        let frame_samples = samples.clone();
+        let mut bus = NESBus::new_with_callback(
+            PPU::new_empty_rom(),
+            Box::new(move |bus| frame_samples.set(bus.apu.take_samples().len())),
+        );
+        bus.mem_write(0x2000, 0b1000_0000);
+
+        while samples.get() == 0 {
+            bus.tick(1);
+        }
+
+        // The first vblank starts 241 scanlines in, enough time for ~670 samples at 44.1 kHz
+        assert!(samples.get() > 650);
+        assert!(bus.apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn test_irq_line_follows_apu_frame_counter() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        assert!(!bus.poll_irq_status());
+
+        for _ in 0..10000 {
+            bus.tick(3);
+        }
+        assert!(bus.poll_irq_status());
+
+        bus.mem_read(APU_STATUS);
+        assert!(!bus.poll_irq_status());
+    }
+
+    #[test]
+    fn test_irq_line_follows_mapper() {
+        let mut rom = crate::cartridge::test::create_example_rom();
+        rom.mapper = crate::mapper::MAPPER_MMC3;
+
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(rom);
+        bus.mem_write(APU_FRAME_COUNTER, 0b0100_0000);
+        bus.mem_write(0xC000, 0);
+        bus.mem_write(0xE001, 0);
+        bus.mem_write(0x2000, 0b0000_1000);
+        bus.mem_write(0x2001, 0b0001_1000);
+
+        // One rendered scanline clocks the counter
+        for _ in 0..114 {
+            bus.tick(1);
+        }
+        assert!(bus.poll_irq_status());
+
+        bus.mem_write(0xE000, 0);
+        assert!(!bus.poll_irq_status());
+    }
+
+    #[test]
+    fn test_write_only_registers_read_open_bus() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.mem_write(0x4000, 0x3C);
+        assert_eq!(bus.mem_read(0x4000), 0x3C);
+        bus.mem_write(0x0010, 0x99);
+        assert_eq!(bus.mem_read(0x4014), 0x99);
+
+        // LDA $4000 sees the high byte of its operand, the last byte fetched
+        bus.mem_write(0x0600, 0xAD);
+        bus.mem_write(0x0601, 0x00);
+        bus.mem_write(0x0602, 0x40);
+        let mut cpu = CPU::new(Box::from(bus));
+        cpu.register.pc = 0x0600;
+        cpu.step();
+        assert_eq!(cpu.register.read(RegisterField::A), 0x40);
+    }
+
+    #[test]
+    fn test_oam_dma_copies_page_and_stalls_cpu() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        for i in 0..OAM_DATA_SIZE {
+            bus.mem_write(0x0200 + i as u16, i as u8);
+        }
+
+        bus.mem_write(PPU_REGISTER_OAM_DMA, 0x02);
+        assert_eq!(bus.ppu.oam_data[0x10], 0);
+        // The DMA halts the CPU on cycle 1, an odd one, and spends a cycle lining up the reads
+        bus.tick(1);
+        assert_eq!(bus.cycles, 1 + 514);
+        assert_eq!(bus.ppu.oam_data[0x10], 0x10);
+        assert_eq!(bus.ppu.oam_data[0xFF], 0xFF);
+    }
+
+    #[test]
+    fn test_sta_4014_cycles() {
+        // STA $4014 takes 4 cycles, and the DMA 513 more when it starts on an even cycle
+        for (start, cycles) in [(0, 4 + 513), (1, 4 + 514)] {
+            let mut bus = NESBus::new(PPU::new_empty_rom());
+            bus.mem_write(0x0600, 0x8D);
+            bus.mem_write(0x0601, 0x14);
+            bus.mem_write(0x0602, 0x40);
+            bus.cycles = start;
+            let mut cpu = CPU::new(Box::from(bus));
+            cpu.register.pc = 0x0600;
+
+            assert_eq!(cpu.step().cycles, cycles);
+        }
+    }
+
+    #[test]
+    fn test_dmc_fetch_steals_cycles() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(crate::cartridge::test::create_example_rom());
+        bus.mem_write(APU_STATUS, 0b0001_0000);
+
+        // The DMC asks for its first byte after cycle 0, and halts the CPU on cycle 1. Its
+        // fetch lands on cycle 3, an odd one, without an alignment cycle.
+        bus.tick(1);
+        assert_eq!(bus.cycles, 1 + 3);
+        bus.tick(1);
+        assert_eq!(bus.cycles, 5);
+    }
+
     #[test]
     fn test_cartridge_read() {
         let mut bus = NESBus::new(PPU::new_empty_rom());
-        bus.rom = Some(Box::from(crate::cartridge::test::create_example_rom()));
-        assert_eq!(bus.mem_read(PRG_START + 0x800), 0x01);
+        bus.load_rom(crate::cartridge::test::create_example_rom());
+        assert_eq!(bus.mem_read(0x8800), 0x01);
+    }
+
+    #[test]
+    fn test_cartridge_prg_ram_read_and_write() {
+        let mut rom = crate::cartridge::test::create_example_rom();
+        rom.mapper = crate::mapper::MAPPER_NROM;
+
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(rom);
+        bus.mem_write(0x6000, 0xCA);
+        assert_eq!(bus.mem_read(0x6000), 0xCA);
+    }
+
+    #[test]
+    fn test_load_rom_gives_ppu_the_chr_rom() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(crate::cartridge::test::create_example_rom());
+        assert_eq!(bus.ppu.read_chr(0x0000), 0x02);
+        assert_eq!(bus.ppu.mirroring(), core::cartridge::Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_ppu_writes_to_chr_ram() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        let mut rom = crate::cartridge::test::create_example_rom();
+        rom.mapper = crate::mapper::MAPPER_UXROM;
+        rom.chr_rom = vec![0; 0x2000];
+        rom.chr_ram = true;
+        bus.load_rom(rom);
+
+        bus.mem_write(0x2006, 0x10);
+        bus.mem_write(0x2006, 0x20);
+        bus.mem_write(0x2007, 0x66);
+        assert_eq!(bus.ppu.read_chr(0x1020), 0x66);
+    }
+
+    #[test]
+    fn test_ppu_writes_to_chr_rom_are_ignored() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(crate::cartridge::test::create_example_rom());
+
+        bus.mem_write(0x2006, 0x00);
+        bus.mem_write(0x2006, 0x00);
+        bus.mem_write(0x2007, 0x66);
+        assert_eq!(bus.ppu.read_chr(0x0000), 0x02);
+    }
+
+    #[test]
+    fn test_pal_ppu_runs_16_dots_every_5_cpu_cycles() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.set_region(Region::Pal);
+        for _ in 0..5 {
+            bus.tick(1);
+        }
+        assert_eq!(bus.ppu.cycles, 16);
+
+        bus.tick(2);
+        assert_eq!(bus.ppu.cycles, 22);
+        assert_eq!(bus.ppu_dots_remainder, 2);
+    }
+
+    #[test]
+    fn test_cpu_runs_until_frame() {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        // JMP $0000
+        bus.mem_write(0x0000, 0x4C);
+        bus.mem_write(0x0001, 0x00);
+        bus.mem_write(0x0002, 0x00);
+        let mut cpu = CPU::new(Box::from(bus));
+
+        cpu.run_until_frame();
+        assert_eq!(
+            cpu.bus
+                .get_clock_cycles_for_peripheral(BusPeripheral::Frames),
+            1
+        );
+        cpu.run_until_frame();
+        assert_eq!(
+            cpu.bus
+                .get_clock_cycles_for_peripheral(BusPeripheral::Frames),
+            2
+        );
+        assert_eq!(
+            cpu.bus
+                .get_clock_cycles_for_peripheral(BusPeripheral::PpuScanlines),
+            241
+        );
     }
 
     #[test]
@@ -1,27 +1,146 @@
+use crate::mapper;
 use core::cartridge::Mirroring;
+use core::region::Region;
+use core::snapshot::crc32;
 
 const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
 const PRG_ROM_PAGE_SIZE: usize = 0x4000;
 const CHR_ROM_PAGE_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 0x2000;
+const PRG_RAM_SIZE: usize = 0x2000;
 
+/// Which console the CPU and PPU timing of the game is made for.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Timing {
+    /// RP2C02, North America and Japan
+    Ntsc,
+    /// RP2C07, Europe and Australia
+    Pal,
+    /// Runs on both
+    MultiRegion,
+    /// UA6538, the Russian famiclone
+    Dendy,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    /// The extended console type from byte 13 of the NES 2.0 header
+    Extended(u8),
+}
+
+/// The input device the game expects to be plugged in. NES 2.0 lists many more, they are kept
+/// by number.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ExpansionDevice {
+    Unspecified,
+    Joypads,
+    FourScore,
+    Zapper,
+    Other(u8),
+}
+
+impl ExpansionDevice {
+    fn from_header(value: u8) -> Self {
+        match value & 0b0011_1111 {
+            0x00 => ExpansionDevice::Unspecified,
+            0x01 => ExpansionDevice::Joypads,
+            0x02 => ExpansionDevice::FourScore,
+            0x08 => ExpansionDevice::Zapper,
+            value => ExpansionDevice::Other(value),
+        }
+    }
+}
+
+/// A game dumped in the iNES format, or its NES 2.0 extension.
+///
+/// # Header
+///
+/// | Byte | iNES                  | NES 2.0                                  |
+/// |------|-----------------------|------------------------------------------|
+/// | 0-3  | "NES" $1A             | "NES" $1A                                |
+/// | 4    | PRG-ROM size in 16K   | PRG-ROM size, low byte                   |
+/// | 5    | CHR-ROM size in 8K    | CHR-ROM size, low byte                   |
+/// | 6    | Flags 6               | Flags 6                                  |
+/// | 7    | Flags 7               | Flags 7                                  |
+/// | 8    |                       | Mapper bits 8-11, submapper              |
+/// | 9    |                       | PRG-ROM and CHR-ROM size, high nibbles   |
+/// | 10   |                       | PRG-RAM and PRG-NVRAM shift counts       |
+/// | 11   |                       | CHR-RAM and CHR-NVRAM shift counts       |
+/// | 12   |                       | CPU/PPU timing                           |
+/// | 13   |                       | Vs. System or extended console type      |
+/// | 14   |                       | Miscellaneous ROMs                       |
+/// | 15   |                       | Default expansion device                 |
+///
+/// # Flags 6
+///
+/// 7  bit  0
+/// ---- ----
+/// NNNN FTBM
+/// |||| ||||
+/// |||| |||+- Nametable mirroring (0: horizontal; 1: vertical)
+/// |||| ||+-- Battery-backed PRG-RAM at $6000-$7FFF
+/// |||| |+--- 512 byte trainer before the PRG-ROM
+/// |||| +---- Four-screen VRAM
+/// ++++------ Mapper bits 0-3
+///
+/// # Flags 7
+///
+/// 7  bit  0
+/// ---- ----
+/// NNNN VVCC
+/// |||| ||||
+/// |||| ||++- Console type (0: NES; 1: Vs. System; 2: Playchoice 10; 3: extended)
+/// |||| ++--- 2 for NES 2.0, 0 for iNES
+/// ++++------ Mapper bits 4-7
+///
+/// https://www.nesdev.org/wiki/NES_2.0
 pub struct Rom {
     pub prg_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
-    pub mapper: u8,
+    /// The board has no CHR-ROM, `chr_rom` is the CHR-RAM the game draws its tiles into
+    pub chr_ram: bool,
+    pub mapper: u16,
+    pub submapper: u8,
     pub screen_mirroring: Mirroring,
+    /// The PRG-RAM is kept alive by a battery, games save to it
+    pub battery: bool,
+    /// Sizes of the RAM on the board in bytes, the NVRAM is the part kept by the battery
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub expansion_device: ExpansionDevice,
+    /// CRC32 of the whole file, identifies the game in save states
+    pub hash: u32,
 }
 
 impl Rom {
     pub fn new(raw: &[u8]) -> Result<Rom, String> {
-        if raw[0..4] != NES_TAG {
+        if raw.len() < HEADER_SIZE || raw[0..4] != NES_TAG {
             return Err("File is not in iNES file format".to_string());
         }
 
-        let mapper = (raw[7] & 0b1111_0000) | (raw[6] >> 4);
+        let nes2 = match (raw[7] >> 2) & 0b11 {
+            0 => false,
+            2 => true,
+            _ => return Err("Unknown iNES header version".to_string()),
+        };
 
-        let ines_ver = (raw[7] >> 2) & 0b11;
-        if ines_ver != 0 {
-            return Err("NES2.0 format is not supported".to_string());
+        let mut mapper = ((raw[7] & 0b1111_0000) | (raw[6] >> 4)) as u16;
+        let mut submapper = 0;
+        if nes2 {
+            mapper |= ((raw[8] & 0b1111) as u16) << 8;
+            submapper = raw[8] >> 4;
+        }
+        if !mapper::is_supported(mapper) {
+            return Err(format!("Mapper {} is not supported", mapper));
         }
 
         let four_screen = raw[6] & 0b1000 != 0;
@@ -32,26 +151,128 @@ impl Rom {
             (false, false) => Mirroring::Horizontal,
         };
 
-        let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
-        let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+        let battery = raw[6] & 0b10 != 0;
+
+        let (prg_rom_size, chr_rom_size) = if nes2 {
+            (
+                nes2_rom_size(raw[4], raw[9] & 0b1111, PRG_ROM_PAGE_SIZE),
+                nes2_rom_size(raw[5], raw[9] >> 4, CHR_ROM_PAGE_SIZE),
+            )
+        } else {
+            (
+                raw[4] as usize * PRG_ROM_PAGE_SIZE,
+                raw[5] as usize * CHR_ROM_PAGE_SIZE,
+            )
+        };
+
+        // iNES doesn't tell the RAM sizes, boards get 8K of each kind they might have
+        let (prg_ram_size, prg_nvram_size, chr_ram_size, chr_nvram_size) = if nes2 {
+            (
+                nes2_ram_size(raw[10] & 0b1111),
+                nes2_ram_size(raw[10] >> 4),
+                nes2_ram_size(raw[11] & 0b1111),
+                nes2_ram_size(raw[11] >> 4),
+            )
+        } else {
+            let (prg_ram, prg_nvram) = if battery {
+                (0, PRG_RAM_SIZE)
+            } else {
+                (PRG_RAM_SIZE, 0)
+            };
+            let chr_ram = if chr_rom_size == 0 { CHR_RAM_SIZE } else { 0 };
+            (prg_ram, prg_nvram, chr_ram, 0)
+        };
+
+        let timing = match raw[12] & 0b11 {
+            1 if nes2 => Timing::Pal,
+            2 if nes2 => Timing::MultiRegion,
+            3 if nes2 => Timing::Dendy,
+            _ => Timing::Ntsc,
+        };
+        let console_type = match raw[7] & 0b11 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ if nes2 => ConsoleType::Extended(raw[13] & 0b1111),
+            _ => ConsoleType::Extended(0),
+        };
+        let expansion_device = if nes2 {
+            ExpansionDevice::from_header(raw[15])
+        } else {
+            ExpansionDevice::Unspecified
+        };
 
         let skip_trainer = raw[6] & 0b100 != 0;
 
-        let prg_rom_start = 16 + if skip_trainer { 512 } else { 0 };
-        let chr_rom_start = prg_rom_start + prg_rom_size;
+        let prg_rom_start = HEADER_SIZE + if skip_trainer { TRAINER_SIZE } else { 0 };
+        let chr_rom_start = prg_rom_start.saturating_add(prg_rom_size);
+        if raw.len() < chr_rom_start.saturating_add(chr_rom_size) {
+            return Err("File is shorter than the header says".to_string());
+        }
+
+        let chr_ram = chr_rom_size == 0;
+        let chr_rom = if chr_ram {
+            // Some NES 2.0 headers leave out the size of the CHR-RAM
+            let size = chr_ram_size + chr_nvram_size;
+            vec![0; if size == 0 { CHR_RAM_SIZE } else { size }]
+        } else {
+            raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec()
+        };
 
         Ok(Rom {
             prg_rom: raw[prg_rom_start..(prg_rom_start + prg_rom_size)].to_vec(),
-            chr_rom: raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec(),
+            chr_rom,
+            chr_ram,
             mapper,
+            submapper,
             screen_mirroring,
+            battery,
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size,
+            chr_nvram_size,
+            timing,
+            console_type,
+            expansion_device,
+            hash: crc32(raw),
         })
     }
 }
 
+impl Rom {
+    /// The console to run the game as, NTSC for games that run on both.
+    pub fn region(&self) -> Region {
+        match self.timing {
+            Timing::Ntsc | Timing::MultiRegion => Region::Ntsc,
+            Timing::Pal => Region::Pal,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+}
+
+/// The high nibble $F switches to exponent-multiplier notation, where the low byte is EEEEEEMM
+/// and the size is 2^E * (MM * 2 + 1) bytes. Otherwise the size is in pages.
+fn nes2_rom_size(low: u8, high: u8, page_size: usize) -> usize {
+    if high == 0b1111 {
+        let exponent = (low >> 2) as u32;
+        let multiplier = (low & 0b11) as usize * 2 + 1;
+        2usize.saturating_pow(exponent).saturating_mul(multiplier)
+    } else {
+        ((high as usize) << 8 | low as usize) * page_size
+    }
+}
+
+/// RAM sizes are given as a shift count, 64 << count bytes, where 0 means none.
+fn nes2_ram_size(shift: u8) -> usize {
+    match shift {
+        0 => 0,
+        shift => 64 << shift,
+    }
+}
+
 #[cfg(test)]
 pub mod test {
-    use crate::cartridge::{Rom, CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE};
+    use super::*;
     use core::cartridge::Mirroring;
 
     struct TestRom {
@@ -89,7 +310,7 @@ pub mod test {
             ],
             trainer: None,
             prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
         };
         let rom = create_rom(test_rom);
         Rom::new(&rom).unwrap()
@@ -100,9 +321,154 @@ pub mod test {
         let rom = create_example_rom();
 
         assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
-        assert_eq!(rom.chr_rom, vec!(2; 1 * CHR_ROM_PAGE_SIZE));
+        assert_eq!(rom.chr_rom, vec!(2; CHR_ROM_PAGE_SIZE));
         assert_eq!(rom.mapper, 3);
         assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+        assert!(!rom.battery);
+        assert!(!rom.chr_ram);
+    }
+
+    #[test]
+    pub fn test_chr_ram_without_chr_rom() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![],
+        });
+
+        let rom = Rom::new(&test_rom).unwrap();
+        assert!(rom.chr_ram);
+        assert_eq!(rom.chr_rom, vec!(0; CHR_RAM_SIZE));
+        assert_eq!(rom.mapper, 2);
+    }
+
+    #[test]
+    pub fn test_battery_flag() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+
+        let rom = Rom::new(&test_rom).unwrap();
+        assert!(rom.battery);
+        assert_eq!(rom.mapper, 1);
+    }
+
+    #[test]
+    pub fn test_ines_defaults() {
+        let rom = create_example_rom();
+        assert_eq!(rom.submapper, 0);
+        assert_eq!(rom.prg_ram_size, 0x2000);
+        assert_eq!(rom.prg_nvram_size, 0);
+        assert_eq!(rom.chr_ram_size, 0);
+        assert_eq!(rom.timing, Timing::Ntsc);
+        assert_eq!(rom.region(), Region::Ntsc);
+        assert_eq!(rom.console_type, ConsoleType::Nes);
+        assert_eq!(rom.expansion_device, ExpansionDevice::Unspecified);
+    }
+
+    #[test]
+    pub fn test_nes2_header() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x00, 0x42, 0x08, 0x10, 0x00, 0x70, 0x07, 0x01, 0x00,
+                0x00, 0x02,
+            ],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![],
+        });
+
+        let rom = Rom::new(&test_rom).unwrap();
+        assert_eq!(rom.mapper, 4);
+        assert_eq!(rom.submapper, 1);
+        assert!(rom.battery);
+        assert_eq!(rom.prg_rom.len(), 2 * PRG_ROM_PAGE_SIZE);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+        assert_eq!(rom.chr_nvram_size, 0);
+        assert!(rom.chr_ram);
+        assert_eq!(rom.chr_rom.len(), 0x2000);
+        assert_eq!(rom.timing, Timing::Pal);
+        assert_eq!(rom.region(), Region::Pal);
+        assert_eq!(rom.console_type, ConsoleType::Nes);
+        assert_eq!(rom.expansion_device, ExpansionDevice::FourScore);
+    }
+
+    #[test]
+    pub fn test_nes2_mapper_above_255() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x10, 0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+
+        assert_eq!(
+            Rom::new(&test_rom).err(),
+            Some("Mapper 257 is not supported".to_string())
+        );
+    }
+
+    #[test]
+    pub fn test_nes2_rom_sizes() {
+        assert_eq!(nes2_rom_size(0x02, 0x0, PRG_ROM_PAGE_SIZE), 0x8000);
+        assert_eq!(nes2_rom_size(0x00, 0x1, PRG_ROM_PAGE_SIZE), 0x100 * 0x4000);
+        // 2^4 * 3
+        assert_eq!(nes2_rom_size(0b0001_0001, 0xF, PRG_ROM_PAGE_SIZE), 48);
+        assert_eq!(nes2_ram_size(0), 0);
+        assert_eq!(nes2_ram_size(7), 0x2000);
+    }
+
+    #[test]
+    pub fn test_truncated_file() {
+        let mut test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x31, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+        test_rom.truncate(0x1000);
+
+        assert_eq!(
+            Rom::new(&test_rom).err(),
+            Some("File is shorter than the header says".to_string())
+        );
+        assert!(Rom::new(&[0x4E, 0x45]).is_err());
+    }
+
+    #[test]
+    pub fn test_unsupported_mapper() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0xF1, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+
+        assert_eq!(
+            Rom::new(&test_rom).err(),
+            Some("Mapper 15 is not supported".to_string())
+        );
     }
 
     #[test]
@@ -128,13 +494,13 @@ pub mod test {
             ],
             trainer: Some(vec![0; 512]),
             prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
         });
 
         let rom = Rom::new(&test_rom).unwrap();
 
         assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
-        assert_eq!(rom.chr_rom, vec!(2; 1 * CHR_ROM_PAGE_SIZE));
+        assert_eq!(rom.chr_rom, vec!(2; CHR_ROM_PAGE_SIZE));
         assert_eq!(rom.mapper, 3);
         assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
     }
@@ -0,0 +1,175 @@
+use crate::joypad::Joypad;
+use crate::zapper::Zapper;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+use ppu::PPU;
+
+pub const CONTROLLER_PORTS: usize = 2;
+
+/// Something plugged into one of the two controller ports, read through $4016 and $4017.
+pub trait ControllerDevice: Snapshot {
+    /// Writes to $4016, bit 0 is the strobe line both ports share.
+    fn write(&mut self, data: u8);
+
+    /// Reads of the port, only the low bits carry data. Light guns look at the picture.
+    fn read(&mut self, ppu: &PPU) -> u8;
+
+    /// The joypads held by players, the one plugged in directly is 0 and a multitap adds 1.
+    fn joypad(&self, _index: usize) -> Option<&Joypad> {
+        None
+    }
+
+    fn joypad_mut(&mut self, _index: usize) -> Option<&mut Joypad> {
+        None
+    }
+
+    fn zapper_mut(&mut self) -> Option<&mut Zapper> {
+        None
+    }
+}
+
+/// Nothing plugged in, the port reads as no buttons pressed.
+#[derive(Default)]
+pub struct Unplugged;
+
+impl ControllerDevice for Unplugged {
+    fn write(&mut self, _data: u8) {}
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        0
+    }
+}
+
+impl Snapshot for Unplugged {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// https://www.nesdev.org/wiki/Four_Score
+//
+// Each port reports two joypads and then a signature, one bit per read:
+//
+// | Reads | $4016    | $4017    |
+// |-------|----------|----------|
+// | 1-8   | Player 1 | Player 2 |
+// | 9-16  | Player 3 | Player 4 |
+// | 17-24 | $10      | $20      |
+// | 25-   | 1        | 1        |
+//
+// Unlike the buttons, the signature is sent most significant bit first.
+const REPORT_BITS: u8 = 24;
+const SIGNATURES: [u8; CONTROLLER_PORTS] = [0x10, 0x20];
+
+/// One half of the Four Score (or the NES Satellite), one goes into each port.
+pub struct FourScore {
+    joypads: [Joypad; 2],
+    signature: u8,
+    strobe: bool,
+    bit_index: u8,
+}
+
+impl FourScore {
+    pub fn new(port: usize) -> Self {
+        FourScore {
+            joypads: [Joypad::new(), Joypad::new()],
+            signature: SIGNATURES[port],
+            strobe: false,
+            bit_index: 0,
+        }
+    }
+}
+
+impl ControllerDevice for FourScore {
+    fn write(&mut self, data: u8) {
+        self.strobe = data & 1 == 1;
+        if self.strobe {
+            self.bit_index = 0;
+        }
+    }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        if self.bit_index >= REPORT_BITS {
+            return 1;
+        }
+
+        let bit = self.bit_index % 8;
+        let response = match self.bit_index / 8 {
+            0 => self.joypads[0].buttons().bits() >> bit,
+            1 => self.joypads[1].buttons().bits() >> bit,
+            _ => self.signature >> (7 - bit),
+        } & 1;
+        if !self.strobe {
+            self.bit_index += 1;
+        }
+        response
+    }
+
+    fn joypad(&self, index: usize) -> Option<&Joypad> {
+        self.joypads.get(index)
+    }
+
+    fn joypad_mut(&mut self, index: usize) -> Option<&mut Joypad> {
+        self.joypads.get_mut(index)
+    }
+}
+
+/// Like the joypads, the buttons held come from the live controllers.
+impl Snapshot for FourScore {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.strobe);
+        state.write_u8(self.bit_index);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.strobe = state.read_bool()?;
+        self.bit_index = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::joypad::JoypadButton;
+    use k9::assert_equal;
+
+    fn read_report(device: &mut impl ControllerDevice) -> Vec<u8> {
+        let ppu = PPU::new_empty_rom();
+        device.write(1);
+        device.write(0);
+        (0..26).map(|_| device.read(&ppu)).collect()
+    }
+
+    #[test]
+    fn test_four_score_report() {
+        let mut four_score = FourScore::new(1);
+        four_score
+            .joypad_mut(0)
+            .unwrap()
+            .set_pressed(JoypadButton::BUTTON_A);
+        four_score
+            .joypad_mut(1)
+            .unwrap()
+            .set_pressed(JoypadButton::START);
+        assert!(four_score.joypad_mut(2).is_none());
+
+        let report = read_report(&mut four_score);
+        assert_equal!(report[0..8], [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_equal!(report[8..16], [0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_equal!(report[16..24], [0, 0, 1, 0, 0, 0, 0, 0]);
+        assert_equal!(report[24..], [1, 1]);
+    }
+
+    #[test]
+    fn test_four_score_signatures() {
+        let report = read_report(&mut FourScore::new(0));
+        assert_equal!(report[16..24], [0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_unplugged_port_reads_zero() {
+        assert!(read_report(&mut Unplugged).iter().all(|&bit| bit == 0));
+    }
+}
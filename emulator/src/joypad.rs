@@ -1,4 +1,7 @@
+use crate::controller::ControllerDevice;
 use bitflags::bitflags;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+use ppu::PPU;
 
 bitflags! {
        // https://wiki.nesdev.com/w/index.php/Controller_reading_code
@@ -56,6 +59,15 @@ impl Joypad {
     pub fn set_released(&mut self, button: JoypadButton) {
         self.button_status.set(button, false);
     }
+
+    pub fn buttons(&self) -> JoypadButton {
+        self.button_status
+    }
+
+    /// Replaces all held buttons at once, as when playing back a movie.
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.button_status = buttons;
+    }
 }
 
 impl Default for Joypad {
@@ -64,6 +76,38 @@ impl Default for Joypad {
     }
 }
 
+impl ControllerDevice for Joypad {
+    fn write(&mut self, data: u8) {
+        Joypad::write(self, data)
+    }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        Joypad::read(self)
+    }
+
+    fn joypad(&self, index: usize) -> Option<&Joypad> {
+        (index == 0).then_some(self)
+    }
+
+    fn joypad_mut(&mut self, index: usize) -> Option<&mut Joypad> {
+        (index == 0).then_some(self)
+    }
+}
+
+/// The buttons held come from the live controller, only the serial read-out is saved.
+impl Snapshot for Joypad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.strobe);
+        state.write_u8(self.button_index);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.strobe = state.read_bool()?;
+        self.button_index = state.read_u8()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::joypad::{Joypad, JoypadButton};
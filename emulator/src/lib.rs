@@ -1,4 +1,11 @@
+pub mod battery;
 pub mod bus;
 pub mod cartridge;
+pub mod controller;
 pub mod joypad;
+pub mod mapper;
+pub mod movie;
+pub mod rewind;
+pub mod savestate;
 pub mod trace;
+pub mod zapper;
@@ -0,0 +1,127 @@
+use crate::cartridge::Rom;
+use crate::mapper::{read_banked, write_banked, CHR_ROM_BANK_SIZE};
+use core::cartridge::Mirroring;
+use core::mapper::{Mapper, PRG_ROM_START};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+const PRG_ROM_BANK_SIZE: usize = 0x8000;
+
+/// # AxROM (mapper 7)
+///
+/// Any write to $8000-$FFFF selects the 32K PRG-ROM bank and the nametable.
+///
+/// 7  bit  0
+/// ---- ----
+/// xxxM xPPP
+///    |  |||
+///    |  +++- Select 32 KB PRG ROM bank for CPU $8000-$FFFF
+///    +------ Select 1 KB VRAM page for all 4 nametables
+///
+pub struct AxRom {
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    chr_ram: bool,
+    prg_bank: usize,
+    mirroring: Mirroring,
+}
+
+impl AxRom {
+    pub fn new(rom: Rom) -> Self {
+        AxRom {
+            prg_rom: rom.prg_rom,
+            chr_rom: rom.chr_rom,
+            chr_ram: rom.chr_ram,
+            prg_bank: 0,
+            mirroring: Mirroring::SingleScreenLower,
+        }
+    }
+}
+
+impl Mapper for AxRom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        if addr < PRG_ROM_START {
+            // No PRG-RAM on the board
+            return 0;
+        }
+
+        read_banked(
+            &self.prg_rom,
+            self.prg_bank,
+            PRG_ROM_BANK_SIZE,
+            (addr - PRG_ROM_START) as usize,
+        )
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if addr < PRG_ROM_START {
+            return;
+        }
+
+        self.prg_bank = (value & 0b0000_0111) as usize;
+        self.mirroring = if value & 0b0001_0000 != 0 {
+            Mirroring::SingleScreenUpper
+        } else {
+            Mirroring::SingleScreenLower
+        };
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr_rom.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            write_banked(
+                &mut self.chr_rom,
+                0,
+                CHR_ROM_BANK_SIZE,
+                addr as usize,
+                value,
+            );
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Snapshot for AxRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.write_bytes(&self.chr_rom);
+        }
+        state.write_usize(self.prg_bank);
+        self.mirroring.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if self.chr_ram {
+            state.read_bytes(&mut self.chr_rom)?;
+        }
+        self.prg_bank = state.read_usize()?;
+        self.mirroring.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::create_example_rom;
+
+    #[test]
+    fn test_prg_bank_and_single_screen_select() {
+        let mut rom = create_example_rom();
+        rom.prg_rom = (0..8)
+            .flat_map(|bank| vec![bank as u8; PRG_ROM_BANK_SIZE])
+            .collect();
+        let mut axrom = AxRom::new(rom);
+        assert_eq!(axrom.read_prg(0xFFFF), 0);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+
+        axrom.write_prg(0x8000, 0b0001_0110);
+        assert_eq!(axrom.read_prg(0x8000), 6);
+        assert_eq!(axrom.read_prg(0xFFFF), 6);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+    }
+}
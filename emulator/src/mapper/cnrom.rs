@@ -0,0 +1,112 @@
+use crate::cartridge::Rom;
+use crate::mapper::{read_banked, write_banked, CHR_ROM_BANK_SIZE, PRG_ROM_BANK_SIZE};
+use core::cartridge::Mirroring;
+use core::mapper::{Mapper, PRG_ROM_END, PRG_ROM_START};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+/// # CNROM (mapper 3)
+///
+/// PRG-ROM is fixed like on NROM, any write to $8000-$FFFF selects the 8K CHR-ROM bank.
+pub struct CnRom {
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    chr_ram: bool,
+    mirroring: Mirroring,
+    chr_bank: usize,
+}
+
+impl CnRom {
+    pub fn new(rom: Rom) -> Self {
+        CnRom {
+            prg_rom: rom.prg_rom,
+            chr_rom: rom.chr_rom,
+            chr_ram: rom.chr_ram,
+            mirroring: rom.screen_mirroring,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for CnRom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            PRG_ROM_START..=PRG_ROM_END => {
+                let mut addr = (addr - PRG_ROM_START) as usize;
+                if self.prg_rom.len() == PRG_ROM_BANK_SIZE {
+                    addr %= PRG_ROM_BANK_SIZE;
+                }
+                self.prg_rom[addr]
+            }
+            // No PRG-RAM on the board
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if addr >= PRG_ROM_START {
+            self.chr_bank = value as usize;
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        read_banked(
+            &self.chr_rom,
+            self.chr_bank,
+            CHR_ROM_BANK_SIZE,
+            addr as usize,
+        )
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            write_banked(
+                &mut self.chr_rom,
+                self.chr_bank,
+                CHR_ROM_BANK_SIZE,
+                addr as usize,
+                value,
+            );
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Snapshot for CnRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.write_bytes(&self.chr_rom);
+        }
+        state.write_usize(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if self.chr_ram {
+            state.read_bytes(&mut self.chr_rom)?;
+        }
+        self.chr_bank = state.read_usize()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::create_example_rom;
+
+    #[test]
+    fn test_chr_bank_switching() {
+        let mut rom = create_example_rom();
+        rom.chr_rom = (0..4)
+            .flat_map(|bank| vec![bank as u8; CHR_ROM_BANK_SIZE])
+            .collect();
+        let mut cnrom = CnRom::new(rom);
+        assert_eq!(cnrom.read_chr(0x1FFF), 0);
+
+        cnrom.write_prg(0xFFFF, 2);
+        assert_eq!(cnrom.read_chr(0x0000), 2);
+        assert_eq!(cnrom.read_prg(0x8000), 0x01);
+    }
+}
@@ -0,0 +1,394 @@
+use crate::cartridge::Rom;
+use crate::mapper::{PRG_RAM_SIZE, PRG_ROM_BANK_SIZE};
+use core::cartridge::Mirroring;
+use core::mapper::{Mapper, PRG_RAM_END, PRG_RAM_START, PRG_ROM_END, PRG_ROM_START};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+const CHR_BANK_SIZE: usize = 0x1000;
+
+const SHIFT_REGISTER_RESET: u8 = 0b1_0000;
+
+/// # MMC1 (mapper 1)
+///
+/// The registers are loaded serially, one bit per write to $8000-$FFFF. Bit 7 of the written
+/// value resets the shift register, otherwise bit 0 is shifted in. The fifth write copies the
+/// value into the register selected by bits 13 and 14 of the address. A write on the cycle
+/// right after another is ignored, so read-modify-write instructions only load the first of
+/// their two writes.
+///
+/// $8000-$9FFF  Control
+/// $A000-$BFFF  CHR bank 0
+/// $C000-$DFFF  CHR bank 1
+/// $E000-$FFFF  PRG bank
+///
+/// # Control
+///
+/// 4bit0
+/// -----
+/// CPPMM
+/// |||||
+/// |||++- Mirroring (0: one-screen, lower bank; 1: one-screen, upper bank;
+/// |||               2: vertical; 3: horizontal)
+/// |++--- PRG ROM bank mode (0, 1: switch 32 KB at $8000, ignoring low bit of bank number;
+/// |                         2: fix first bank at $8000 and switch 16 KB bank at $C000;
+/// |                         3: fix last bank at $C000 and switch 16 KB bank at $8000)
+/// +----- CHR ROM bank mode (0: switch 8 KB at a time; 1: switch two separate 4 KB banks)
+///
+/// # PRG bank
+///
+/// 4bit0
+/// -----
+/// RPPPP
+/// |||||
+/// |++++- Select 16 KB PRG ROM bank (low bit ignored in 32 KB mode)
+/// +----- PRG RAM chip enable (0: enabled; 1: disabled)
+///
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    prg_ram: [u8; PRG_RAM_SIZE],
+    chr_rom: Vec<u8>,
+    chr_ram: bool,
+
+    shift_register: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+
+    cpu_cycle: usize,
+    last_write_cycle: Option<usize>,
+}
+
+impl Mmc1 {
+    pub fn new(rom: Rom) -> Self {
+        Mmc1 {
+            prg_rom: rom.prg_rom,
+            prg_ram: [0; PRG_RAM_SIZE],
+            chr_rom: rom.chr_rom,
+            chr_ram: rom.chr_ram,
+
+            shift_register: SHIFT_REGISTER_RESET,
+            // Power on with the last bank fixed at $C000, so the reset vector is reachable
+            control: 0b0_1100,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+
+            cpu_cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    fn write_shift_register(&mut self, addr: u16, value: u8) {
+        if self.last_write_cycle == Some(self.cpu_cycle.wrapping_sub(1)) {
+            return;
+        }
+        self.last_write_cycle = Some(self.cpu_cycle);
+
+        if value & 0b1000_0000 != 0 {
+            self.shift_register = SHIFT_REGISTER_RESET;
+            self.control |= 0b0_1100;
+            return;
+        }
+
+        let complete = self.shift_register & 1 == 1;
+        self.shift_register = (self.shift_register >> 1) | ((value & 1) << 4);
+
+        if complete {
+            let data = self.shift_register;
+            match addr {
+                0x8000..=0x9FFF => self.control = data,
+                0xA000..=0xBFFF => self.chr_bank_0 = data,
+                0xC000..=0xDFFF => self.chr_bank_1 = data,
+                _ => self.prg_bank = data,
+            }
+            self.shift_register = SHIFT_REGISTER_RESET;
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0b1_0000 == 0
+    }
+
+    fn prg_rom_bank_count(&self) -> usize {
+        (self.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1)
+    }
+
+    fn prg_rom_bank(&self, addr: u16) -> usize {
+        let bank = (self.prg_bank & 0b1111) as usize;
+        let upper_half = addr >= 0xC000;
+
+        match ((self.control >> 2) & 0b11, upper_half) {
+            (0, false) | (1, false) => bank & !1,
+            (0, true) | (1, true) => bank | 1,
+            (2, false) => 0,
+            (2, true) => bank,
+            (3, false) => bank,
+            (3, true) => self.prg_rom_bank_count() - 1,
+            _ => unreachable!(),
+        }
+    }
+
+    fn chr_bank(&self, addr: u16) -> usize {
+        let upper_half = addr >= CHR_BANK_SIZE as u16;
+
+        match (self.control & 0b1_0000 != 0, upper_half) {
+            (false, false) => (self.chr_bank_0 & !1) as usize,
+            (false, true) => (self.chr_bank_0 | 1) as usize,
+            (true, false) => self.chr_bank_0 as usize,
+            (true, true) => self.chr_bank_1 as usize,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let offset = self.chr_bank(addr) * CHR_BANK_SIZE + (addr as usize % CHR_BANK_SIZE);
+        offset % self.chr_rom.len()
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            PRG_RAM_START..=PRG_RAM_END if self.prg_ram_enabled() => {
+                self.prg_ram[(addr - PRG_RAM_START) as usize]
+            }
+            PRG_RAM_START..=PRG_RAM_END => 0xFF,
+            PRG_ROM_START..=PRG_ROM_END => {
+                let bank = self.prg_rom_bank(addr) % self.prg_rom_bank_count();
+                let offset = (addr as usize - PRG_ROM_START as usize) % PRG_ROM_BANK_SIZE;
+                self.prg_rom[bank * PRG_ROM_BANK_SIZE + offset]
+            }
+            _ => panic!("Unexpected PRG read on {:04X}", addr),
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        match addr {
+            PRG_RAM_START..=PRG_RAM_END => {
+                if self.prg_ram_enabled() {
+                    self.prg_ram[(addr - PRG_RAM_START) as usize] = value;
+                }
+            }
+            PRG_ROM_START..=PRG_ROM_END => self.write_shift_register(addr, value),
+            _ => panic!("Unexpected PRG write on {:04X}", addr),
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        if self.chr_rom.is_empty() {
+            return 0;
+        }
+
+        self.chr_rom[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        if self.chr_ram && !self.chr_rom.is_empty() {
+            let offset = self.chr_offset(addr);
+            self.chr_rom[offset] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn notify_cpu_cycle(&mut self, cycle: usize) {
+        self.cpu_cycle = cycle;
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.prg_ram)
+    }
+}
+
+impl Snapshot for Mmc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.write_bytes(&self.chr_rom);
+        }
+        state.write_bytes(&self.prg_ram);
+        state.write_u8(self.shift_register);
+        state.write_u8(self.control);
+        state.write_u8(self.chr_bank_0);
+        state.write_u8(self.chr_bank_1);
+        state.write_u8(self.prg_bank);
+        state.write_usize(self.cpu_cycle);
+        state.write_bool(self.last_write_cycle.is_some());
+        state.write_usize(self.last_write_cycle.unwrap_or(0));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if self.chr_ram {
+            state.read_bytes(&mut self.chr_rom)?;
+        }
+        state.read_bytes(&mut self.prg_ram)?;
+        self.shift_register = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.chr_bank_0 = state.read_u8()?;
+        self.chr_bank_1 = state.read_u8()?;
+        self.prg_bank = state.read_u8()?;
+        self.cpu_cycle = state.read_usize()?;
+        let written = state.read_bool()?;
+        let last_write_cycle = state.read_usize()?;
+        self.last_write_cycle = written.then_some(last_write_cycle);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::NESBus;
+    use crate::cartridge::test::create_example_rom;
+    use crate::mapper::MAPPER_MMC1;
+    use core::mem::Mem;
+    use cpu6502::cpu::CPU;
+    use ppu::PPU;
+
+    fn create_mmc1() -> Mmc1 {
+        let mut rom = create_example_rom();
+        rom.prg_rom = (0..8)
+            .flat_map(|bank| vec![bank as u8; PRG_ROM_BANK_SIZE])
+            .collect();
+        rom.chr_rom = (0..8)
+            .flat_map(|bank| vec![bank as u8; CHR_BANK_SIZE])
+            .collect();
+        Mmc1::new(rom)
+    }
+
+    fn write_register(mmc1: &mut Mmc1, addr: u16, value: u8) {
+        for bit in 0..5 {
+            mmc1.write_prg(addr, (value >> bit) & 1);
+        }
+    }
+
+    #[test]
+    fn test_power_on_fixes_last_bank_at_c000() {
+        let mmc1 = create_mmc1();
+        assert_eq!(mmc1.read_prg(0x8000), 0);
+        assert_eq!(mmc1.read_prg(0xFFFF), 7);
+    }
+
+    #[test]
+    fn test_register_is_loaded_on_the_fifth_write() {
+        let mut mmc1 = create_mmc1();
+        for _ in 0..4 {
+            mmc1.write_prg(0xE000, 1);
+        }
+        assert_eq!(mmc1.read_prg(0x8000), 0);
+
+        mmc1.write_prg(0xE000, 0);
+        assert_eq!(mmc1.read_prg(0x8000), 0b0_1111 % 8);
+    }
+
+    #[test]
+    fn test_reset_clears_shift_register() {
+        let mut mmc1 = create_mmc1();
+        mmc1.write_prg(0xE000, 1);
+        mmc1.write_prg(0xE000, 1);
+        mmc1.write_prg(0x8000, 0b1000_0000);
+
+        write_register(&mut mmc1, 0xE000, 3);
+        assert_eq!(mmc1.read_prg(0x8000), 3);
+    }
+
+    #[test]
+    fn test_prg_bank_modes() {
+        let mut mmc1 = create_mmc1();
+        write_register(&mut mmc1, 0xE000, 5);
+
+        // Fix last bank at $C000
+        assert_eq!(mmc1.read_prg(0x8000), 5);
+        assert_eq!(mmc1.read_prg(0xC000), 7);
+
+        // Fix first bank at $8000
+        write_register(&mut mmc1, 0x8000, 0b0_1000);
+        assert_eq!(mmc1.read_prg(0x8000), 0);
+        assert_eq!(mmc1.read_prg(0xC000), 5);
+
+        // 32K mode ignores the low bit
+        write_register(&mut mmc1, 0x8000, 0b0_0000);
+        assert_eq!(mmc1.read_prg(0x8000), 4);
+        assert_eq!(mmc1.read_prg(0xC000), 5);
+    }
+
+    #[test]
+    fn test_chr_bank_modes() {
+        let mut mmc1 = create_mmc1();
+        write_register(&mut mmc1, 0xA000, 3);
+        write_register(&mut mmc1, 0xC000, 6);
+
+        // 8K mode ignores the low bit and CHR bank 1
+        assert_eq!(mmc1.read_chr(0x0000), 2);
+        assert_eq!(mmc1.read_chr(0x1000), 3);
+
+        write_register(&mut mmc1, 0x8000, 0b1_1100);
+        assert_eq!(mmc1.read_chr(0x0000), 3);
+        assert_eq!(mmc1.read_chr(0x1000), 6);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut mmc1 = create_mmc1();
+        for (control, mirroring) in [
+            (0, Mirroring::SingleScreenLower),
+            (1, Mirroring::SingleScreenUpper),
+            (2, Mirroring::Vertical),
+            (3, Mirroring::Horizontal),
+        ] {
+            write_register(&mut mmc1, 0x8000, 0b0_1100 | control);
+            assert_eq!(mmc1.mirroring(), mirroring);
+        }
+    }
+
+    #[test]
+    fn test_prg_ram_can_be_disabled() {
+        let mut mmc1 = create_mmc1();
+        mmc1.write_prg(0x6000, 0x42);
+        assert_eq!(mmc1.read_prg(0x6000), 0x42);
+
+        write_register(&mut mmc1, 0xE000, 0b1_0000);
+        mmc1.write_prg(0x6000, 0x43);
+        assert_eq!(mmc1.read_prg(0x6000), 0xFF);
+
+        write_register(&mut mmc1, 0xE000, 0);
+        assert_eq!(mmc1.read_prg(0x6000), 0x42);
+    }
+
+    #[test]
+    fn test_mmc1_ignores_second_write_of_read_modify_write() {
+        let mut rom = create_example_rom();
+        rom.mapper = MAPPER_MMC1;
+        rom.prg_rom = (0..8)
+            .flat_map(|bank| vec![bank; PRG_ROM_BANK_SIZE])
+            .collect();
+
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(rom);
+        // INC $E000 five times. Each writes back 7 read from the fixed last bank, then 8, and
+        // only the 7 reaches the shift register.
+        for instruction in 0..5 {
+            bus.mem_write(0x0600 + instruction * 3, 0xEE);
+            bus.mem_write(0x0601 + instruction * 3, 0x00);
+            bus.mem_write(0x0602 + instruction * 3, 0xE0);
+        }
+        let mut cpu = CPU::new(Box::from(bus));
+        cpu.register.pc = 0x0600;
+
+        for _ in 0..5 {
+            cpu.step();
+        }
+        // PRG bank 0b1_1111, wrapped to the 8 banks of the ROM
+        assert_eq!(cpu.mem_read(0x8000), 7);
+    }
+}
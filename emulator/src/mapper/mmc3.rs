@@ -0,0 +1,371 @@
+use crate::cartridge::Rom;
+use crate::mapper::{read_banked, write_banked, PRG_RAM_SIZE};
+use core::cartridge::Mirroring;
+use core::mapper::{Mapper, PRG_RAM_END, PRG_RAM_START, PRG_ROM_START};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+/// # MMC3 (mapper 4)
+///
+/// The registers are selected by the address range and whether the address is even or odd.
+///
+/// $8000  Bank select          $8001  Bank data
+/// $A000  Mirroring            $A001  PRG-RAM protect
+/// $C000  IRQ latch            $C001  IRQ reload
+/// $E000  IRQ disable          $E001  IRQ enable
+///
+/// # Bank select
+///
+/// 7  bit  0
+/// ---- ----
+/// CPxx xRRR
+/// ||    |||
+/// ||    +++- Bank register to update on the next write to bank data (R0-R7)
+/// |+-------- PRG ROM bank mode (0: $8000-$9FFF swappable, $C000-$DFFF fixed to second-last
+/// |                             bank; 1: $C000-$DFFF swappable, $8000-$9FFF fixed to
+/// |                             second-last bank)
+/// +--------- CHR A12 inversion (0: two 2 KB banks at $0000-$0FFF, four 1 KB banks at
+///                               $1000-$1FFF; 1: two 2 KB banks at $1000-$1FFF, four 1 KB
+///                               banks at $0000-$0FFF)
+///
+/// # Scanline counter
+///
+/// The counter is clocked on every rising edge of PPU A12. With the usual setup of background
+/// tiles at $0000 and sprites at $1000 that happens once per rendered scanline, when the PPU
+/// starts fetching sprite patterns. When it reaches zero with the IRQ enabled, the IRQ line is
+/// asserted until $E000 is written.
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    prg_ram: [u8; PRG_RAM_SIZE],
+    chr_rom: Vec<u8>,
+    chr_ram: bool,
+    four_screen: bool,
+
+    bank_select: u8,
+    bank_registers: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    prg_ram_write_protected: bool,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_flag: bool,
+    a12: bool,
+}
+
+impl Mmc3 {
+    pub fn new(rom: Rom) -> Self {
+        Mmc3 {
+            prg_rom: rom.prg_rom,
+            prg_ram: [0; PRG_RAM_SIZE],
+            chr_rom: rom.chr_rom,
+            chr_ram: rom.chr_ram,
+            four_screen: rom.screen_mirroring == Mirroring::FourScreen,
+
+            bank_select: 0,
+            bank_registers: [0; 8],
+            mirroring: rom.screen_mirroring,
+            prg_ram_enabled: true,
+            prg_ram_write_protected: false,
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_flag: false,
+            a12: false,
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> usize {
+        let second_last = (self.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(2);
+        let prg_mode = self.bank_select & 0b0100_0000 != 0;
+
+        match ((addr - PRG_ROM_START) as usize / PRG_BANK_SIZE, prg_mode) {
+            (0, false) | (2, true) => self.bank_registers[6] as usize,
+            (0, true) | (2, false) => second_last,
+            (1, _) => self.bank_registers[7] as usize,
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank(&self, addr: u16) -> usize {
+        let mut slot = addr as usize / CHR_BANK_SIZE;
+        if self.bank_select & 0b1000_0000 != 0 {
+            slot ^= 0b100;
+        }
+
+        match slot {
+            0 => (self.bank_registers[0] & !1) as usize,
+            1 => (self.bank_registers[0] | 1) as usize,
+            2 => (self.bank_registers[1] & !1) as usize,
+            3 => (self.bank_registers[1] | 1) as usize,
+            _ => self.bank_registers[slot - 2] as usize,
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_flag = true;
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let even = addr & 1 == 0;
+        match (addr, even) {
+            (0x8000..=0x9FFF, true) => self.bank_select = value,
+            (0x8000..=0x9FFF, false) => {
+                self.bank_registers[(self.bank_select & 0b111) as usize] = value
+            }
+            (0xA000..=0xBFFF, true) => {
+                if !self.four_screen {
+                    self.mirroring = if value & 1 == 0 {
+                        Mirroring::Vertical
+                    } else {
+                        Mirroring::Horizontal
+                    };
+                }
+            }
+            (0xA000..=0xBFFF, false) => {
+                self.prg_ram_enabled = value & 0b1000_0000 != 0;
+                self.prg_ram_write_protected = value & 0b0100_0000 != 0;
+            }
+            (0xC000..=0xDFFF, true) => self.irq_latch = value,
+            (0xC000..=0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, true) => {
+                self.irq_enabled = false;
+                self.irq_flag = false;
+            }
+            (_, false) => self.irq_enabled = true,
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            PRG_RAM_START..=PRG_RAM_END if self.prg_ram_enabled => {
+                self.prg_ram[(addr - PRG_RAM_START) as usize]
+            }
+            PRG_RAM_START..=PRG_RAM_END => 0,
+            _ => read_banked(
+                &self.prg_rom,
+                self.prg_bank(addr),
+                PRG_BANK_SIZE,
+                addr as usize,
+            ),
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        match addr {
+            PRG_RAM_START..=PRG_RAM_END => {
+                if self.prg_ram_enabled && !self.prg_ram_write_protected {
+                    self.prg_ram[(addr - PRG_RAM_START) as usize] = value;
+                }
+            }
+            PRG_ROM_START.. => self.write_register(addr, value),
+            _ => panic!("Unexpected PRG write on {:04X}", addr),
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        read_banked(
+            &self.chr_rom,
+            self.chr_bank(addr),
+            CHR_BANK_SIZE,
+            addr as usize,
+        )
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let bank = self.chr_bank(addr);
+            write_banked(&mut self.chr_rom, bank, CHR_BANK_SIZE, addr as usize, value);
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_flag
+    }
+
+    fn notify_ppu_address(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 && !self.a12 {
+            self.clock_irq_counter();
+        }
+        self.a12 = a12;
+    }
+}
+
+impl Snapshot for Mmc3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.write_bytes(&self.chr_rom);
+        }
+        state.write_bytes(&self.prg_ram);
+        state.write_u8(self.bank_select);
+        state.write_bytes(&self.bank_registers);
+        self.mirroring.save_state(state);
+        state.write_bool(self.prg_ram_enabled);
+        state.write_bool(self.prg_ram_write_protected);
+        state.write_u8(self.irq_latch);
+        state.write_u8(self.irq_counter);
+        state.write_bool(self.irq_reload);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_flag);
+        state.write_bool(self.a12);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if self.chr_ram {
+            state.read_bytes(&mut self.chr_rom)?;
+        }
+        state.read_bytes(&mut self.prg_ram)?;
+        self.bank_select = state.read_u8()?;
+        state.read_bytes(&mut self.bank_registers)?;
+        self.mirroring.load_state(state)?;
+        self.prg_ram_enabled = state.read_bool()?;
+        self.prg_ram_write_protected = state.read_bool()?;
+        self.irq_latch = state.read_u8()?;
+        self.irq_counter = state.read_u8()?;
+        self.irq_reload = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_flag = state.read_bool()?;
+        self.a12 = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::create_example_rom;
+
+    fn create_mmc3() -> Mmc3 {
+        let mut rom = create_example_rom();
+        rom.prg_rom = (0..16)
+            .flat_map(|bank| vec![bank as u8; PRG_BANK_SIZE])
+            .collect();
+        rom.chr_rom = (0..32)
+            .flat_map(|bank| vec![bank as u8; CHR_BANK_SIZE])
+            .collect();
+        Mmc3::new(rom)
+    }
+
+    fn clock_scanline(mmc3: &mut Mmc3) {
+        mmc3.notify_ppu_address(0x0000);
+        mmc3.notify_ppu_address(0x1000);
+    }
+
+    #[test]
+    fn test_prg_bank_modes() {
+        let mut mmc3 = create_mmc3();
+        mmc3.write_prg(0x8000, 6);
+        mmc3.write_prg(0x8001, 3);
+        mmc3.write_prg(0x8000, 7);
+        mmc3.write_prg(0x8001, 4);
+
+        assert_eq!(mmc3.read_prg(0x8000), 3);
+        assert_eq!(mmc3.read_prg(0xA000), 4);
+        assert_eq!(mmc3.read_prg(0xC000), 14);
+        assert_eq!(mmc3.read_prg(0xE000), 15);
+
+        mmc3.write_prg(0x8000, 0b0100_0000);
+        assert_eq!(mmc3.read_prg(0x8000), 14);
+        assert_eq!(mmc3.read_prg(0xA000), 4);
+        assert_eq!(mmc3.read_prg(0xC000), 3);
+        assert_eq!(mmc3.read_prg(0xE000), 15);
+    }
+
+    #[test]
+    fn test_chr_banks_and_a12_inversion() {
+        let mut mmc3 = create_mmc3();
+        for (register, bank) in [(0, 5), (1, 8), (2, 20), (3, 21), (4, 22), (5, 23)] {
+            mmc3.write_prg(0x8000, register);
+            mmc3.write_prg(0x8001, bank);
+        }
+
+        assert_eq!(mmc3.read_chr(0x0000), 4);
+        assert_eq!(mmc3.read_chr(0x0400), 5);
+        assert_eq!(mmc3.read_chr(0x0800), 8);
+        assert_eq!(mmc3.read_chr(0x0C00), 9);
+        assert_eq!(mmc3.read_chr(0x1000), 20);
+        assert_eq!(mmc3.read_chr(0x1C00), 23);
+
+        mmc3.write_prg(0x8000, 0b1000_0000);
+        assert_eq!(mmc3.read_chr(0x0000), 20);
+        assert_eq!(mmc3.read_chr(0x0C00), 23);
+        assert_eq!(mmc3.read_chr(0x1000), 4);
+        assert_eq!(mmc3.read_chr(0x1800), 8);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut mmc3 = create_mmc3();
+        mmc3.write_prg(0xA000, 1);
+        assert_eq!(mmc3.mirroring(), Mirroring::Horizontal);
+        mmc3.write_prg(0xA000, 0);
+        assert_eq!(mmc3.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_irq_fires_after_latch_scanlines() {
+        let mut mmc3 = create_mmc3();
+        mmc3.write_prg(0xC000, 3);
+        mmc3.write_prg(0xC001, 0);
+        mmc3.write_prg(0xE001, 0);
+
+        // The first clock reloads the counter from the latch
+        for _ in 0..3 {
+            clock_scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        clock_scanline(&mut mmc3);
+        assert!(mmc3.irq());
+
+        mmc3.write_prg(0xE000, 0);
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn test_irq_counter_only_counts_rising_edges() {
+        let mut mmc3 = create_mmc3();
+        mmc3.write_prg(0xC000, 1);
+        mmc3.write_prg(0xE001, 0);
+
+        mmc3.notify_ppu_address(0x1000);
+        mmc3.notify_ppu_address(0x1FFF);
+        mmc3.notify_ppu_address(0x1000);
+        assert!(!mmc3.irq());
+
+        clock_scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::cartridge::Rom;
+use crate::mapper::axrom::AxRom;
+use crate::mapper::cnrom::CnRom;
+use crate::mapper::mmc1::Mmc1;
+use crate::mapper::mmc3::Mmc3;
+use crate::mapper::nrom::Nrom;
+use crate::mapper::uxrom::UxRom;
+use core::mapper::Mapper;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub mod axrom;
+pub mod cnrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod nrom;
+pub mod uxrom;
+
+// https://www.nesdev.org/wiki/Mapper
+pub const MAPPER_NROM: u16 = 0;
+pub const MAPPER_MMC1: u16 = 1;
+pub const MAPPER_UXROM: u16 = 2;
+pub const MAPPER_CNROM: u16 = 3;
+pub const MAPPER_MMC3: u16 = 4;
+pub const MAPPER_AXROM: u16 = 7;
+
+const PRG_RAM_SIZE: usize = 0x2000;
+const PRG_ROM_BANK_SIZE: usize = 0x4000;
+const CHR_ROM_BANK_SIZE: usize = 0x2000;
+
+pub fn is_supported(mapper: u16) -> bool {
+    matches!(
+        mapper,
+        MAPPER_NROM | MAPPER_MMC1 | MAPPER_UXROM | MAPPER_CNROM | MAPPER_MMC3 | MAPPER_AXROM
+    )
+}
+
+pub fn create_mapper(rom: Rom) -> Rc<RefCell<dyn Mapper>> {
+    match rom.mapper {
+        MAPPER_NROM => Rc::new(RefCell::new(Nrom::new(rom))),
+        MAPPER_MMC1 => Rc::new(RefCell::new(Mmc1::new(rom))),
+        MAPPER_UXROM => Rc::new(RefCell::new(UxRom::new(rom))),
+        MAPPER_CNROM => Rc::new(RefCell::new(CnRom::new(rom))),
+        MAPPER_MMC3 => Rc::new(RefCell::new(Mmc3::new(rom))),
+        MAPPER_AXROM => Rc::new(RefCell::new(AxRom::new(rom))),
+        mapper => unreachable!("Rom::new rejects unsupported mapper {}", mapper),
+    }
+}
+
+/// Looks up `offset` within a bank of `bank_size` bytes, wrapping the bank number around the
+/// number of banks the memory actually has.
+fn read_banked(memory: &[u8], bank: usize, bank_size: usize, offset: usize) -> u8 {
+    if memory.is_empty() {
+        return 0;
+    }
+
+    memory[banked_index(memory.len(), bank, bank_size, offset)]
+}
+
+/// Like `read_banked`, for the boards that have RAM there.
+fn write_banked(memory: &mut [u8], bank: usize, bank_size: usize, offset: usize, value: u8) {
+    if memory.is_empty() {
+        return;
+    }
+
+    memory[banked_index(memory.len(), bank, bank_size, offset)] = value;
+}
+
+fn banked_index(len: usize, bank: usize, bank_size: usize, offset: usize) -> usize {
+    let bank_count = (len / bank_size).max(1);
+    ((bank % bank_count) * bank_size + offset % bank_size) % len
+}
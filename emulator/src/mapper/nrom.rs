@@ -0,0 +1,122 @@
+use crate::cartridge::Rom;
+use crate::mapper::{write_banked, CHR_ROM_BANK_SIZE, PRG_RAM_SIZE, PRG_ROM_BANK_SIZE};
+use core::cartridge::Mirroring;
+use core::mapper::{Mapper, PRG_RAM_END, PRG_RAM_START, PRG_ROM_END, PRG_ROM_START};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+/// # NROM (mapper 0)
+///
+/// No bank switching. 16K or 32K of PRG-ROM at $8000, where 16K boards mirror it at $C000, and
+/// 8K of CHR-ROM.
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    prg_ram: [u8; PRG_RAM_SIZE],
+    chr_rom: Vec<u8>,
+    chr_ram: bool,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(rom: Rom) -> Self {
+        Nrom {
+            prg_rom: rom.prg_rom,
+            prg_ram: [0; PRG_RAM_SIZE],
+            chr_rom: rom.chr_rom,
+            chr_ram: rom.chr_ram,
+            mirroring: rom.screen_mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            PRG_RAM_START..=PRG_RAM_END => self.prg_ram[(addr - PRG_RAM_START) as usize],
+            PRG_ROM_START..=PRG_ROM_END => {
+                let mut addr = (addr - PRG_ROM_START) as usize;
+                if self.prg_rom.len() == PRG_ROM_BANK_SIZE {
+                    addr %= PRG_ROM_BANK_SIZE;
+                }
+                self.prg_rom[addr]
+            }
+            _ => panic!("Unexpected PRG read on {:04X}", addr),
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if let PRG_RAM_START..=PRG_RAM_END = addr {
+            self.prg_ram[(addr - PRG_RAM_START) as usize] = value;
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr_rom.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            write_banked(
+                &mut self.chr_rom,
+                0,
+                CHR_ROM_BANK_SIZE,
+                addr as usize,
+                value,
+            );
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.prg_ram)
+    }
+}
+
+impl Snapshot for Nrom {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.write_bytes(&self.chr_rom);
+        }
+        state.write_bytes(&self.prg_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if self.chr_ram {
+            state.read_bytes(&mut self.chr_rom)?;
+        }
+        state.read_bytes(&mut self.prg_ram)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::create_example_rom;
+
+    #[test]
+    fn test_16k_prg_rom_is_mirrored() {
+        let mut rom = create_example_rom();
+        rom.prg_rom = vec![0; PRG_ROM_BANK_SIZE];
+        rom.prg_rom[0x10] = 0xAB;
+        let nrom = Nrom::new(rom);
+
+        assert_eq!(nrom.read_prg(0x8010), 0xAB);
+        assert_eq!(nrom.read_prg(0xC010), 0xAB);
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        let mut nrom = Nrom::new(create_example_rom());
+        nrom.write_prg(0x6001, 0x42);
+        nrom.write_prg(0x8001, 0x42);
+
+        assert_eq!(nrom.read_prg(0x6001), 0x42);
+        assert_eq!(nrom.read_prg(0x8001), 0x01);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cartridge::Rom;
+use crate::mapper::{read_banked, write_banked, CHR_ROM_BANK_SIZE, PRG_ROM_BANK_SIZE};
+use core::cartridge::Mirroring;
+use core::mapper::{Mapper, PRG_ROM_END, PRG_ROM_START};
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+const PRG_ROM_FIXED_START: u16 = 0xC000;
+
+/// # UxROM (mapper 2)
+///
+/// $8000-$BFFF  Switchable 16K PRG-ROM bank
+/// $C000-$FFFF  Fixed to the last 16K PRG-ROM bank
+///
+/// Any write to $8000-$FFFF selects the bank at $8000.
+pub struct UxRom {
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    chr_ram: bool,
+    mirroring: Mirroring,
+    prg_bank: usize,
+}
+
+impl UxRom {
+    pub fn new(rom: Rom) -> Self {
+        UxRom {
+            prg_rom: rom.prg_rom,
+            chr_rom: rom.chr_rom,
+            chr_ram: rom.chr_ram,
+            mirroring: rom.screen_mirroring,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for UxRom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            PRG_ROM_START..PRG_ROM_FIXED_START => read_banked(
+                &self.prg_rom,
+                self.prg_bank,
+                PRG_ROM_BANK_SIZE,
+                (addr - PRG_ROM_START) as usize,
+            ),
+            PRG_ROM_FIXED_START..=PRG_ROM_END => read_banked(
+                &self.prg_rom,
+                (self.prg_rom.len() / PRG_ROM_BANK_SIZE).saturating_sub(1),
+                PRG_ROM_BANK_SIZE,
+                (addr - PRG_ROM_FIXED_START) as usize,
+            ),
+            // No PRG-RAM on the board
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if addr >= PRG_ROM_START {
+            self.prg_bank = value as usize;
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr_rom.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            write_banked(
+                &mut self.chr_rom,
+                0,
+                CHR_ROM_BANK_SIZE,
+                addr as usize,
+                value,
+            );
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Snapshot for UxRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.write_bytes(&self.chr_rom);
+        }
+        state.write_usize(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if self.chr_ram {
+            state.read_bytes(&mut self.chr_rom)?;
+        }
+        self.prg_bank = state.read_usize()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::create_example_rom;
+
+    #[test]
+    fn test_switchable_and_fixed_bank() {
+        let mut rom = create_example_rom();
+        rom.prg_rom = (0..8)
+            .flat_map(|bank| vec![bank as u8; PRG_ROM_BANK_SIZE])
+            .collect();
+        let mut uxrom = UxRom::new(rom);
+        assert_eq!(uxrom.read_prg(0x8000), 0);
+        assert_eq!(uxrom.read_prg(0xC000), 7);
+
+        uxrom.write_prg(0x8000, 5);
+        assert_eq!(uxrom.read_prg(0xBFFF), 5);
+        assert_eq!(uxrom.read_prg(0xFFFF), 7);
+    }
+}
@@ -0,0 +1,475 @@
+use crate::bus::NESBus;
+use crate::cartridge::Rom;
+use crate::joypad::JoypadButton;
+use bitflags::bitflags;
+use std::io::Write;
+
+// https://fceux.com/web/help/fm2.html
+const FM2_VERSION: u32 = 3;
+// The FM2 button columns, from the highest bit of `JoypadButton` to the lowest
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+// Two joypads, or four with a Four Score
+pub const MAX_PLAYERS: usize = 4;
+
+bitflags! {
+       // The commands column of an FM2 input line, carried out at the start of the frame
+       #[derive(Default)]
+       pub struct MovieCommand: u8 {
+           const SOFT_RESET        = 0b00000001;
+           const POWER             = 0b00000010;
+       }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub commands: MovieCommand,
+    /// The buttons of players 1 to 4, 3 and 4 only with a Four Score
+    pub joypads: [JoypadButton; MAX_PLAYERS],
+}
+
+impl MovieFrame {
+    pub fn new(commands: MovieCommand) -> Self {
+        MovieFrame {
+            commands,
+            joypads: [JoypadButton::empty(); MAX_PLAYERS],
+        }
+    }
+
+    /// Parses an input line such as `|0|R..U...A|.L.....A||`, which has two more joypads with a
+    /// Four Score.
+    pub fn parse(line: &str, fourscore: bool) -> Result<MovieFrame, String> {
+        let mut fields = line.split('|').skip(1);
+        let commands = fields
+            .next()
+            .and_then(|commands| commands.trim().parse().ok())
+            .ok_or(format!("Invalid movie input line {}", line))?;
+
+        let mut frame = MovieFrame::new(MovieCommand::from_bits_truncate(commands));
+        for joypad in frame.joypads.iter_mut().take(players(fourscore)) {
+            *joypad = match fields.next() {
+                Some(buttons) => parse_buttons(buttons)?,
+                None => return Err(format!("Invalid movie input line {}", line)),
+            };
+        }
+        Ok(frame)
+    }
+
+    pub fn to_fm2(&self, fourscore: bool) -> String {
+        let mut line = format!("|{}|", self.commands.bits());
+        for &joypad in self.joypads.iter().take(players(fourscore)) {
+            line.push_str(&format_buttons(joypad));
+            line.push('|');
+        }
+        // The expansion port is always empty
+        line.push('|');
+        line
+    }
+}
+
+/// A recording of the input of every frame since power on, in FCEUX's FM2 text format, for the
+/// joypads in the two controller ports or on a Four Score.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_filename: String,
+    /// `base64:` followed by the MD5 of the PRG and CHR ROM, see `rom_checksum`
+    pub rom_checksum: String,
+    pub fourscore: bool,
+    /// Recorded on a PAL console
+    pub pal: bool,
+    pub comments: Vec<String>,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(rom_filename: &str, rom: &Rom) -> Self {
+        Movie {
+            rom_filename: rom_filename.to_string(),
+            rom_checksum: rom_checksum(rom),
+            fourscore: false,
+            pal: false,
+            comments: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, String> {
+        let mut movie = Movie {
+            rom_filename: String::new(),
+            rom_checksum: String::new(),
+            fourscore: false,
+            pal: false,
+            comments: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        for line in text.lines() {
+            if line.starts_with('|') {
+                movie.frames.push(MovieFrame::parse(line, movie.fourscore)?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" if value != FM2_VERSION.to_string() => {
+                    return Err(format!("Unsupported movie version {}", value))
+                }
+                "binary" if value != "0" => {
+                    return Err("Binary movies are not supported".to_string())
+                }
+                "savestate" => {
+                    return Err("Movies starting from a save state are not supported".to_string())
+                }
+                "port0" | "port1" if value != "0" && value != "1" => {
+                    return Err(format!("Unsupported controller {} in {}", value, key))
+                }
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "romChecksum" => movie.rom_checksum = value.to_string(),
+                "fourscore" => movie.fourscore = value == "1",
+                "palFlag" => movie.pal = value == "1",
+                "comment" => movie.comments.push(value.to_string()),
+                // The rest of the header describes the recording emulator
+                _ => {}
+            }
+        }
+
+        Ok(movie)
+    }
+
+    /// The header lines, everything before the first input line.
+    pub fn header(&self) -> String {
+        let mut header = format!(
+            "version {}\nemuVersion 0\nrerecordCount 0\npalFlag {}\nromFilename {}\n\
+             romChecksum {}\nguid 00000000-0000-0000-0000-000000000000\nfourscore {}\n\
+             microphone 0\nport0 {}\nport1 {}\nport2 0\nFDS 0\nNewPPU 1\n",
+            FM2_VERSION,
+            self.pal as u8,
+            self.rom_filename,
+            self.rom_checksum,
+            self.fourscore as u8,
+            // With a Four Score the ports aren't described separately
+            !self.fourscore as u8,
+            !self.fourscore as u8
+        );
+        for comment in &self.comments {
+            header.push_str(&format!("comment {}\n", comment));
+        }
+        header
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let mut text = self.header();
+        for frame in &self.frames {
+            text.push_str(&frame.to_fm2(self.fourscore));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Feeds a movie to the controllers, one frame at a time.
+pub struct MoviePlayer {
+    frames: Vec<MovieFrame>,
+    position: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayer {
+            frames: movie.frames,
+            position: 0,
+        }
+    }
+
+    /// Holds the buttons of the next frame and returns the commands the caller has to carry out
+    /// before running it, or None once the movie is over. Call it once before the first frame
+    /// and then at the end of every frame.
+    pub fn play_frame(&mut self, bus: &mut NESBus) -> Option<MovieCommand> {
+        let frame = self.frames.get(self.position)?;
+        self.position += 1;
+        for (player, &buttons) in frame.joypads.iter().enumerate() {
+            if let Some(joypad) = bus.joypad_mut(player) {
+                joypad.set_buttons(buttons);
+            }
+        }
+        Some(frame.commands)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+}
+
+/// Writes a movie while it's being played, one line per frame, so it survives the emulator
+/// being closed at any point.
+pub struct MovieRecorder<W: Write> {
+    writer: W,
+    fourscore: bool,
+}
+
+impl<W: Write> MovieRecorder<W> {
+    pub fn new(mut writer: W, movie: &Movie) -> std::io::Result<Self> {
+        writer.write_all(movie.to_fm2().as_bytes())?;
+        Ok(MovieRecorder {
+            writer,
+            fourscore: movie.fourscore,
+        })
+    }
+
+    /// Records the buttons held for the next frame, with the commands carried out before it.
+    pub fn record_frame(&mut self, commands: MovieCommand, bus: &NESBus) -> std::io::Result<()> {
+        let mut frame = MovieFrame::new(commands);
+        for (player, buttons) in frame.joypads.iter_mut().enumerate() {
+            if let Some(joypad) = bus.joypad(player) {
+                *buttons = joypad.buttons();
+            }
+        }
+        writeln!(self.writer, "{}", frame.to_fm2(self.fourscore))?;
+        self.writer.flush()
+    }
+}
+
+fn players(fourscore: bool) -> usize {
+    match fourscore {
+        true => MAX_PLAYERS,
+        false => 2,
+    }
+}
+
+fn parse_buttons(buttons: &str) -> Result<JoypadButton, String> {
+    if buttons.is_empty() {
+        // No controller in the port
+        return Ok(JoypadButton::empty());
+    }
+    if buttons.len() != FM2_BUTTONS.len() {
+        return Err(format!("Invalid movie buttons {}", buttons));
+    }
+
+    let mut bits = 0;
+    for button in buttons.bytes() {
+        bits <<= 1;
+        if button != b'.' && button != b' ' {
+            bits |= 1;
+        }
+    }
+    Ok(JoypadButton::from_bits_truncate(bits))
+}
+
+fn format_buttons(buttons: JoypadButton) -> String {
+    FM2_BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(index, &button)| match buttons.bits() & (0x80 >> index) {
+            0 => '.',
+            _ => button as char,
+        })
+        .collect()
+}
+
+/// The checksum FCEUX identifies a game by in its movies: the MD5 of the PRG and CHR ROM,
+/// base64 encoded.
+pub fn rom_checksum(rom: &Rom) -> String {
+    let mut data = rom.prg_rom.clone();
+    data.extend_from_slice(&rom.chr_rom);
+    format!("base64:{}", base64(&md5(&data)))
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32)
+        .collect();
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    for chunk in message.chunks(64) {
+        let words: Vec<u32> = chunk
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[(i / 16) * 4 + i % 4]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 16];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+    use ppu::PPU;
+
+    const FM2: &str = "version 3\n\
+        emuVersion 22020\n\
+        rerecordCount 7\n\
+        palFlag 0\n\
+        romFilename Super Mario Bros.\n\
+        romChecksum base64:jjYwGG411HcjG/j9UOVM3Q==\n\
+        guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B\n\
+        fourscore 0\n\
+        microphone 0\n\
+        port0 1\n\
+        port1 1\n\
+        port2 0\n\
+        FDS 0\n\
+        NewPPU 0\n\
+        comment author someone\n\
+        |2|........|........||\n\
+        |0|....T...|........||\n\
+        |1|R......A|.L......||\n";
+
+    #[test]
+    fn test_parse_fm2() {
+        let movie = Movie::parse(FM2).unwrap();
+        assert_equal!(movie.rom_filename, "Super Mario Bros.");
+        assert_equal!(movie.rom_checksum, "base64:jjYwGG411HcjG/j9UOVM3Q==");
+        assert_equal!(movie.comments, vec!["author someone".to_string()]);
+        assert_equal!(
+            movie.frames,
+            vec![
+                MovieFrame::new(MovieCommand::POWER),
+                MovieFrame {
+                    commands: MovieCommand::empty(),
+                    joypads: [
+                        JoypadButton::START,
+                        JoypadButton::empty(),
+                        JoypadButton::empty(),
+                        JoypadButton::empty(),
+                    ],
+                },
+                MovieFrame {
+                    commands: MovieCommand::SOFT_RESET,
+                    joypads: [
+                        JoypadButton::RIGHT | JoypadButton::BUTTON_A,
+                        JoypadButton::LEFT,
+                        JoypadButton::empty(),
+                        JoypadButton::empty(),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fm2_round_trip() {
+        let movie = Movie::parse(FM2).unwrap();
+        let text = movie.to_fm2();
+        assert!(text.contains("|1|R......A|.L......||\n"));
+        assert_equal!(Movie::parse(&text).unwrap(), movie);
+
+        let pal = Movie { pal: true, ..movie };
+        let text = pal.to_fm2();
+        assert!(text.contains("palFlag 1\n"));
+        assert_equal!(Movie::parse(&text).unwrap(), pal);
+    }
+
+    #[test]
+    fn test_four_score_fm2() {
+        let mut movie = Movie::parse(FM2).unwrap();
+        movie.fourscore = true;
+        movie.frames[0].joypads[3] = JoypadButton::DOWN;
+
+        let text = movie.to_fm2();
+        assert!(text.contains("fourscore 1\n"));
+        assert!(text.contains("|2|........|........|........|..D.....||\n"));
+        assert_equal!(Movie::parse(&text).unwrap(), movie);
+    }
+
+    #[test]
+    fn test_parse_invalid_fm2() {
+        assert!(Movie::parse("version 2\n").is_err());
+        assert!(Movie::parse("binary 1\n").is_err());
+        assert!(Movie::parse("|0|RLDU|||\n").is_err());
+        assert!(Movie::parse("|x|........|||\n").is_err());
+    }
+
+    #[test]
+    fn test_play_and_record_frames() {
+        let movie = Movie::parse(FM2).unwrap();
+        let mut player = MoviePlayer::new(movie.clone());
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        let mut recorder = MovieRecorder::new(
+            Vec::new(),
+            &Movie {
+                frames: Vec::new(),
+                ..movie.clone()
+            },
+        )
+        .unwrap();
+
+        while let Some(commands) = player.play_frame(&mut bus) {
+            recorder.record_frame(commands, &bus).unwrap();
+        }
+        assert!(player.is_finished());
+
+        let recorded = String::from_utf8(recorder.writer).unwrap();
+        assert_equal!(Movie::parse(&recorded).unwrap(), movie);
+    }
+
+    #[test]
+    fn test_md5_and_base64() {
+        assert_equal!(base64(&md5(b"")), "1B2M2Y8AsgTpgAmY7PhCfg==");
+        assert_equal!(
+            md5(b"abc"),
+            [
+                0x90, 0x01, 0x50, 0x98, 0x3C, 0xD2, 0x4F, 0xB0, 0xD6, 0x96, 0x3F, 0x7D, 0x28, 0xE1,
+                0x7F, 0x72
+            ]
+        );
+        assert_equal!(base64(b"ab"), "YWI=");
+        assert_equal!(base64(b"a"), "YQ==");
+    }
+}
@@ -0,0 +1,235 @@
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+use std::collections::VecDeque;
+
+/// Keeps the recent past of the machine so it can be played backwards.
+///
+/// Every `interval` frames the whole machine state is captured. Only the newest snapshot is kept
+/// as is, older ones are stored as the XOR against the snapshot after them, run-length encoded.
+/// Most of the machine doesn't change between two frames, so the deltas are mostly runs of
+/// zeros. When the deltas outgrow the memory budget the oldest are dropped.
+pub struct RewindBuffer {
+    interval: usize,
+    frames: usize,
+    budget: usize,
+
+    newest: Option<Vec<u8>>,
+    // The back is the delta from the newest snapshot to the one before it
+    deltas: VecDeque<Vec<u8>>,
+    delta_bytes: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: usize, budget: usize) -> Self {
+        RewindBuffer {
+            interval: interval.max(1),
+            frames: 0,
+            budget,
+
+            newest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    /// Called once per frame while playing, captures the machine every `interval` frames.
+    pub fn on_frame(&mut self, machine: &impl Snapshot) {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return;
+        }
+        self.frames = 0;
+
+        let mut state = StateWriter::new();
+        machine.save_state(&mut state);
+        self.push(state.into_bytes());
+    }
+
+    /// Restores the newest snapshot and forgets it, so the next call goes further back. Returns
+    /// false once there is nothing left to rewind to.
+    pub fn rewind(&mut self, machine: &mut impl Snapshot) -> Result<bool, String> {
+        let Some(snapshot) = self.pop() else {
+            return Ok(false);
+        };
+
+        machine.load_state(&mut StateReader::new(&snapshot))?;
+        self.frames = 0;
+        Ok(true)
+    }
+
+    /// The number of snapshots that can be rewound to.
+    pub fn len(&self) -> usize {
+        match self.newest {
+            Some(_) => self.deltas.len() + 1,
+            None => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    fn push(&mut self, snapshot: Vec<u8>) {
+        if let Some(previous) = self.newest.take() {
+            if previous.len() == snapshot.len() {
+                let delta = encode_delta(&snapshot, &previous);
+                self.delta_bytes += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                // A different machine, the older snapshots don't apply to it
+                self.deltas.clear();
+                self.delta_bytes = 0;
+            }
+        }
+        self.newest = Some(snapshot);
+
+        while self.delta_bytes > self.budget {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.delta_bytes -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            self.delta_bytes -= delta.len();
+            self.newest = Some(decode_delta(&newest, &delta));
+        }
+        Some(newest)
+    }
+}
+
+/// Encodes `previous` XOR `current` as pairs of a zero run length and a literal run, each
+/// length a LEB128 varint followed by the literal bytes.
+fn encode_delta(current: &[u8], previous: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut position = 0;
+    while position < current.len() {
+        let zeros = (position..current.len())
+            .take_while(|&i| current[i] == previous[i])
+            .count();
+        position += zeros;
+
+        let literals = (position..current.len())
+            .take_while(|&i| current[i] != previous[i])
+            .count();
+        write_varint(&mut delta, zeros);
+        write_varint(&mut delta, literals);
+        delta.extend((position..position + literals).map(|i| current[i] ^ previous[i]));
+        position += literals;
+    }
+    delta
+}
+
+fn decode_delta(current: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut previous = current.to_vec();
+    let mut position = 0;
+    let mut input = delta.iter().copied();
+    while let Some(zeros) = read_varint(&mut input) {
+        position += zeros;
+        let literals = read_varint(&mut input).unwrap_or(0);
+        for byte in previous[position..position + literals].iter_mut() {
+            *byte ^= input.next().unwrap_or(0);
+        }
+        position += literals;
+    }
+    previous
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = input.next()?;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Machine {
+        memory: Vec<u8>,
+    }
+
+    impl Snapshot for Machine {
+        fn save_state(&self, state: &mut StateWriter) {
+            state.write_bytes(&self.memory);
+        }
+
+        fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+            state.read_bytes(&mut self.memory)
+        }
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let previous: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let mut current = previous.clone();
+        current[0] = 0xFF;
+        current[500..700].fill(0);
+        current[999] = 0xAA;
+
+        let delta = encode_delta(&current, &previous);
+        assert!(delta.len() < 250);
+        assert_eq!(decode_delta(&current, &delta), previous);
+    }
+
+    #[test]
+    fn test_rewind_plays_snapshots_backwards() {
+        let mut machine = Machine {
+            memory: vec![0; 64],
+        };
+        let mut rewind = RewindBuffer::new(2, 1024);
+
+        for frame in 1..=6u8 {
+            machine.memory[frame as usize] = frame;
+            machine.memory[0] = frame;
+            rewind.on_frame(&machine);
+        }
+        assert_eq!(rewind.len(), 3);
+
+        for frame in [6, 4, 2] {
+            assert_eq!(rewind.rewind(&mut machine), Ok(true));
+            assert_eq!(machine.memory[0], frame);
+            assert_eq!(machine.memory[frame as usize], frame);
+            assert_eq!(machine.memory[frame as usize + 1], 0);
+        }
+        assert_eq!(rewind.rewind(&mut machine), Ok(false));
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn test_rewind_drops_oldest_snapshots_over_budget() {
+        let mut machine = Machine {
+            memory: vec![0; 256],
+        };
+        let mut rewind = RewindBuffer::new(1, 600);
+
+        for frame in 1..=10u8 {
+            machine.memory.fill(frame);
+            rewind.on_frame(&machine);
+        }
+        assert_eq!(rewind.len(), 3);
+
+        for frame in [10, 9, 8] {
+            assert_eq!(rewind.rewind(&mut machine), Ok(true));
+            assert_eq!(machine.memory[100], frame);
+        }
+        assert_eq!(rewind.rewind(&mut machine), Ok(false));
+    }
+}
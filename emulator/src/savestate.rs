@@ -0,0 +1,178 @@
+use core::snapshot::{crc32, Snapshot, StateReader, StateWriter};
+
+const MAGIC: [u8; 4] = *b"NESS";
+/// Bumped whenever a component changes what it saves, old save states are rejected.
+pub const VERSION: u16 = 9;
+const HEADER_SIZE: usize = 14;
+
+/// # Save state file
+///
+/// | Offset | Size | Content                                    |
+/// |--------|------|--------------------------------------------|
+/// | 0      | 4    | "NESS"                                     |
+/// | 4      | 2    | Version                                    |
+/// | 6      | 4    | CRC32 of the ROM file the state belongs to |
+/// | 10     | 4    | CRC32 of the machine state                 |
+/// | 14     |      | Machine state                              |
+///
+/// All numbers are little-endian.
+pub fn save_state(machine: &impl Snapshot, rom_hash: u32) -> Vec<u8> {
+    let mut payload = StateWriter::new();
+    machine.save_state(&mut payload);
+    let payload = payload.into_bytes();
+
+    let mut state = StateWriter::new();
+    state.write_bytes(&MAGIC);
+    state.write_u16(VERSION);
+    state.write_u32(rom_hash);
+    state.write_u32(crc32(&payload));
+    state.write_bytes(&payload);
+    state.into_bytes()
+}
+
+/// Restores a save state made by `save_state`. The header is checked before anything is
+/// restored, and a payload that fails to load half way is rolled back, so the machine is left
+/// untouched by any state that is rejected.
+pub fn load_state(machine: &mut impl Snapshot, rom_hash: u32, data: &[u8]) -> Result<(), String> {
+    let mut state = StateReader::new(data);
+
+    let mut magic = [0; 4];
+    state.read_bytes(&mut magic)?;
+    if magic != MAGIC {
+        return Err("File is not a save state".to_string());
+    }
+
+    let version = state.read_u16()?;
+    if version != VERSION {
+        return Err(format!(
+            "Save state version {} is not supported, expected {}",
+            version, VERSION
+        ));
+    }
+
+    if state.read_u32()? != rom_hash {
+        return Err("Save state belongs to a different ROM".to_string());
+    }
+
+    let checksum = state.read_u32()?;
+    let payload = &data[HEADER_SIZE..];
+    if crc32(payload) != checksum {
+        return Err("Save state is corrupted".to_string());
+    }
+
+    let mut backup = StateWriter::new();
+    machine.save_state(&mut backup);
+
+    let mut payload = StateReader::new(payload);
+    let result = machine.load_state(&mut payload).and_then(|_| {
+        if payload.is_empty() {
+            Ok(())
+        } else {
+            Err("Save state has trailing data".to_string())
+        }
+    });
+    if result.is_err() {
+        machine
+            .load_state(&mut StateReader::new(&backup.into_bytes()))
+            .expect("Restoring the machine's own state can't fail");
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::NESBus;
+    use crate::cartridge::test::create_example_rom;
+    use core::bus::Bus;
+    use core::mem::Mem;
+    use ppu::PPU;
+
+    const ROM_HASH: u32 = 0x1234_5678;
+
+    fn create_bus() -> NESBus<'static> {
+        let mut bus = NESBus::new(PPU::new_empty_rom());
+        bus.load_rom(create_example_rom());
+        bus
+    }
+
+    #[test]
+    fn test_load_state_restores_saved_state() {
+        let mut bus = create_bus();
+        bus.mem_write(0x0010, 0x42);
+        bus.mem_write(0x2000, 0b1001_0000);
+        bus.mem_write(0x4015, 0b0000_0001);
+        bus.mem_write(0x8000, 0x01);
+        for _ in 0..1000 {
+            bus.tick(1);
+        }
+        let state = save_state(&bus, ROM_HASH);
+
+        let mut restored = create_bus();
+        assert_eq!(load_state(&mut restored, ROM_HASH, &state), Ok(()));
+        assert_eq!(save_state(&restored, ROM_HASH), state);
+        assert_eq!(restored.mem_read(0x0010), 0x42);
+        assert_eq!(restored.cycles, 1000);
+        assert_eq!(restored.ppu.scanline, bus.ppu.scanline);
+    }
+
+    #[test]
+    fn test_load_state_rejects_other_roms_and_corruption() {
+        let mut bus = create_bus();
+        bus.mem_write(0x0010, 0x42);
+        let state = save_state(&bus, ROM_HASH);
+
+        let mut other = create_bus();
+        assert_eq!(
+            load_state(&mut other, 0xCAFE, &state),
+            Err("Save state belongs to a different ROM".to_string())
+        );
+
+        let mut corrupted = state.clone();
+        corrupted[HEADER_SIZE + 0x10] ^= 0xFF;
+        assert_eq!(
+            load_state(&mut other, ROM_HASH, &corrupted),
+            Err("Save state is corrupted".to_string())
+        );
+
+        let mut old_version = state.clone();
+        old_version[4] = 0;
+        assert_eq!(
+            load_state(&mut other, ROM_HASH, &old_version),
+            Err(format!(
+                "Save state version 0 is not supported, expected {}",
+                VERSION
+            ))
+        );
+
+        assert_eq!(
+            load_state(&mut other, ROM_HASH, b"NES\x1a"),
+            Err("File is not a save state".to_string())
+        );
+        assert_eq!(other.mem_read(0x0010), 0);
+    }
+
+    #[test]
+    fn test_load_state_rolls_back_truncated_payload() {
+        let mut bus = create_bus();
+        bus.mem_write(0x0010, 0x42);
+        let state = save_state(&bus, ROM_HASH);
+
+        // A valid header around a payload that runs out half way through the machine
+        let payload = &state[HEADER_SIZE..state.len() / 2];
+        let mut truncated = state[..HEADER_SIZE - 4].to_vec();
+        truncated.extend_from_slice(&crc32(payload).to_le_bytes());
+        truncated.extend_from_slice(payload);
+
+        let mut other = create_bus();
+        other.mem_write(0x0010, 0x24);
+        for _ in 0..1000 {
+            other.tick(1);
+        }
+        let before = save_state(&other, ROM_HASH);
+
+        assert!(load_state(&mut other, ROM_HASH, &truncated).is_err());
+        assert_eq!(save_state(&other, ROM_HASH), before);
+        assert_eq!(other.mem_read(0x0010), 0x24);
+    }
+}
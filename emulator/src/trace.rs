@@ -33,7 +33,11 @@ pub fn trace(cpu: &mut CPU) -> String {
         | AddressingMode::NoneAddressing => (0, 0),
         _ => {
             let addr = cpu.get_absolute_address(&ops.mode, begin + 1);
-            (addr, cpu.mem_read(addr))
+            match addr {
+                // Reading APU and I/O registers has side effects, log them as open bus
+                0x4000..=0x401F => (addr, 0xFF),
+                _ => (addr, cpu.mem_read(addr)),
+            }
         }
     };
 
@@ -193,8 +197,9 @@ mod test {
         cpu.register.write(RegisterField::X, 2);
         cpu.register.write(RegisterField::Y, 3);
         let mut result: Vec<String> = vec![];
-        cpu.run_with_callback(|cpu| {
+        cpu.run_until(|cpu| {
             result.push(trace(cpu));
+            result.len() > 3
         });
         assert_eq!(
             "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD PPU:  0,  0 CYC:0",
@@ -218,8 +223,8 @@ mod test {
         bus.mem_write(101, 0x33);
 
         //data
-        bus.mem_write(0x33, 00);
-        bus.mem_write(0x34, 04);
+        bus.mem_write(0x33, 0x00);
+        bus.mem_write(0x34, 0x04);
 
         //target cell
         bus.mem_write(0x400, 0xAA);
@@ -227,8 +232,9 @@ mod test {
         let mut cpu = CPU::new(Box::from(bus));
         cpu.register.pc = 0x64;
         let mut result: Vec<String> = vec![];
-        cpu.run_with_callback(|cpu| {
+        cpu.run_until(|cpu| {
             result.push(trace(cpu));
+            true
         });
         assert_eq!(
             "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD PPU:  0,  0 CYC:0",
@@ -0,0 +1,162 @@
+use crate::controller::ControllerDevice;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+use ppu::{FRAME_HEIGHT, FRAME_WIDTH, PPU};
+
+// https://www.nesdev.org/wiki/Zapper
+//
+// 7  bit  0
+// ---- ----
+// xxxT Lxxx
+//    | |
+//    | +---- Light sensed (0: detected; 1: not detected)
+//    +------ Trigger (0: released; 1: pulled)
+const LIGHT_NOT_SENSED: u8 = 0b0000_1000;
+const TRIGGER_PULLED: u8 = 0b0001_0000;
+
+// The photodiode keeps reporting light for a while after the beam has passed
+const LIGHT_SCANLINES: usize = 20;
+
+/// The light gun. It has no shift register, every read of its port reports the trigger and
+/// whether the photodiode sees a bright pixel where the gun is aimed.
+#[derive(Default)]
+pub struct Zapper {
+    aim: Option<(usize, usize)>,
+    trigger: bool,
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Zapper {
+            aim: None,
+            trigger: false,
+        }
+    }
+
+    /// Aims the gun at a pixel of the picture, or away from the screen with None.
+    pub fn aim(&mut self, aim: Option<(usize, usize)>) {
+        self.aim = aim.filter(|&(x, y)| x < FRAME_WIDTH && y < FRAME_HEIGHT);
+    }
+
+    pub fn set_trigger(&mut self, pulled: bool) {
+        self.trigger = pulled;
+    }
+
+    pub fn read(&self, ppu: &PPU) -> u8 {
+        let mut value = 0;
+        if !self.senses_light(ppu) {
+            value |= LIGHT_NOT_SENSED;
+        }
+        if self.trigger {
+            value |= TRIGGER_PULLED;
+        }
+        value
+    }
+
+    /// The pixel under the gun is seen if the beam drew it during the last few scanlines, and
+    /// it's bright.
+    fn senses_light(&self, ppu: &PPU) -> bool {
+        let Some((x, y)) = self.aim else {
+            return false;
+        };
+
+        let scanline = ppu.scanline as usize;
+        let drawn = scanline > y || (scanline == y && ppu.cycles > x);
+        if !drawn || scanline >= y + LIGHT_SCANLINES {
+            return false;
+        }
+
+        is_bright(ppu.frame_buffer[y * FRAME_WIDTH + x])
+    }
+}
+
+impl ControllerDevice for Zapper {
+    // The Zapper ignores the strobe
+    fn write(&mut self, _data: u8) {}
+
+    fn read(&mut self, ppu: &PPU) -> u8 {
+        Zapper::read(self, ppu)
+    }
+
+    fn zapper_mut(&mut self) -> Option<&mut Zapper> {
+        Some(self)
+    }
+}
+
+/// Where the gun is aimed comes from the live mouse, there's nothing else to save.
+impl Snapshot for Zapper {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Only the two lightest rows of the palette are bright enough, and not their blacks and grays
+/// in columns $D-$F.
+fn is_bright(color: u8) -> bool {
+    color & 0x30 >= 0x20 && color & 0x0F < 0x0D
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    const WHITE: u8 = 0x30;
+    const BLACK: u8 = 0x0F;
+
+    fn ppu_at(scanline: u16, cycles: usize) -> PPU {
+        let mut ppu = PPU::new_empty_rom();
+        ppu.frame_buffer.fill(BLACK);
+        ppu.scanline = scanline;
+        ppu.cycles = cycles;
+        ppu
+    }
+
+    #[test]
+    fn test_trigger() {
+        let ppu = ppu_at(0, 0);
+        let mut zapper = Zapper::new();
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED);
+
+        zapper.set_trigger(true);
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED | TRIGGER_PULLED);
+    }
+
+    #[test]
+    fn test_light_sensed_after_beam_passes() {
+        let mut zapper = Zapper::new();
+        zapper.aim(Some((100, 50)));
+
+        let mut ppu = ppu_at(50, 50);
+        ppu.frame_buffer[50 * FRAME_WIDTH + 100] = WHITE;
+        // Not drawn yet this frame
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED);
+
+        ppu.cycles = 150;
+        assert_equal!(zapper.read(&ppu), 0);
+
+        ppu.scanline = 60;
+        assert_equal!(zapper.read(&ppu), 0);
+
+        // The photodiode has stopped seeing it
+        ppu.scanline = 80;
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED);
+    }
+
+    #[test]
+    fn test_dark_pixels_and_off_screen_aim() {
+        let mut zapper = Zapper::new();
+        let mut ppu = ppu_at(60, 0);
+        ppu.frame_buffer[50 * FRAME_WIDTH + 100] = 0x16;
+
+        zapper.aim(Some((100, 50)));
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED);
+
+        ppu.frame_buffer[50 * FRAME_WIDTH + 100] = WHITE;
+        zapper.aim(Some((300, 50)));
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED);
+        zapper.aim(None);
+        assert_equal!(zapper.read(&ppu), LIGHT_NOT_SENSED);
+    }
+}
@@ -0,0 +1,70 @@
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+/// The background half of the rendering pipeline. Every 8 dots the next tile is loaded into the
+/// low byte of the shift registers, and every dot they shift one pixel to the left. Fine X
+/// selects which bit of the high byte is the current pixel.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct BackgroundShifters {
+    pub next_tile_id: u8,
+    pub next_tile_attribute: u8,
+    pub next_tile_lo: u8,
+    pub next_tile_hi: u8,
+
+    pattern_lo: u16,
+    pattern_hi: u16,
+    attribute_lo: u16,
+    attribute_hi: u16,
+}
+
+impl BackgroundShifters {
+    pub fn load(&mut self) {
+        self.pattern_lo = (self.pattern_lo & 0xFF00) | self.next_tile_lo as u16;
+        self.pattern_hi = (self.pattern_hi & 0xFF00) | self.next_tile_hi as u16;
+
+        // The palette is the same for all 8 pixels of the tile, so inflate it to a whole byte
+        let fill = |bit: u8| if bit != 0 { 0xFF } else { 0x00 };
+        self.attribute_lo = (self.attribute_lo & 0xFF00) | fill(self.next_tile_attribute & 0b01);
+        self.attribute_hi = (self.attribute_hi & 0xFF00) | fill(self.next_tile_attribute & 0b10);
+    }
+
+    pub fn shift(&mut self) {
+        self.pattern_lo <<= 1;
+        self.pattern_hi <<= 1;
+        self.attribute_lo <<= 1;
+        self.attribute_hi <<= 1;
+    }
+
+    /// Returns the (palette, pixel) pair for the current dot.
+    pub fn pixel(&self, fine_x: u8) -> (u8, u8) {
+        let mux = 0x8000 >> fine_x;
+        let bit = |register: u16| (register & mux != 0) as u8;
+
+        let pixel = (bit(self.pattern_hi) << 1) | bit(self.pattern_lo);
+        let palette = (bit(self.attribute_hi) << 1) | bit(self.attribute_lo);
+        (palette, pixel)
+    }
+}
+
+impl Snapshot for BackgroundShifters {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.next_tile_id);
+        state.write_u8(self.next_tile_attribute);
+        state.write_u8(self.next_tile_lo);
+        state.write_u8(self.next_tile_hi);
+        state.write_u16(self.pattern_lo);
+        state.write_u16(self.pattern_hi);
+        state.write_u16(self.attribute_lo);
+        state.write_u16(self.attribute_hi);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.next_tile_id = state.read_u8()?;
+        self.next_tile_attribute = state.read_u8()?;
+        self.next_tile_lo = state.read_u8()?;
+        self.next_tile_hi = state.read_u8()?;
+        self.pattern_lo = state.read_u16()?;
+        self.pattern_hi = state.read_u16()?;
+        self.attribute_lo = state.read_u16()?;
+        self.attribute_hi = state.read_u16()?;
+        Ok(())
+    }
+}
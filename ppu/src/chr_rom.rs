@@ -0,0 +1,39 @@
+use core::cartridge::Mirroring;
+use core::mapper::Mapper;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+
+/// A board with nothing but a fixed CHR-ROM, for running the PPU without a cartridge mapper.
+pub(crate) struct ChrRom {
+    chr_rom: Vec<u8>,
+    mirroring: Mirroring,
+}
+
+impl ChrRom {
+    pub(crate) fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+        ChrRom { chr_rom, mirroring }
+    }
+}
+
+impl Mapper for ChrRom {
+    fn read_prg(&self, _addr: u16) -> u8 {
+        0
+    }
+
+    fn write_prg(&mut self, _addr: u16, _value: u8) {}
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr_rom[addr as usize % self.chr_rom.len()]
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Snapshot for ChrRom {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
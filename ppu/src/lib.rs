@@ -1,17 +1,27 @@
+use crate::background::BackgroundShifters;
+use crate::chr_rom::ChrRom;
 use crate::register::{
     is_read_allowed, is_write_allowed, RegisterField, Registers, PPU_REGISTERS_MAP,
 };
+use crate::sprite::{ScanlineSprite, MAX_SPRITES_PER_SCANLINE};
 use core::cartridge::Mirroring;
+use core::mapper::Mapper;
 use core::mem::Mem;
-
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+mod background;
+mod chr_rom;
+mod pipeline;
 mod register;
 mod registers;
+pub mod sprite;
 
 const PATTERN_TABLE_START: u16 = 0x0000;
 const PATTERN_TABLE_END: u16 = 0x1FFF;
 const NAMETABLE_START: u16 = 0x2000;
-const NAMETABLE_END: u16 = 0x2FFF;
-const NAMETABLE_MIRROR_START: u16 = 0x3000;
 const NAMETABLE_MIRROR_END: u16 = 0x3EFF;
 const PALETTE_RAM_START: u16 = 0x3F00;
 const PALETTE_RAM_END: u16 = 0x3FFF;
@@ -21,21 +31,32 @@ const PPU_VRAM_SIZE: usize = 2048;
 pub const CHR_ROM_BANK_SIZE: usize = 0x1000;
 pub const OAM_DATA_SIZE: usize = 256;
 
+pub const FRAME_WIDTH: usize = 256;
+pub const FRAME_HEIGHT: usize = 240;
+
+const DOTS_PER_SCANLINE: usize = 341;
+const VISIBLE_SCANLINES: u16 = 240;
+
 pub struct PPU {
-    pub chr_rom: Vec<u8>,
+    mapper: Rc<RefCell<dyn Mapper>>,
     pub palette_table: [u8; PALETTE_TABLE_SIZE],
     pub vram: [u8; PPU_VRAM_SIZE],
     pub oam_data: [u8; OAM_DATA_SIZE],
-    pub mirroring: Mirroring,
     pub registers: Registers,
     internal_data_buf: u8,
 
     pub scanline: u16,
     pub cycles: usize,
+    /// Frames that reached vblank, whether or not they raised an NMI. Not part of the state.
+    pub frames: usize,
     pub nmi_interrupt: Option<u8>,
+    odd_frame: bool,
+    region: Region,
 
-    scanline_zero_handled: bool,
-    pub sprite_zero_hit: Option<(u8, u8)>,
+    background: BackgroundShifters,
+    scanline_sprites: Vec<ScanlineSprite>,
+    /// The finished picture, one system palette index per pixel
+    pub frame_buffer: Vec<u8>,
 }
 
 impl PPU {
@@ -44,95 +65,161 @@ impl PPU {
     }
 
     pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+        PPU::new_with_mapper(Rc::new(RefCell::new(ChrRom::new(chr_rom, mirroring))))
+    }
+
+    pub fn new_with_mapper(mapper: Rc<RefCell<dyn Mapper>>) -> Self {
         PPU {
-            chr_rom,
+            mapper,
             palette_table: [0; PALETTE_TABLE_SIZE],
             vram: [0; PPU_VRAM_SIZE],
             oam_data: [0; OAM_DATA_SIZE],
-            mirroring,
 
             registers: Registers::new(),
             internal_data_buf: 0,
 
             scanline: 0,
             cycles: 0,
+            frames: 0,
             nmi_interrupt: None,
+            odd_frame: false,
+            region: Region::Ntsc,
+
+            background: BackgroundShifters::default(),
+            scanline_sprites: Vec::with_capacity(MAX_SPRITES_PER_SCANLINE),
+            frame_buffer: vec![0; FRAME_WIDTH * FRAME_HEIGHT],
+        }
+    }
+
+    /// Plugs in the cartridge. The pattern tables and the nametable mirroring come from the
+    /// mapper from now on.
+    pub fn load_mapper(&mut self, mapper: Rc<RefCell<dyn Mapper>>) {
+        self.mapper = mapper;
+    }
+
+    /// Sets the length of the frame and of vblank. NTSC to begin with.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    fn pre_render_scanline(&self) -> u16 {
+        self.region.scanlines_per_frame() - 1
+    }
 
-            scanline_zero_handled: false,
-            sprite_zero_hit: None,
+    pub fn mirroring(&self) -> Mirroring {
+        self.mapper.borrow().mirroring()
+    }
+
+    pub fn read_chr(&self, addr: u16) -> u8 {
+        self.mapper.borrow().read_chr(addr)
+    }
+
+    /// The 16 bytes of the tile starting at `addr` in the pattern tables, low plane first.
+    pub fn read_chr_tile(&self, addr: u16) -> [u8; 16] {
+        let mapper = self.mapper.borrow();
+        let mut tile = [0; 16];
+        for (i, item) in tile.iter_mut().enumerate() {
+            *item = mapper.read_chr(addr + i as u16);
         }
+        tile
     }
 
+    /// Advances the PPU by `cycles` dots. Returns true if a frame was finished and vblank began,
+    /// whether or not the game asked for the NMI.
     pub fn tick(&mut self, cycles: u8) -> bool {
-        self.cycles += cycles as usize;
+        let mut new_frame = false;
+        for _ in 0..cycles {
+            new_frame |= self.clock();
+        }
+        new_frame
+    }
 
-        if self.scanline == 0 && !self.scanline_zero_handled {
-            self.sprite_zero_hit = None;
-            self.scanline_zero_handled = true;
+    fn clock(&mut self) -> bool {
+        let pre_render_scanline = self.pre_render_scanline();
+        if self.scanline < VISIBLE_SCANLINES || self.scanline == pre_render_scanline {
+            self.render_dot();
         }
 
-        if self.cycles >= 341 {
-            if self.is_sprite_zero_hit(self.cycles) {
-                self.registers.scroll_before_sprite_zero = self.registers.scroll;
-                self.sprite_zero_hit = Some((self.oam_data[3], self.oam_data[0]));
-                self.registers.status.set_sprite_zero_hit(true);
-            }
+        // The last dot of the pre-render scanline is skipped on odd frames while rendering
+        if self.scanline == pre_render_scanline
+            && self.region.skips_odd_frame_dot()
+            && self.cycles == DOTS_PER_SCANLINE - 2
+            && self.odd_frame
+            && self.rendering_enabled()
+        {
+            self.cycles += 1;
+        }
 
-            self.cycles -= 341;
-            self.scanline += 1;
+        self.cycles += 1;
+        if self.cycles < DOTS_PER_SCANLINE {
+            return false;
+        }
 
-            if self.scanline == 241 {
-                self.registers.status.set_vblank_status(true);
-                self.registers.status.set_sprite_zero_hit(false);
-                if self.registers.control.generate_vblank_nmi() {
-                    self.nmi_interrupt = Some(1);
-                    return true;
-                }
-            }
+        self.cycles = 0;
+        self.scanline += 1;
 
-            if self.scanline >= 262 {
-                self.scanline = 0;
-                self.nmi_interrupt = None;
-                self.scanline_zero_handled = false;
-                self.registers.status.set_sprite_zero_hit(false);
-                self.registers.status.reset_vblank_status();
+        if self.scanline == self.region.vblank_scanline() {
+            self.frames += 1;
+            self.registers.status.set_vblank_status(true);
+            if self.registers.control.generate_vblank_nmi() {
+                self.nmi_interrupt = Some(1);
             }
+            return true;
+        }
+
+        if self.scanline == pre_render_scanline {
+            self.registers.status.reset_vblank_status();
+            self.registers.status.set_sprite_zero_hit(false);
+            self.registers.status.set_sprite_overflow(false);
+        }
+
+        if self.scanline > pre_render_scanline {
+            self.scanline = 0;
+            self.odd_frame = !self.odd_frame;
+            self.nmi_interrupt = None;
         }
 
         false
     }
 
-    fn is_sprite_zero_hit(&self, cycle: usize) -> bool {
-        let y = self.oam_data[0] as usize;
-        let x = self.oam_data[3] as usize;
-        (y == self.scanline as usize) && x <= cycle && self.registers.mask.show_sprites()
+    /// Outside of rendering $2007 accesses step v by 1 or 32. While rendering they instead
+    /// trigger both a coarse X and a Y increment, which some games use for raster effects.
+    fn increment_vram_addr(&mut self) {
+        let rendering = (self.scanline < VISIBLE_SCANLINES
+            || self.scanline == self.pre_render_scanline())
+            && self.rendering_enabled();
+        let increment = self.registers.control.vram_address_increment() as u16;
+
+        let loopy = &mut self.registers.loopy;
+        if rendering {
+            loopy.increment_x();
+            loopy.increment_y();
+        } else {
+            loopy.v = loopy.v.wrapping_add(increment) & 0x7FFF;
+        }
     }
 
-    fn increment_vram_addr(&mut self) {
-        self.registers
-            .address
-            .increment(self.registers.control.vram_address_increment());
+    /// The address $2007 accesses, v without the fine Y scroll bit that's beyond the bus.
+    fn vram_addr(&self) -> u16 {
+        self.registers.loopy.v & 0x3FFF
     }
 
     fn read_data(&mut self) -> u8 {
-        let addr = self.registers.address.get();
+        let addr = self.vram_addr();
         self.increment_vram_addr();
 
         match addr {
             PATTERN_TABLE_START..=PATTERN_TABLE_END => {
+                self.mapper.borrow_mut().notify_ppu_address(addr);
                 let result = self.internal_data_buf;
-                self.internal_data_buf = self.chr_rom[addr as usize];
+                self.internal_data_buf = self.read_chr(addr);
                 result
             }
-            NAMETABLE_START..=NAMETABLE_END => {
+            NAMETABLE_START..=NAMETABLE_MIRROR_END => {
                 let result = self.internal_data_buf;
                 self.internal_data_buf = self.vram[self.mirror_vram_addr(addr) as usize];
                 result
             }
-            NAMETABLE_MIRROR_START..=NAMETABLE_MIRROR_END => panic!(
-                "addr space 0x3000..0x3EFF is not expected to be used, requested = {:04X}",
-                addr
-            ),
             PALETTE_RAM_START..=PALETTE_RAM_END => {
                 self.palette_table[self.map_palette_table_address_to_index(addr)]
             }
@@ -141,17 +228,17 @@ impl PPU {
     }
 
     fn write_to_data(&mut self, value: u8) {
-        let addr = self.registers.address.get();
+        let addr = self.vram_addr();
 
         match addr {
-            PATTERN_TABLE_START..=PATTERN_TABLE_END => panic!("Write to chr_rom not allowed"),
-            NAMETABLE_START..=NAMETABLE_END => {
+            PATTERN_TABLE_START..=PATTERN_TABLE_END => {
+                let mut mapper = self.mapper.borrow_mut();
+                mapper.notify_ppu_address(addr);
+                mapper.write_chr(addr, value);
+            }
+            NAMETABLE_START..=NAMETABLE_MIRROR_END => {
                 self.vram[self.mirror_vram_addr(addr) as usize] = value
             }
-            NAMETABLE_MIRROR_START..=NAMETABLE_MIRROR_END => unimplemented!(
-                "addr space 0x3000..0x3EFF is not expected to be used, requested = {:04X}",
-                addr
-            ),
             PALETTE_RAM_START..=PALETTE_RAM_END => {
                 self.palette_table[self.map_palette_table_address_to_index(addr)] = value;
             }
@@ -176,22 +263,29 @@ impl PPU {
         let vram_index = mirrored_vram - 0x2000; // to vram vector
         let name_table = vram_index / 0x400; // to the name table index
 
-        match (&self.mirroring, name_table) {
+        match (self.mirroring(), name_table) {
             (Mirroring::Vertical, 2) | (Mirroring::Vertical, 3) => vram_index - 0x800,
             (Mirroring::Horizontal, 2) => vram_index - 0x400,
             (Mirroring::Horizontal, 1) => vram_index - 0x400,
             (Mirroring::Horizontal, 3) => vram_index - 0x800,
+            (Mirroring::SingleScreenLower, _) => vram_index & 0x3FF,
+            (Mirroring::SingleScreenUpper, _) => 0x400 | (vram_index & 0x3FF),
             _ => vram_index,
         }
     }
 
     fn write_to_ppu_address(&mut self, value: u8) {
-        self.registers.address.update(value)
+        self.registers.loopy.write_address(value);
+    }
+
+    fn write_to_scroll(&mut self, value: u8) {
+        self.registers.loopy.write_scroll(value);
     }
 
     fn write_to_control(&mut self, value: u8) {
         let before_nmi_status = self.registers.control.generate_vblank_nmi();
         self.registers.control.update(value);
+        self.registers.loopy.write_control(value);
         if !before_nmi_status
             && self.registers.control.generate_vblank_nmi()
             && self.registers.status.is_in_vblank()
@@ -203,8 +297,7 @@ impl PPU {
     fn read_status(&mut self) -> u8 {
         let data = self.registers.status.snapshot();
         self.registers.status.reset_vblank_status();
-        self.registers.address.reset_latch();
-        self.registers.scroll.reset_latch();
+        self.registers.loopy.reset_latch();
         data
     }
 
@@ -262,15 +355,7 @@ impl Mem for PPU {
             RegisterField::Mask => self.registers.mask.update(value),
             RegisterField::OAMAddress => self.write_to_oam_address(value),
             RegisterField::OAMData => self.write_to_oam_data(value),
-            RegisterField::Scroll => {
-                self.registers.scroll.write(value);
-
-                if let Some(_) = self.sprite_zero_hit {
-                    if !self.registers.scroll.latch {
-                        self.sprite_zero_hit = Some((self.cycles as u8, self.scanline as u8))
-                    }
-                }
-            }
+            RegisterField::Scroll => self.write_to_scroll(value),
             RegisterField::Address => self.write_to_ppu_address(value),
             RegisterField::Data => self.write_to_data(value),
             _ => panic!("Unexpected write on {:#?}", register),
@@ -278,6 +363,57 @@ impl Mem for PPU {
     }
 }
 
+/// The mapper is shared with the CPU bus, which saves it.
+impl Snapshot for PPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.palette_table);
+        state.write_bytes(&self.vram);
+        state.write_bytes(&self.oam_data);
+        self.registers.save_state(state);
+        state.write_u8(self.internal_data_buf);
+
+        state.write_u16(self.scanline);
+        state.write_usize(self.cycles);
+        state.write_bool(self.nmi_interrupt.is_some());
+        state.write_u8(self.nmi_interrupt.unwrap_or(0));
+        state.write_bool(self.odd_frame);
+
+        self.background.save_state(state);
+        state.write_u8(self.scanline_sprites.len() as u8);
+        for sprite in &self.scanline_sprites {
+            sprite.save_state(state);
+        }
+        state.write_bytes(&self.frame_buffer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.palette_table)?;
+        state.read_bytes(&mut self.vram)?;
+        state.read_bytes(&mut self.oam_data)?;
+        self.registers.load_state(state)?;
+        self.internal_data_buf = state.read_u8()?;
+
+        self.scanline = state.read_u16()?;
+        self.cycles = state.read_usize()?;
+        let nmi_pending = state.read_bool()?;
+        let nmi = state.read_u8()?;
+        self.nmi_interrupt = nmi_pending.then_some(nmi);
+        self.odd_frame = state.read_bool()?;
+
+        self.background.load_state(state)?;
+        let sprites = state.read_u8()? as usize;
+        if sprites > MAX_SPRITES_PER_SCANLINE {
+            return Err(format!("Invalid sprite count {} in save state", sprites));
+        }
+        self.scanline_sprites.clear();
+        for _ in 0..sprites {
+            self.scanline_sprites
+                .push(ScanlineSprite::load_state(state)?);
+        }
+        state.read_bytes(&mut self.frame_buffer)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -306,7 +442,7 @@ pub mod test {
         ppu.write_to_ppu_address(0x05);
 
         ppu.read_data(); //load_into_buffer
-        assert_equal!(ppu.registers.address.get(), 0x2306);
+        assert_equal!(ppu.registers.loopy.v, 0x2306);
         assert_equal!(ppu.read_data(), 0x66);
     }
 
@@ -401,6 +537,26 @@ pub mod test {
         assert_equal!(ppu.read_data(), 0x77); //read from B
     }
 
+    // Single screen: https://wiki.nesdev.com/w/index.php/Mirroring
+    //   [0x2000 a ] [0x2400 a ]
+    //   [0x2800 a ] [0x2C00 a ]
+    #[test]
+    fn test_vram_single_screen_mirror() {
+        let mut ppu = PPU::new(vec![0; 2048], Mirroring::SingleScreenUpper);
+
+        ppu.write_to_ppu_address(0x20);
+        ppu.write_to_ppu_address(0x05);
+
+        ppu.write_to_data(0x66);
+
+        ppu.write_to_ppu_address(0x2C);
+        ppu.write_to_ppu_address(0x05);
+
+        ppu.read_data(); //load into buffer
+        assert_equal!(ppu.read_data(), 0x66);
+        assert_equal!(ppu.vram[0x405], 0x66);
+    }
+
     #[test]
     fn test_read_status_resets_latch() {
         let mut ppu = PPU::new_empty_rom();
@@ -436,6 +592,53 @@ pub mod test {
         // assert_equal!(ppu.addr.read(), 0x0306)
     }
 
+    #[test]
+    fn test_scroll_and_address_share_write_toggle() {
+        let mut ppu = PPU::new_empty_rom();
+        ppu.vram[0x0305] = 0x66;
+
+        ppu.write_to_scroll(0x00);
+        ppu.write_to_ppu_address(0x05);
+        assert_equal!(ppu.registers.loopy.v, 0x0005);
+
+        ppu.read_status();
+        ppu.write_to_ppu_address(0x23);
+        ppu.write_to_ppu_address(0x05);
+
+        ppu.read_data(); //load_into_buffer
+        assert_equal!(ppu.read_data(), 0x66);
+    }
+
+    #[test]
+    fn test_address_write_sets_scroll_mid_frame() {
+        let mut ppu = PPU::new_empty_rom();
+        ppu.write_to_ppu_address(0x24);
+        ppu.write_to_ppu_address(0x2A);
+
+        let loopy = &ppu.registers.loopy;
+        assert_equal!(loopy.coarse_x(), 10);
+        assert_equal!(loopy.coarse_y(), 1);
+        assert_equal!(loopy.fine_y(), 2);
+        assert_equal!(loopy.tile_address(), 0x242A);
+    }
+
+    #[test]
+    fn test_data_access_while_rendering_increments_coarse_x_and_y() {
+        let mut ppu = PPU::new_empty_rom();
+        ppu.registers
+            .mask
+            .update(MaskRegister::SHOW_BACKGROUND.bits());
+        ppu.write_to_ppu_address(0x20);
+        ppu.write_to_ppu_address(0x00);
+
+        ppu.write_to_data(0x66);
+        assert_equal!(ppu.registers.loopy.v, 0x3001);
+
+        ppu.scanline = 241;
+        ppu.write_to_data(0x66);
+        assert_equal!(ppu.registers.loopy.v, 0x3002);
+    }
+
     #[test]
     fn test_read_status_resets_vblank() {
         let mut ppu = PPU::new_empty_rom();
@@ -487,6 +690,61 @@ pub mod test {
         ppu.tick(241)
     }
 
+    struct RecordingMapper {
+        addresses: Vec<u16>,
+    }
+
+    impl Snapshot for RecordingMapper {
+        fn save_state(&self, _state: &mut StateWriter) {}
+
+        fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    impl Mapper for RecordingMapper {
+        fn read_prg(&self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn write_prg(&mut self, _addr: u16, _value: u8) {}
+
+        fn read_chr(&self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn mirroring(&self) -> Mirroring {
+            Mirroring::Horizontal
+        }
+
+        fn notify_ppu_address(&mut self, addr: u16) {
+            self.addresses.push(addr)
+        }
+    }
+
+    #[test]
+    fn test_tick_notifies_mapper_of_pattern_fetches_while_rendering() {
+        let mapper = Rc::new(RefCell::new(RecordingMapper { addresses: vec![] }));
+        let mut ppu = PPU::new_with_mapper(mapper.clone());
+
+        tick_one_scanline(&mut ppu);
+        assert_equal!(mapper.borrow().addresses.len(), 0);
+
+        ppu.write_to_control(ControlRegister::SPRITE_PATTERN_ADDR.bits());
+        ppu.registers
+            .mask
+            .update(MaskRegister::SHOW_BACKGROUND.bits());
+        tick_one_scanline(&mut ppu);
+
+        // Background tiles come from $0000 and sprites from $1000, so A12 rises once per scanline
+        let addresses = &mapper.borrow().addresses;
+        let rising_edges = addresses
+            .windows(2)
+            .filter(|pair| pair[0] & 0x1000 == 0 && pair[1] & 0x1000 != 0)
+            .count();
+        assert_equal!(rising_edges, 1);
+    }
+
     #[test]
     fn test_tick_cycles_less_than_341_scanline_should_not_change() {
         let mut ppu = PPU::new_empty_rom();
@@ -552,6 +810,38 @@ pub mod test {
             .contains(StatusRegister::SPRITE_ZERO_HIT));
     }
 
+    #[test]
+    fn test_tick_reports_new_frame_without_nmi() {
+        let mut ppu = PPU::new_empty_rom();
+
+        for _ in 0..240 {
+            assert!(!tick_one_scanline(&mut ppu));
+        }
+        assert!(tick_one_scanline(&mut ppu));
+        assert_equal!(ppu.scanline, 241);
+        assert_equal!(ppu.nmi_interrupt, None);
+    }
+
+    #[test]
+    fn test_pal_and_dendy_frames_have_312_scanlines() {
+        for (region, vblank_scanline) in [(Region::Pal, 241), (Region::Dendy, 291)] {
+            let mut ppu = PPU::new_empty_rom();
+            ppu.set_region(region);
+            ppu.registers
+                .control
+                .set(ControlRegister::GENERATE_NMI_AT_VBI, true);
+
+            let mut nmi_scanlines = vec![];
+            for _ in 0..312 {
+                if tick_one_scanline(&mut ppu) {
+                    nmi_scanlines.push(ppu.scanline);
+                }
+            }
+            assert_equal!(nmi_scanlines, vec![vblank_scanline]);
+            assert_equal!(ppu.scanline, 0);
+        }
+    }
+
     #[test]
     fn test_tick_resets_nmi_after_262_scanlines() {
         let mut ppu = PPU::new_empty_rom();
@@ -582,67 +872,27 @@ pub mod test {
     }
 
     #[test]
-    fn test_tick_checks_if_sprite_zero_is_hit_on_every_cycle() {
-        let mut ppu = PPU::new_empty_rom();
-        ppu.registers
-            .control
-            .set(ControlRegister::GENERATE_NMI_AT_VBI, true);
-        ppu.registers.mask.set(MaskRegister::SHOW_SPRITES, true);
-
-        ppu.oam_data[0] = 10; // sprite_zero_hit scanline = 10
-        ppu.oam_data[3] = 0; // sprite_zero_hit 0 <= cycle
-
-        tick_one_scanline(&mut ppu);
-        assert!(!ppu
-            .registers
-            .status
-            .contains(StatusRegister::SPRITE_ZERO_HIT));
-
-        ppu.oam_data[0] = 1; // sprite_zero_hit scanline = 1
-        ppu.oam_data[3] = 0; // sprite_zero_hit 0 <= cycle
-
-        tick_one_scanline(&mut ppu);
-        assert!(ppu
-            .registers
-            .status
-            .contains(StatusRegister::SPRITE_ZERO_HIT));
-    }
-
-    #[test]
-    fn test_tick_resets_sprite_zero_hit_during_vblank() {
+    fn test_tick_clears_sprite_flags_on_pre_render_scanline() {
         let mut ppu = PPU::new_empty_rom();
-        ppu.registers
-            .control
-            .set(ControlRegister::GENERATE_NMI_AT_VBI, true);
-        ppu.registers.mask.set(MaskRegister::SHOW_SPRITES, true);
+        ppu.registers.status.set_sprite_zero_hit(true);
+        ppu.registers.status.set_sprite_overflow(true);
 
-        ppu.oam_data[0] = 10; // sprite_zero_hit scanline = 10
-        ppu.oam_data[3] = 0; // sprite_zero_hit 0 <= cycle
-
-        tick_one_scanline(&mut ppu);
-        assert!(!ppu
-            .registers
-            .status
-            .contains(StatusRegister::SPRITE_ZERO_HIT));
-
-        ppu.oam_data[0] = 1; // sprite_zero_hit scanline = 1
-        ppu.oam_data[3] = 0; // sprite_zero_hit 0 <= cycle
-
-        tick_one_scanline(&mut ppu);
+        for _ in 0..241 {
+            tick_one_scanline(&mut ppu);
+        }
         assert!(ppu
             .registers
             .status
-            .contains(StatusRegister::SPRITE_ZERO_HIT));
+            .contains(StatusRegister::SPRITE_ZERO_HIT | StatusRegister::SPRITE_OVERFLOW));
 
-        for _ in 1..240 {
+        for _ in 241..261 {
             tick_one_scanline(&mut ppu);
         }
-
-        assert_equal!(ppu.scanline, 241);
+        assert_equal!(ppu.scanline, 261);
         assert!(!ppu
             .registers
             .status
-            .contains(StatusRegister::SPRITE_ZERO_HIT));
+            .intersects(StatusRegister::SPRITE_ZERO_HIT | StatusRegister::SPRITE_OVERFLOW));
     }
 
     #[test]
@@ -651,12 +901,12 @@ pub mod test {
         ppu.palette_table[0] = 0xFF;
         ppu.palette_table[31] = 0xAA;
 
-        ppu.registers.address.update(0x3F);
-        ppu.registers.address.update(0x00);
+        ppu.write_to_ppu_address(0x3F);
+        ppu.write_to_ppu_address(0x00);
         assert_equal!(ppu.read_data(), 0xFF);
 
-        ppu.registers.address.update(0x3F);
-        ppu.registers.address.update(0x1F);
+        ppu.write_to_ppu_address(0x3F);
+        ppu.write_to_ppu_address(0x1F);
         assert_equal!(ppu.read_data(), 0xAA);
     }
 
@@ -666,12 +916,12 @@ pub mod test {
         ppu.palette_table[0] = 0xFF;
         ppu.palette_table[31] = 0xAA;
 
-        ppu.registers.address.update(0x3F);
-        ppu.registers.address.update(0x10);
+        ppu.write_to_ppu_address(0x3F);
+        ppu.write_to_ppu_address(0x10);
         assert_equal!(ppu.read_data(), 0xFF);
 
-        ppu.registers.address.update(0x3F);
-        ppu.registers.address.update(0x3F);
+        ppu.write_to_ppu_address(0x3F);
+        ppu.write_to_ppu_address(0x3F);
         assert_equal!(ppu.read_data(), 0xAA);
     }
 }
@@ -0,0 +1,511 @@
+use crate::sprite::{ScanlineSprite, SpriteAttribute, MAX_SPRITES_PER_SCANLINE};
+use crate::{FRAME_WIDTH, PALETTE_RAM_START, PPU, VISIBLE_SCANLINES};
+
+const OAM_SPRITES: usize = 64;
+
+// https://www.nesdev.org/wiki/PPU_rendering
+impl PPU {
+    pub(crate) fn rendering_enabled(&self) -> bool {
+        self.registers.mask.show_background() || self.registers.mask.show_sprites()
+    }
+
+    /// Runs one dot of a visible or the pre-render scanline.
+    ///
+    /// Dots 1-256 output a pixel each, while the background pipeline fetches the tiles 8 dots
+    /// ahead. At dot 257 the sprites for the next scanline are evaluated and their patterns
+    /// fetched, and dots 321-336 prefetch the first two tiles of the next scanline.
+    pub(crate) fn render_dot(&mut self) {
+        let dot = self.cycles;
+        let pre_render = self.scanline == self.pre_render_scanline();
+
+        if self.rendering_enabled() {
+            if (2..=257).contains(&dot) || (321..=337).contains(&dot) {
+                self.background.shift();
+
+                match (dot - 1) % 8 {
+                    0 => {
+                        self.background.load();
+                        self.fetch_tile_id();
+                    }
+                    2 => self.fetch_tile_attribute(),
+                    4 => self.background.next_tile_lo = self.fetch_tile_pattern(0),
+                    6 => self.background.next_tile_hi = self.fetch_tile_pattern(8),
+                    7 => self.registers.loopy.increment_x(),
+                    _ => {}
+                }
+            }
+
+            match dot {
+                256 => self.registers.loopy.increment_y(),
+                257 => {
+                    self.registers.loopy.copy_horizontal();
+                    self.evaluate_sprites();
+                }
+                280..=304 if pre_render => self.registers.loopy.copy_vertical(),
+                _ => {}
+            }
+        }
+
+        if !pre_render && (1..=256).contains(&dot) {
+            self.output_pixel(dot - 1);
+        }
+    }
+
+    fn fetch_chr(&mut self, addr: u16) -> u8 {
+        let mut mapper = self.mapper.borrow_mut();
+        mapper.notify_ppu_address(addr);
+        mapper.read_chr(addr)
+    }
+
+    fn fetch_tile_id(&mut self) {
+        let addr = self.mirror_vram_addr(self.registers.loopy.tile_address());
+        self.background.next_tile_id = self.vram[addr as usize];
+    }
+
+    fn fetch_tile_attribute(&mut self) {
+        let addr = self.mirror_vram_addr(self.registers.loopy.attribute_address());
+        let attribute = self.vram[addr as usize];
+
+        // Each attribute byte covers 4x4 tiles, two bits for every 2x2 quadrant
+        let loopy = &self.registers.loopy;
+        let shift = ((loopy.coarse_y() & 0b10) << 1) | (loopy.coarse_x() & 0b10);
+        self.background.next_tile_attribute = (attribute >> shift) & 0b11;
+    }
+
+    fn fetch_tile_pattern(&mut self, plane: u16) -> u8 {
+        let addr = self.registers.control.background_pattern_table_address()
+            + self.background.next_tile_id as u16 * 16
+            + self.registers.loopy.fine_y()
+            + plane;
+        self.fetch_chr(addr)
+    }
+
+    /// The pattern row of a sprite tile. 8x16 sprites ignore the sprite pattern table address,
+    /// bit 0 of the tile index selects the table and the top tile is the even one below it.
+    fn sprite_pattern_address(&self, tile: u8, row: u16) -> u16 {
+        if self.registers.control.sprite_size() == 8 {
+            return self.registers.control.sprite_pattern_table_address() + tile as u16 * 16 + row;
+        }
+
+        let table = (tile as u16 & 1) * 0x1000;
+        let tile = (tile & 0xFE) as u16 + row / 8;
+        table + tile * 16 + row % 8
+    }
+
+    /// Finds the first 8 sprites in OAM that are on the next scanline. The hardware fetches
+    /// patterns for all 8 slots, using tile $FF for the empty ones.
+    fn evaluate_sprites(&mut self) {
+        self.scanline_sprites.clear();
+
+        let height = self.registers.control.sprite_size() as u16;
+        let in_range = |y: u8| self.scanline.wrapping_sub(y as u16) < height;
+        let sprites = if self.scanline < VISIBLE_SCANLINES {
+            OAM_SPRITES
+        } else {
+            0
+        };
+
+        // OAM offset and row within the sprite of the sprites found
+        let mut found: Vec<(usize, u16)> = Vec::with_capacity(MAX_SPRITES_PER_SCANLINE);
+        let mut n = 0;
+        while n < sprites && found.len() < MAX_SPRITES_PER_SCANLINE {
+            let y = self.oam_data[n * 4];
+            if in_range(y) {
+                found.push((n * 4, self.scanline.wrapping_sub(y as u16)));
+            }
+            n += 1;
+        }
+
+        // With 8 sprites found the hardware keeps looking for a 9th to set the overflow flag,
+        // but increments the byte offset along with the sprite index. It reads tile, attribute
+        // and X bytes as Y coordinates, causing both false positives and negatives.
+        let mut m = 0;
+        let mut overflow = false;
+        while n < sprites {
+            if in_range(self.oam_data[n * 4 + m]) {
+                overflow = true;
+                break;
+            }
+            n += 1;
+            m = (m + 1) % 4;
+        }
+        if overflow {
+            self.registers.status.set_sprite_overflow(true);
+        }
+
+        for slot in 0..MAX_SPRITES_PER_SCANLINE {
+            let Some(&(oam, row)) = found.get(slot) else {
+                let addr = self.sprite_pattern_address(0xFF, 0);
+                self.fetch_chr(addr);
+                self.fetch_chr(addr + 8);
+                continue;
+            };
+
+            let attributes = SpriteAttribute::from_bits_truncate(self.oam_data[oam + 2]);
+            let row = if attributes.contains(SpriteAttribute::FLIP_SPRITE_VERTICALLY) {
+                height - 1 - row
+            } else {
+                row
+            };
+
+            let addr = self.sprite_pattern_address(self.oam_data[oam + 1], row);
+            let mut pattern_lo = self.fetch_chr(addr);
+            let mut pattern_hi = self.fetch_chr(addr + 8);
+            if attributes.contains(SpriteAttribute::FLIP_SPRITE_HORIZONTALLY) {
+                pattern_lo = pattern_lo.reverse_bits();
+                pattern_hi = pattern_hi.reverse_bits();
+            }
+
+            self.scanline_sprites.push(ScanlineSprite {
+                sprite_zero: oam == 0,
+                x: self.oam_data[oam + 3],
+                attributes,
+                pattern_lo,
+                pattern_hi,
+            });
+        }
+    }
+
+    fn output_pixel(&mut self, x: usize) {
+        let mask = &self.registers.mask;
+        let show_background = mask.show_background() && (x >= 8 || mask.leftmost_8pxl_background());
+        let show_sprites = mask.show_sprites() && (x >= 8 || mask.leftmost_8pxl_sprite());
+
+        let (bg_palette, bg_pixel) = if show_background {
+            self.background.pixel(self.registers.loopy.x)
+        } else {
+            (0, 0)
+        };
+
+        // The first opaque sprite wins, even if it is behind the background and a later
+        // sprite is not
+        let sprite = if show_sprites {
+            self.scanline_sprites.iter().find_map(|sprite| {
+                let (palette, pixel) = sprite.pixel(x);
+                (pixel != 0).then_some((palette, pixel, sprite.attributes.behind_background()))
+            })
+        } else {
+            None
+        };
+
+        // Sprite zero hits when an opaque pixel of it overlaps an opaque background pixel,
+        // whichever sprite is drawn on top. It never hits at x=255.
+        if bg_pixel != 0 && show_sprites && x != FRAME_WIDTH - 1 {
+            let sprite_zero = self.scanline_sprites.first().filter(|s| s.sprite_zero);
+            if sprite_zero.is_some_and(|sprite| sprite.pixel(x).1 != 0) {
+                self.registers.status.set_sprite_zero_hit(true);
+            }
+        }
+
+        let palette_addr = match sprite {
+            Some((palette, pixel, behind)) if !behind || bg_pixel == 0 => {
+                0x10 | (palette << 2) | pixel
+            }
+            _ if bg_pixel != 0 => (bg_palette << 2) | bg_pixel,
+            _ => 0,
+        };
+
+        let index =
+            self.map_palette_table_address_to_index(PALETTE_RAM_START + palette_addr as u16);
+        let color = if self.registers.mask.is_grayscale() {
+            self.palette_table[index] & 0x30
+        } else {
+            self.palette_table[index] & 0x3F
+        };
+
+        self.frame_buffer[self.scanline as usize * FRAME_WIDTH + x] = color;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registers::control::ControlRegister;
+    use crate::registers::mask::MaskRegister;
+    use crate::registers::status::StatusRegister;
+    use crate::sprite::SpriteAttribute;
+    use crate::PPU;
+    use core::cartridge::Mirroring;
+    use k9::assert_equal;
+
+    const BACKDROP: u8 = 0x0F;
+    const BACKGROUND_COLOR: u8 = 0x21;
+    const SPRITE_COLOR: u8 = 0x16;
+    const SPRITE_COLOR_2: u8 = 0x2A;
+
+    const SHOW_ALL: u8 = MaskRegister::SHOW_BACKGROUND.bits()
+        | MaskRegister::SHOW_SPRITES.bits()
+        | MaskRegister::LEFTMOST_8PXL_BACKGROUND.bits()
+        | MaskRegister::LEFTMOST_8PXL_SPRITE.bits();
+
+    /// A PPU whose tile 1 is solid color 1 and all other tiles are transparent, apart from
+    /// tile $101 that is solid color 2.
+    fn new_ppu(mirroring: Mirroring) -> PPU {
+        let mut chr_rom = vec![0; 0x2000];
+        chr_rom[0x10..0x18].fill(0xFF);
+        chr_rom[0x1018..0x1020].fill(0xFF);
+
+        let mut ppu = PPU::new(chr_rom, mirroring);
+        ppu.palette_table[0] = BACKDROP;
+        ppu.palette_table[1] = BACKGROUND_COLOR;
+        ppu.palette_table[0x11] = SPRITE_COLOR;
+        ppu.palette_table[0x12] = SPRITE_COLOR_2;
+        ppu
+    }
+
+    // Of the NTSC PPU the tests run
+    const PRE_RENDER_SCANLINE: u16 = 261;
+
+    fn tick_until(ppu: &mut PPU, scanline: u16) {
+        while ppu.scanline != scanline || ppu.cycles != 0 {
+            ppu.tick(1);
+        }
+    }
+
+    /// Runs the pre-render scanline and every visible scanline.
+    fn render_frame(ppu: &mut PPU) {
+        tick_until(ppu, PRE_RENDER_SCANLINE);
+        tick_until(ppu, super::VISIBLE_SCANLINES);
+    }
+
+    fn pixel(ppu: &PPU, x: usize, y: usize) -> u8 {
+        ppu.frame_buffer[y * super::FRAME_WIDTH + x]
+    }
+
+    #[test]
+    fn test_render_background_tile() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.vram[33] = 1; // second tile of the second row
+        ppu.registers
+            .mask
+            .update(MaskRegister::SHOW_BACKGROUND.bits());
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 8, 8), BACKGROUND_COLOR);
+        assert_equal!(pixel(&ppu, 15, 15), BACKGROUND_COLOR);
+        assert_equal!(pixel(&ppu, 7, 8), BACKDROP);
+        assert_equal!(pixel(&ppu, 16, 8), BACKDROP);
+        assert_equal!(pixel(&ppu, 8, 16), BACKDROP);
+    }
+
+    #[test]
+    fn test_render_sprite_one_scanline_below_its_y() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.oam_data[..4].copy_from_slice(&[9, 1, 0, 20]);
+        ppu.registers.mask.update(MaskRegister::SHOW_SPRITES.bits());
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 20, 9), BACKDROP);
+        assert_equal!(pixel(&ppu, 20, 10), SPRITE_COLOR);
+        assert_equal!(pixel(&ppu, 27, 17), SPRITE_COLOR);
+        assert_equal!(pixel(&ppu, 28, 10), BACKDROP);
+        assert_equal!(pixel(&ppu, 20, 18), BACKDROP);
+    }
+
+    #[test]
+    fn test_render_only_eight_sprites_per_scanline() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        for n in 0..9 {
+            ppu.oam_data[n * 4..n * 4 + 4].copy_from_slice(&[19, 1, 0, n as u8 * 8]);
+        }
+        ppu.registers.mask.update(MaskRegister::SHOW_SPRITES.bits());
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 63, 20), SPRITE_COLOR);
+        assert_equal!(pixel(&ppu, 64, 20), BACKDROP);
+    }
+
+    #[test]
+    fn test_render_nametable_switch_mid_frame() {
+        let mut ppu = new_ppu(Mirroring::Vertical);
+        ppu.vram[0x400..0x7C0].fill(1); // every tile of the second nametable
+        ppu.registers
+            .mask
+            .update(MaskRegister::SHOW_BACKGROUND.bits());
+
+        tick_until(&mut ppu, PRE_RENDER_SCANLINE);
+        tick_until(&mut ppu, 100);
+        ppu.write_to_control(0b01);
+        tick_until(&mut ppu, super::VISIBLE_SCANLINES);
+
+        // The new nametable is picked up at the end of scanline 100
+        assert_equal!(pixel(&ppu, 8, 99), BACKDROP);
+        assert_equal!(pixel(&ppu, 255, 100), BACKDROP);
+        assert_equal!(pixel(&ppu, 8, 101), BACKGROUND_COLOR);
+        assert_equal!(pixel(&ppu, 255, 239), BACKGROUND_COLOR);
+    }
+
+    #[test]
+    fn test_render_8x16_sprite_selects_table_from_tile_index() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        // Tile $01 selects the table at $1000, tiles $100 and $101
+        ppu.oam_data[..4].copy_from_slice(&[9, 0x01, 0, 20]);
+        ppu.write_to_control(ControlRegister::SPRITE_SIZE.bits());
+        ppu.registers.mask.update(SHOW_ALL);
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 20, 17), BACKDROP);
+        assert_equal!(pixel(&ppu, 20, 18), SPRITE_COLOR_2);
+        assert_equal!(pixel(&ppu, 20, 25), SPRITE_COLOR_2);
+        assert_equal!(pixel(&ppu, 20, 26), BACKDROP);
+    }
+
+    #[test]
+    fn test_render_8x16_sprite_flipped_vertically_swaps_tiles() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        let attributes = SpriteAttribute::FLIP_SPRITE_VERTICALLY.bits();
+        ppu.oam_data[..4].copy_from_slice(&[9, 0x01, attributes, 20]);
+        ppu.write_to_control(ControlRegister::SPRITE_SIZE.bits());
+        ppu.registers.mask.update(SHOW_ALL);
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 20, 10), SPRITE_COLOR_2);
+        assert_equal!(pixel(&ppu, 20, 17), SPRITE_COLOR_2);
+        assert_equal!(pixel(&ppu, 20, 18), BACKDROP);
+    }
+
+    #[test]
+    fn test_render_sprite_behind_opaque_background() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.vram[66] = 1; // third tile of the third row
+        let behind = SpriteAttribute::PRIORITY_FG_OR_BG.bits();
+        ppu.oam_data[..4].copy_from_slice(&[15, 1, behind, 12]);
+        ppu.registers.mask.update(SHOW_ALL);
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 15, 16), SPRITE_COLOR);
+        assert_equal!(pixel(&ppu, 16, 16), BACKGROUND_COLOR);
+    }
+
+    #[test]
+    fn test_render_clips_left_8_pixels() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.vram[..32].fill(1);
+        ppu.oam_data[..4].copy_from_slice(&[9, 1, 0, 4]);
+        ppu.registers
+            .mask
+            .update(MaskRegister::SHOW_BACKGROUND.bits() | MaskRegister::SHOW_SPRITES.bits());
+
+        render_frame(&mut ppu);
+
+        assert_equal!(pixel(&ppu, 7, 0), BACKDROP);
+        assert_equal!(pixel(&ppu, 8, 0), BACKGROUND_COLOR);
+        assert_equal!(pixel(&ppu, 7, 10), BACKDROP);
+        assert_equal!(pixel(&ppu, 8, 10), SPRITE_COLOR);
+    }
+
+    fn sprite_zero_hit(ppu: &PPU) -> bool {
+        ppu.registers
+            .status
+            .contains(StatusRegister::SPRITE_ZERO_HIT)
+    }
+
+    fn sprite_overflow(ppu: &PPU) -> bool {
+        ppu.registers
+            .status
+            .contains(StatusRegister::SPRITE_OVERFLOW)
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_needs_opaque_overlap() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.vram[66] = 1; // third tile of the third row, pixels (16..24, 16..24)
+        ppu.registers.mask.update(SHOW_ALL);
+
+        ppu.oam_data[..4].copy_from_slice(&[7, 1, 0, 16]); // scanlines 8..16
+        render_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+
+        ppu.oam_data[..4].copy_from_slice(&[8, 0, 0, 16]); // transparent tile
+        render_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+
+        ppu.oam_data[..4].copy_from_slice(&[8, 1, 0, 20]);
+        tick_until(&mut ppu, PRE_RENDER_SCANLINE);
+        tick_until(&mut ppu, 16);
+        assert!(!sprite_zero_hit(&ppu));
+        tick_until(&mut ppu, 17);
+        assert!(sprite_zero_hit(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_behind_background_and_other_sprites() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.vram[66] = 1;
+        ppu.registers.mask.update(SHOW_ALL);
+
+        let behind = SpriteAttribute::PRIORITY_FG_OR_BG.bits();
+        ppu.oam_data[..4].copy_from_slice(&[15, 1, behind, 16]);
+        ppu.oam_data[4..8].copy_from_slice(&[15, 1, 0, 16]);
+        render_frame(&mut ppu);
+
+        assert!(sprite_zero_hit(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_exceptions() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.vram[..32].fill(1);
+
+        // Hidden by the left column clipping
+        ppu.registers
+            .mask
+            .update(MaskRegister::SHOW_BACKGROUND.bits() | MaskRegister::SHOW_SPRITES.bits());
+        ppu.oam_data[..4].copy_from_slice(&[0, 1, 0, 0]);
+        render_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+
+        // Only overlapping at x=255
+        ppu.oam_data[..4].copy_from_slice(&[0, 1, 0, 255]);
+        render_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+
+        ppu.oam_data[..4].copy_from_slice(&[0, 1, 0, 254]);
+        render_frame(&mut ppu);
+        assert!(sprite_zero_hit(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_overflow_with_nine_sprites_on_a_scanline() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.oam_data.fill(0xF0);
+        for n in 0..8 {
+            ppu.oam_data[n * 4] = 50;
+        }
+        ppu.registers.mask.update(SHOW_ALL);
+
+        render_frame(&mut ppu);
+        assert!(!sprite_overflow(&ppu));
+
+        ppu.oam_data[8 * 4] = 50;
+        render_frame(&mut ppu);
+        assert!(sprite_overflow(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_overflow_diagonal_scan() {
+        let mut ppu = new_ppu(Mirroring::Horizontal);
+        ppu.oam_data.fill(0xF0);
+        for n in 0..8 {
+            ppu.oam_data[n * 4] = 50;
+        }
+        ppu.registers.mask.update(SHOW_ALL);
+
+        // The 10th sprite is on the scanline but its Y is never read, the scan reads its
+        // tile byte instead
+        ppu.oam_data[9 * 4] = 50;
+        render_frame(&mut ppu);
+        assert!(!sprite_overflow(&ppu));
+
+        // A 10th sprite whose tile byte looks like a Y on the scanline
+        ppu.oam_data[9 * 4] = 0xF0;
+        ppu.oam_data[9 * 4 + 1] = 50;
+        render_frame(&mut ppu);
+        assert!(sprite_overflow(&ppu));
+    }
+}
@@ -1,8 +1,8 @@
-use crate::registers::address::AddressRegister;
 use crate::registers::control::ControlRegister;
+use crate::registers::loopy::LoopyRegisters;
 use crate::registers::mask::MaskRegister;
-use crate::registers::scroll::ScrollRegister;
 use crate::registers::status::StatusRegister;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
@@ -77,10 +77,7 @@ pub struct Registers {
     pub mask: MaskRegister,
     pub status: StatusRegister,
     pub oam_address: u8,
-    pub scroll: ScrollRegister,
-    pub address: AddressRegister,
-
-    pub scroll_before_sprite_zero: ScrollRegister,
+    pub loopy: LoopyRegisters,
 }
 
 impl Registers {
@@ -90,10 +87,7 @@ impl Registers {
             mask: MaskRegister::new(),
             status: StatusRegister::new(),
             oam_address: 0,
-            scroll: ScrollRegister::new(),
-            address: AddressRegister::new(),
-
-            scroll_before_sprite_zero: ScrollRegister::new(),
+            loopy: LoopyRegisters::new(),
         }
     }
 }
@@ -103,3 +97,21 @@ impl Default for Registers {
         Registers::new()
     }
 }
+
+impl Snapshot for Registers {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.control.bits());
+        state.write_u8(self.mask.bits());
+        state.write_u8(self.status.bits());
+        state.write_u8(self.oam_address);
+        self.loopy.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.control = ControlRegister::from_bits_truncate(state.read_u8()?);
+        self.mask = MaskRegister::from_bits_truncate(state.read_u8()?);
+        self.status = StatusRegister::from_bits_truncate(state.read_u8()?);
+        self.oam_address = state.read_u8()?;
+        self.loopy.load_state(state)
+    }
+}
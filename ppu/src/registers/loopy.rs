@@ -0,0 +1,205 @@
+use core::snapshot::{Snapshot, StateReader, StateWriter};
+const COARSE_X: u16 = 0x001F;
+const COARSE_Y: u16 = 0x03E0;
+const NAMETABLE_X: u16 = 0x0400;
+const NAMETABLE_Y: u16 = 0x0800;
+const NAMETABLE: u16 = NAMETABLE_X | NAMETABLE_Y;
+const FINE_Y: u16 = 0x7000;
+
+const HORIZONTAL: u16 = COARSE_X | NAMETABLE_X;
+const VERTICAL: u16 = COARSE_Y | NAMETABLE_Y | FINE_Y;
+
+/// # Internal scroll registers https://www.nesdev.org/wiki/PPU_scrolling
+///
+/// v: Current VRAM address (15 bits)
+/// t: Temporary VRAM address (15 bits), the top left onscreen tile
+/// x: Fine X scroll (3 bits)
+/// w: First or second write toggle, shared by $2005 and $2006
+///
+/// The VRAM addresses are laid out as
+///
+/// yyy NN YYYYY XXXXX
+/// ||| || ||||| +++++-- coarse X scroll
+/// ||| || +++++-------- coarse Y scroll
+/// ||| ++-------------- nametable select
+/// +++----------------- fine Y scroll
+///
+#[derive(Copy, Clone, Debug)]
+pub struct LoopyRegisters {
+    pub v: u16,
+    pub t: u16,
+    pub x: u8,
+    pub w: bool,
+}
+
+impl LoopyRegisters {
+    pub fn new() -> Self {
+        LoopyRegisters {
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+        }
+    }
+
+    /// $2000 write: t: ...GH.. ........ <- d: ......GH
+    pub fn write_control(&mut self, data: u8) {
+        self.t = (self.t & !NAMETABLE) | (((data & 0b11) as u16) << 10);
+    }
+
+    /// $2005 first write: t: ....... ...ABCDE <- d: ABCDE...
+    ///                    x:              FGH <- d: .....FGH
+    /// $2005 second write: t: FGH..AB CDE..... <- d: ABCDEFGH
+    pub fn write_scroll(&mut self, data: u8) {
+        if !self.w {
+            self.t = (self.t & !COARSE_X) | (data >> 3) as u16;
+            self.x = data & 0b111;
+        } else {
+            self.t = (self.t & !(COARSE_Y | FINE_Y))
+                | (((data & 0b1111_1000) as u16) << 2)
+                | (((data & 0b111) as u16) << 12);
+        }
+        self.w = !self.w;
+    }
+
+    /// $2006 first write: t: .CDEFGH ........ <- d: ..CDEFGH, and bit 14 is cleared
+    /// $2006 second write: t: ....... ABCDEFGH <- d: ABCDEFGH, then v = t
+    pub fn write_address(&mut self, data: u8) {
+        if !self.w {
+            self.t = (self.t & 0x00FF) | (((data & 0b0011_1111) as u16) << 8);
+        } else {
+            self.t = (self.t & 0xFF00) | data as u16;
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    pub fn reset_latch(&mut self) {
+        self.w = false;
+    }
+
+    pub fn coarse_x(&self) -> u16 {
+        self.v & COARSE_X
+    }
+
+    pub fn coarse_y(&self) -> u16 {
+        (self.v & COARSE_Y) >> 5
+    }
+
+    pub fn fine_y(&self) -> u16 {
+        (self.v & FINE_Y) >> 12
+    }
+
+    /// The nametable byte of the tile v points at.
+    pub fn tile_address(&self) -> u16 {
+        0x2000 | (self.v & 0x0FFF)
+    }
+
+    /// The attribute byte covering the tile v points at.
+    pub fn attribute_address(&self) -> u16 {
+        0x23C0 | (self.v & NAMETABLE) | ((self.v >> 4) & 0b11_1000) | ((self.v >> 2) & 0b111)
+    }
+
+    /// Moves v one tile to the right, wrapping into the horizontally adjacent nametable.
+    pub fn increment_x(&mut self) {
+        if self.coarse_x() == 31 {
+            self.v &= !COARSE_X;
+            self.v ^= NAMETABLE_X;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    /// Moves v one pixel row down. Row 29 is the last one in a nametable, so it wraps into the
+    /// vertically adjacent nametable. Coarse Y of 30 and 31 point into the attribute table, and
+    /// wrap to 0 without switching nametable.
+    pub fn increment_y(&mut self) {
+        if self.fine_y() < 7 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !FINE_Y;
+        let coarse_y = match self.coarse_y() {
+            29 => {
+                self.v ^= NAMETABLE_Y;
+                0
+            }
+            31 => 0,
+            y => y + 1,
+        };
+        self.v = (self.v & !COARSE_Y) | (coarse_y << 5);
+    }
+
+    /// v: ....A.. ...BCDEF <- t: ....A.. ...BCDEF
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !HORIZONTAL) | (self.t & HORIZONTAL);
+    }
+
+    /// v: GHIA.BC DEF..... <- t: GHIA.BC DEF.....
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !VERTICAL) | (self.t & VERTICAL);
+    }
+}
+
+impl Default for LoopyRegisters {
+    fn default() -> Self {
+        LoopyRegisters::new()
+    }
+}
+
+impl Snapshot for LoopyRegisters {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.v);
+        state.write_u16(self.t);
+        state.write_u8(self.x);
+        state.write_bool(self.w);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.v = state.read_u16()?;
+        self.t = state.read_u16()?;
+        self.x = state.read_u8()?;
+        self.w = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    #[test]
+    fn test_scroll_and_address_writes_share_the_latch() {
+        let mut loopy = LoopyRegisters::new();
+        loopy.write_control(0b10);
+        loopy.write_scroll(0b0111_1101); // coarse X 15, fine X 5
+        loopy.write_scroll(0b0101_1110); // coarse Y 11, fine Y 6
+        assert_equal!(loopy.t, (6 << 12) | (0b10 << 10) | (11 << 5) | 15);
+        assert_equal!(loopy.x, 5);
+        assert_equal!(loopy.v, 0);
+
+        loopy.write_address(0x3D);
+        loopy.write_address(0xF0);
+        assert_equal!(loopy.v, 0x3DF0);
+        assert_equal!(loopy.w, false);
+    }
+
+    #[test]
+    fn test_increment_wraps_into_adjacent_nametables() {
+        let mut loopy = LoopyRegisters::new();
+        loopy.v = 31;
+        loopy.increment_x();
+        assert_equal!(loopy.v, NAMETABLE_X);
+
+        loopy.v = FINE_Y | (29 << 5);
+        loopy.increment_y();
+        assert_equal!(loopy.v, NAMETABLE_Y);
+
+        // Coarse Y 31 is in the attribute table and wraps without switching nametable
+        loopy.v = FINE_Y | (31 << 5);
+        loopy.increment_y();
+        assert_equal!(loopy.v, 0);
+    }
+}
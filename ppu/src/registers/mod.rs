@@ -1,5 +1,4 @@
-pub mod address;
 pub mod control;
+pub mod loopy;
 pub mod mask;
-pub mod scroll;
 pub mod status;
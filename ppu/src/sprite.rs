@@ -0,0 +1,82 @@
+use bitflags::bitflags;
+use core::snapshot::{StateReader, StateWriter};
+
+pub const MAX_SPRITES_PER_SCANLINE: usize = 8;
+
+bitflags! {
+    /// # Sprite attributes (OAM byte 2) https://www.nesdev.org/wiki/PPU_OAM
+    ///
+    /// 76543210
+    /// ||||||||
+    /// ||||||++- Palette (4 to 7) of sprite
+    /// |||+++--- Unimplemented (read 0)
+    /// ||+------ Priority (0: in front of background; 1: behind background)
+    /// |+------- Flip sprite horizontally
+    /// +-------- Flip sprite vertically
+    ///
+    pub struct SpriteAttribute: u8 {
+        const PALETTE1                  = 0b00000001;
+        const PALETTE2                  = 0b00000010;
+        const UNUSED1                   = 0b00000100;
+        const UNUSED2                   = 0b00001000;
+        const UNUSED3                   = 0b00010000;
+        const PRIORITY_FG_OR_BG         = 0b00100000;
+        const FLIP_SPRITE_HORIZONTALLY  = 0b01000000;
+        const FLIP_SPRITE_VERTICALLY    = 0b10000000;
+    }
+}
+
+impl SpriteAttribute {
+    pub fn palette(&self) -> u8 {
+        self.bits & (SpriteAttribute::PALETTE1.bits | SpriteAttribute::PALETTE2.bits)
+    }
+
+    pub fn behind_background(&self) -> bool {
+        self.contains(SpriteAttribute::PRIORITY_FG_OR_BG)
+    }
+}
+
+/// A sprite found by the evaluation of a scanline, with the pattern row already fetched and
+/// flipped so the leftmost pixel is bit 7.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ScanlineSprite {
+    /// Sprite 0 in OAM, used for the sprite zero hit
+    pub sprite_zero: bool,
+    pub x: u8,
+    pub attributes: SpriteAttribute,
+    pub pattern_lo: u8,
+    pub pattern_hi: u8,
+}
+
+impl ScanlineSprite {
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.sprite_zero);
+        state.write_u8(self.x);
+        state.write_u8(self.attributes.bits());
+        state.write_u8(self.pattern_lo);
+        state.write_u8(self.pattern_hi);
+    }
+
+    pub fn load_state(state: &mut StateReader) -> Result<Self, String> {
+        Ok(ScanlineSprite {
+            sprite_zero: state.read_bool()?,
+            x: state.read_u8()?,
+            attributes: SpriteAttribute::from_bits_truncate(state.read_u8()?),
+            pattern_lo: state.read_u8()?,
+            pattern_hi: state.read_u8()?,
+        })
+    }
+
+    /// Returns the (palette, pixel) pair of the sprite at screen column `x`, pixel 0 being
+    /// transparent.
+    pub fn pixel(&self, x: usize) -> (u8, u8) {
+        let column = x.wrapping_sub(self.x as usize);
+        if column >= 8 {
+            return (0, 0);
+        }
+
+        let bit = |plane: u8| (plane >> (7 - column)) & 1;
+        let pixel = (bit(self.pattern_hi) << 1) | bit(self.pattern_lo);
+        (self.attributes.palette(), pixel)
+    }
+}
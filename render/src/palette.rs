@@ -1,7 +1,4 @@
-use ppu::PPU;
-
 #[rustfmt::skip]
-
 pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
     (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96), (0xA1, 0x00, 0x5E),
     (0xC7, 0x00, 0x28), (0xBA, 0x06, 0x00), (0x8C, 0x17, 0x00), (0x5C, 0x2F, 0x00), (0x10, 0x45, 0x00),
@@ -17,39 +14,3 @@ pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
     (0xFF, 0xEF, 0xA6), (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
     (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11)
 ];
-
-pub(crate) fn background_palette(
-    ppu: &PPU,
-    attribute_table: &[u8],
-    tile_column: usize,
-    tile_row: usize,
-) -> [u8; 4] {
-    let table_index = tile_row / 4 * 8 + tile_column / 4;
-    let attribute_byte = attribute_table[table_index];
-
-    let palette_index = match (tile_column % 4 / 2, tile_row % 4 / 2) {
-        (0, 0) => attribute_byte & 0b11,
-        (1, 0) => (attribute_byte >> 2) & 0b11,
-        (0, 1) => (attribute_byte >> 4) & 0b11,
-        (1, 1) => (attribute_byte >> 6) & 0b11,
-        (_, _) => panic!("should not happen"),
-    };
-
-    let palette_start = 1 + (palette_index as usize) * 4;
-    [
-        ppu.palette_table[0],
-        ppu.palette_table[palette_start],
-        ppu.palette_table[palette_start + 1],
-        ppu.palette_table[palette_start + 2],
-    ]
-}
-
-pub(crate) fn sprite_palette(ppu: &PPU, index: u8) -> [u8; 4] {
-    let start = 0x11 + (index * 4) as usize;
-    [
-        0,
-        ppu.palette_table[start],
-        ppu.palette_table[start + 1],
-        ppu.palette_table[start + 2],
-    ]
-}
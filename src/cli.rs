@@ -0,0 +1,208 @@
+use apu::DEFAULT_SAMPLE_RATE;
+use core::region::Region;
+use emulator::cartridge::ExpansionDevice;
+
+pub struct WavOutput {
+    pub path: String,
+    pub frames: usize,
+}
+
+pub struct Options {
+    pub rom_path: String,
+    pub sample_rate: u32,
+    /// Run without a window, writing the audio of the first `frames` frames to a WAV file
+    pub wav_output: Option<WavOutput>,
+    /// FM2 movie to write the input of every frame to
+    pub record_movie: Option<String>,
+    /// FM2 movie to take the input from instead of the keyboard
+    pub play_movie: Option<String>,
+    /// Plug a Zapper, aimed with the mouse, into the second port
+    pub zapper: bool,
+    /// Plug a Four Score into both ports, for up to four players
+    pub four_score: bool,
+    /// Run as this console instead of the one the ROM header names
+    pub region: Option<Region>,
+}
+
+impl Options {
+    /// Plugs in what the NES 2.0 header of the game asks for, unless a device was picked on the
+    /// command line.
+    pub fn apply_expansion_device(&mut self, device: ExpansionDevice) {
+        if self.zapper || self.four_score {
+            return;
+        }
+        match device {
+            ExpansionDevice::Zapper => self.zapper = true,
+            ExpansionDevice::FourScore => self.four_score = true,
+            _ => {}
+        }
+    }
+}
+
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} <filename> [--sample-rate <hz>] [--wav <output.wav> --frames <count>] \
+         [--record <movie.fm2> | --play <movie.fm2>] [--zapper | --four-score] \
+         [--region <ntsc|pal|dendy>]",
+        program
+    )
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+    let mut wav_path = None;
+    let mut frames = None;
+    let mut record_movie = None;
+    let mut play_movie = None;
+    let mut zapper = false;
+    let mut four_score = false;
+    let mut region = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--sample-rate" => sample_rate = parse_value(arg, iter.next())?,
+            "--wav" => wav_path = Some(iter.next().ok_or("--wav needs a path")?.clone()),
+            "--frames" => frames = Some(parse_value(arg, iter.next())?),
+            "--record" => record_movie = Some(iter.next().ok_or("--record needs a path")?.clone()),
+            "--zapper" => zapper = true,
+            "--four-score" => four_score = true,
+            "--region" => {
+                let name = iter.next().ok_or("--region needs a name")?;
+                region = Some(Region::from_name(name).ok_or(format!(
+                    "Unknown region {}, expected ntsc, pal or dendy",
+                    name
+                ))?);
+            }
+            "--play" => play_movie = Some(iter.next().ok_or("--play needs a path")?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    let wav_output = match (wav_path, frames) {
+        (Some(path), Some(frames)) => Some(WavOutput { path, frames }),
+        (None, None) => None,
+        _ => return Err("--wav and --frames must be given together".to_string()),
+    };
+    if record_movie.is_some() && (play_movie.is_some() || wav_output.is_some()) {
+        return Err("--record can't be combined with --play or --wav".to_string());
+    }
+    if zapper && four_score {
+        return Err("--zapper and --four-score both need the second port".to_string());
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or("Missing ROM filename")?,
+        sample_rate,
+        wav_output,
+        record_movie,
+        play_movie,
+        zapper,
+        four_score,
+        region,
+    })
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or(format!("{} needs a number", option))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rom_only() {
+        let options = parse_args(&args(&["nes", "game.nes"])).unwrap();
+        assert_equal!(options.rom_path, "game.nes");
+        assert_equal!(options.sample_rate, DEFAULT_SAMPLE_RATE);
+        assert!(options.wav_output.is_none());
+        assert!(options.record_movie.is_none());
+        assert!(options.play_movie.is_none());
+        assert!(!options.zapper);
+        assert!(!options.four_score);
+    }
+
+    #[test]
+    fn test_headless_wav_output() {
+        let options = parse_args(&args(&[
+            "nes",
+            "--wav",
+            "out.wav",
+            "game.nes",
+            "--frames",
+            "120",
+            "--sample-rate",
+            "48000",
+        ]))
+        .unwrap();
+
+        let wav_output = options.wav_output.unwrap();
+        assert_equal!(wav_output.path, "out.wav");
+        assert_equal!(wav_output.frames, 120);
+        assert_equal!(options.sample_rate, 48000);
+    }
+
+    #[test]
+    fn test_movie_options() {
+        let options = parse_args(&args(&["nes", "game.nes", "--record", "run.fm2"])).unwrap();
+        assert_equal!(options.record_movie, Some("run.fm2".to_string()));
+        assert!(!options.zapper);
+
+        let options = parse_args(&args(&["nes", "--zapper", "game.nes"])).unwrap();
+        assert!(options.zapper);
+        assert!(parse_args(&args(&["nes", "--zapper", "--four-score", "game.nes"])).is_err());
+
+        let options = parse_args(&args(&[
+            "nes", "game.nes", "--play", "run.fm2", "--wav", "out.wav", "--frames", "60",
+        ]))
+        .unwrap();
+        assert_equal!(options.play_movie, Some("run.fm2".to_string()));
+        assert!(options.wav_output.is_some());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(parse_args(&args(&["nes"])).is_err());
+        assert!(parse_args(&args(&["nes", "game.nes", "--wav", "out.wav"])).is_err());
+        assert!(parse_args(&args(&["nes", "game.nes", "--frames", "many"])).is_err());
+        assert!(parse_args(&args(&["nes", "a.nes", "b.nes"])).is_err());
+        assert!(parse_args(&args(&[
+            "nes", "game.nes", "--record", "a.fm2", "--play", "b.fm2"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_expansion_device_from_header() {
+        let mut options = parse_args(&args(&["nes", "game.nes"])).unwrap();
+        options.apply_expansion_device(ExpansionDevice::Joypads);
+        assert!(!options.zapper && !options.four_score);
+        options.apply_expansion_device(ExpansionDevice::Zapper);
+        assert!(options.zapper);
+
+        let mut options = parse_args(&args(&["nes", "--four-score", "game.nes"])).unwrap();
+        options.apply_expansion_device(ExpansionDevice::Zapper);
+        assert!(!options.zapper);
+    }
+
+    #[test]
+    fn test_region() {
+        let options = parse_args(&args(&["nes", "game.nes"])).unwrap();
+        assert_equal!(options.region, None);
+
+        let options = parse_args(&args(&["nes", "--region", "pal", "game.nes"])).unwrap();
+        assert_equal!(options.region, Some(Region::Pal));
+        assert!(parse_args(&args(&["nes", "--region", "secam", "game.nes"])).is_err());
+    }
+}
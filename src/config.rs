@@ -0,0 +1,341 @@
+use emulator::joypad::JoypadButton;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const CONFIG_DIR: &str = "nes_emulator_rs";
+const CONFIG_FILE: &str = "config.toml";
+/// The button names of SDL's game controller mappings.
+const GAMEPAD_BUTTONS: [&str; 21] = [
+    "a",
+    "b",
+    "x",
+    "y",
+    "back",
+    "guide",
+    "start",
+    "leftstick",
+    "rightstick",
+    "leftshoulder",
+    "rightshoulder",
+    "dpup",
+    "dpdown",
+    "dpleft",
+    "dpright",
+    "misc1",
+    "paddle1",
+    "paddle2",
+    "paddle3",
+    "paddle4",
+    "touchpad",
+];
+
+/// Frontend settings, read from `config.toml` in the user's config directory
+/// (`~/.config/nes_emulator_rs/config.toml` on Linux). Everything left out keeps its default.
+///
+/// Keys are named as SDL names them (`Up`, `Return`, `Keypad 8`, `F1`), game controller buttons
+/// in SDL's game controller mapping names (`a`, `back`, `dpup`).
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub window_scale: f32,
+    pub screenshot_dir: PathBuf,
+    /// How far a game controller's stick has to be pushed to count as the d-pad, up to 32767
+    pub stick_threshold: i16,
+    pub hotkeys: Hotkeys,
+    #[serde(deserialize_with = "JoypadBindings::deserialize_player1")]
+    pub player1: JoypadBindings,
+    #[serde(deserialize_with = "JoypadBindings::deserialize_player2")]
+    pub player2: JoypadBindings,
+    #[serde(deserialize_with = "JoypadBindings::deserialize_player3")]
+    pub player3: JoypadBindings,
+    #[serde(deserialize_with = "JoypadBindings::deserialize_player4")]
+    pub player4: JoypadBindings,
+    #[serde(deserialize_with = "JoypadBindings::deserialize_gamepad")]
+    pub gamepad: JoypadBindings,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hotkeys {
+    pub screenshot: String,
+    pub rewind: String,
+    pub reset: String,
+    pub power_cycle: String,
+    /// One key per save state slot
+    pub save_state: Vec<String>,
+    pub load_state: Vec<String>,
+}
+
+/// The keys or game controller buttons for the buttons of one joypad.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JoypadBindings {
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+    pub select: String,
+    pub start: String,
+    pub a: String,
+    pub b: String,
+}
+
+/// A `[playerN]` or `[gamepad]` table as written, the buttons left out keep the defaults of
+/// that table.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct JoypadOverrides {
+    up: Option<String>,
+    down: Option<String>,
+    left: Option<String>,
+    right: Option<String>,
+    select: Option<String>,
+    start: Option<String>,
+    a: Option<String>,
+    b: Option<String>,
+}
+
+impl JoypadOverrides {
+    fn apply(self, defaults: JoypadBindings) -> JoypadBindings {
+        JoypadBindings {
+            up: self.up.unwrap_or(defaults.up),
+            down: self.down.unwrap_or(defaults.down),
+            left: self.left.unwrap_or(defaults.left),
+            right: self.right.unwrap_or(defaults.right),
+            select: self.select.unwrap_or(defaults.select),
+            start: self.start.unwrap_or(defaults.start),
+            a: self.a.unwrap_or(defaults.a),
+            b: self.b.unwrap_or(defaults.b),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// Reads the config file, the defaults are used when there is none.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|err| err.to_string())?;
+        if config.window_scale.is_nan() || config.window_scale < 1.0 {
+            return Err("window_scale: must be at least 1".to_string());
+        }
+        if config.stick_threshold <= 0 {
+            return Err("stick_threshold: must be above 0".to_string());
+        }
+
+        let mut gamepad_buttons = HashSet::new();
+        for (setting, _, name) in config.gamepad.buttons() {
+            if !GAMEPAD_BUTTONS.contains(&name) {
+                return Err(format!("gamepad.{}: unknown button \"{}\"", setting, name));
+            }
+            if !gamepad_buttons.insert(name) {
+                return Err(format!("gamepad.{}: \"{}\" is bound twice", setting, name));
+            }
+        }
+        Ok(config)
+    }
+
+    /// The keyboard bindings of each player, with the name of their table.
+    pub fn players(&self) -> [(&'static str, &JoypadBindings); 4] {
+        [
+            ("player1", &self.player1),
+            ("player2", &self.player2),
+            ("player3", &self.player3),
+            ("player4", &self.player4),
+        ]
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            window_scale: 3.0,
+            screenshot_dir: PathBuf::from("."),
+            stick_threshold: 16_000,
+            hotkeys: Hotkeys::default(),
+            player1: JoypadBindings::player1(),
+            player2: JoypadBindings::player2(),
+            player3: JoypadBindings::player3(),
+            player4: JoypadBindings::player4(),
+            gamepad: JoypadBindings::gamepad(),
+        }
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Hotkeys {
+            screenshot: "G".to_string(),
+            rewind: "Backspace".to_string(),
+            reset: "F11".to_string(),
+            power_cycle: "F12".to_string(),
+            save_state: names(&["F1", "F2", "F3", "F4"]),
+            load_state: names(&["F5", "F6", "F7", "F8"]),
+        }
+    }
+}
+
+impl JoypadBindings {
+    fn new(buttons: [&str; 8]) -> Self {
+        let [up, down, left, right, select, start, a, b] = buttons.map(String::from);
+        JoypadBindings {
+            up,
+            down,
+            left,
+            right,
+            select,
+            start,
+            a,
+            b,
+        }
+    }
+
+    fn player1() -> Self {
+        JoypadBindings::new(["Up", "Down", "Left", "Right", "Space", "Return", "A", "S"])
+    }
+
+    fn player2() -> Self {
+        JoypadBindings::new(["I", "K", "J", "L", "9", "0", "P", "O"])
+    }
+
+    fn player3() -> Self {
+        JoypadBindings::new([
+            "Keypad 8", "Keypad 5", "Keypad 4", "Keypad 6", "Keypad 7", "Keypad 9", "Keypad 3",
+            "Keypad 1",
+        ])
+    }
+
+    fn player4() -> Self {
+        JoypadBindings::new(["W", "X", "Q", "E", "1", "2", "C", "Z"])
+    }
+
+    /// Laid out like on the NES pad, B to the left of A.
+    fn gamepad() -> Self {
+        JoypadBindings::new([
+            "dpup", "dpdown", "dpleft", "dpright", "back", "start", "b", "a",
+        ])
+    }
+
+    fn deserialize_over<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        defaults: JoypadBindings,
+    ) -> Result<Self, D::Error> {
+        Ok(JoypadOverrides::deserialize(deserializer)?.apply(defaults))
+    }
+
+    fn deserialize_player1<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JoypadBindings::deserialize_over(deserializer, JoypadBindings::player1())
+    }
+
+    fn deserialize_player2<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JoypadBindings::deserialize_over(deserializer, JoypadBindings::player2())
+    }
+
+    fn deserialize_player3<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JoypadBindings::deserialize_over(deserializer, JoypadBindings::player3())
+    }
+
+    fn deserialize_player4<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JoypadBindings::deserialize_over(deserializer, JoypadBindings::player4())
+    }
+
+    fn deserialize_gamepad<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JoypadBindings::deserialize_over(deserializer, JoypadBindings::gamepad())
+    }
+
+    /// Each binding with its name in the config and the button it's for.
+    pub fn buttons(&self) -> [(&'static str, JoypadButton, &str); 8] {
+        [
+            ("up", JoypadButton::UP, &self.up),
+            ("down", JoypadButton::DOWN, &self.down),
+            ("left", JoypadButton::LEFT, &self.left),
+            ("right", JoypadButton::RIGHT, &self.right),
+            ("select", JoypadButton::SELECT, &self.select),
+            ("start", JoypadButton::START, &self.start),
+            ("a", JoypadButton::BUTTON_A, &self.a),
+            ("b", JoypadButton::BUTTON_B, &self.b),
+        ]
+    }
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    #[test]
+    fn test_empty_config_is_the_default() {
+        assert_equal!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_partial_config() {
+        let config = Config::parse(
+            r#"
+            window_scale = 2.0
+            screenshot_dir = "/tmp/shots"
+
+            [hotkeys]
+            rewind = "R"
+
+            [player2]
+            up = "T"
+
+            [gamepad]
+            a = "x"
+            "#,
+        )
+        .unwrap();
+
+        assert_equal!(config.window_scale, 2.0);
+        assert_equal!(config.screenshot_dir, PathBuf::from("/tmp/shots"));
+        assert_equal!(config.hotkeys.rewind, "R");
+        assert_equal!(config.hotkeys.screenshot, "G");
+        assert_equal!(config.player2.up, "T");
+        assert_equal!(config.player2.down, JoypadBindings::player2().down);
+        assert_equal!(config.player1, JoypadBindings::player1());
+        assert_equal!(config.gamepad.a, "x");
+        assert_equal!(config.gamepad.b, JoypadBindings::gamepad().b);
+    }
+
+    #[test]
+    fn test_errors_name_the_bad_key() {
+        let err = Config::parse("[hotkeys]\nscreenshoot = \"G\"\n").unwrap_err();
+        assert!(err.contains("screenshoot"), "{}", err);
+
+        let err = Config::parse("[player1]\njump = \"W\"\n").unwrap_err();
+        assert!(err.contains("jump"), "{}", err);
+
+        let err = Config::parse("window_scale = \"big\"\n").unwrap_err();
+        assert!(err.contains("window_scale"), "{}", err);
+
+        let err = Config::parse("window_scale = 0.5\n").unwrap_err();
+        assert!(err.starts_with("window_scale"), "{}", err);
+
+        let err = Config::parse("[gamepad]\nstart = \"select\"\n").unwrap_err();
+        assert_equal!(err, "gamepad.start: unknown button \"select\"");
+
+        let err = Config::parse("[gamepad]\nstart = \"a\"\n").unwrap_err();
+        assert_equal!(err, "gamepad.b: \"a\" is bound twice");
+    }
+
+    #[test]
+    fn test_missing_file_gives_defaults() {
+        let config = Config::load(Path::new("/nonexistent/config.toml")).unwrap();
+        assert_equal!(config, Config::default());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::config::Config;
+use crate::input::{InputButton, InputEvent, Player};
+use emulator::joypad::JoypadButton;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use std::collections::HashMap;
+
+struct Gamepad {
+    // The device stays open as long as this is kept
+    _controller: GameController,
+    player: Player,
+    // The directions the left stick is pushed in
+    stick: JoypadButton,
+}
+
+/// SDL game controllers, each driving the joypad of one player. A pad gets the first player
+/// without one when it's plugged in, so the first pad plays along with the keyboard.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    // By joystick instance id
+    pads: HashMap<u32, Gamepad>,
+    buttons: HashMap<Button, JoypadButton>,
+    stick_threshold: i16,
+}
+
+impl Gamepads {
+    pub fn new(subsystem: GameControllerSubsystem, config: &Config) -> Result<Self, String> {
+        let mut buttons = HashMap::new();
+        for (setting, button, name) in config.gamepad.buttons() {
+            let controller_button = Button::from_string(name)
+                .ok_or(format!("gamepad.{}: unknown button \"{}\"", setting, name))?;
+            buttons.insert(controller_button, button);
+        }
+
+        Ok(Gamepads {
+            subsystem,
+            pads: HashMap::new(),
+            buttons,
+            stick_threshold: config.stick_threshold,
+        })
+    }
+
+    /// Turns controller events into input for the joypads. SDL reports the pads that are
+    /// already connected at startup as plugged in too.
+    pub fn handle_event(&mut self, event: &Event, input_events: &mut Vec<InputEvent>) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => self.plug(which),
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(pad) = self.pads.remove(&which) {
+                    println!("Controller of player {} unplugged", pad.player as usize + 1);
+                    release(pad.player, JoypadButton::all(), input_events);
+                }
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                if let (Some(pad), Some(&button)) =
+                    (self.pads.get(&which), self.buttons.get(&button))
+                {
+                    input_events.push(InputEvent::pressed(InputButton::Joypad(pad.player, button)));
+                }
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                if let (Some(pad), Some(&button)) =
+                    (self.pads.get(&which), self.buttons.get(&button))
+                {
+                    release(pad.player, button, input_events);
+                }
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                if let Some(pad) = self.pads.get_mut(&which) {
+                    let (directions, pushed) = stick_directions(axis, value, self.stick_threshold);
+                    let stick = (pad.stick - directions) | pushed;
+                    press(pad.player, stick - pad.stick, input_events);
+                    release(pad.player, pad.stick - stick, input_events);
+                    pad.stick = stick;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn plug(&mut self, joystick_index: u32) {
+        let controller = match self.subsystem.open(joystick_index) {
+            Ok(controller) => controller,
+            Err(err) => {
+                eprintln!("Unable to open controller {}: {}", joystick_index, err);
+                return;
+            }
+        };
+        if self.pads.contains_key(&controller.instance_id()) {
+            return;
+        }
+
+        let free_player = Player::ALL
+            .into_iter()
+            .find(|&player| self.pads.values().all(|pad| pad.player != player));
+        let Some(player) = free_player else {
+            println!(
+                "Ignoring controller {}, all players have one",
+                controller.name()
+            );
+            return;
+        };
+
+        println!(
+            "Controller {} plugged in for player {}",
+            controller.name(),
+            player as usize + 1
+        );
+        self.pads.insert(
+            controller.instance_id(),
+            Gamepad {
+                _controller: controller,
+                player,
+                stick: JoypadButton::empty(),
+            },
+        );
+    }
+}
+
+fn press(player: Player, buttons: JoypadButton, input_events: &mut Vec<InputEvent>) {
+    for button in each_button(buttons) {
+        input_events.push(InputEvent::pressed(InputButton::Joypad(player, button)));
+    }
+}
+
+fn release(player: Player, buttons: JoypadButton, input_events: &mut Vec<InputEvent>) {
+    for button in each_button(buttons) {
+        input_events.push(InputEvent::released(InputButton::Joypad(player, button)));
+    }
+}
+
+fn each_button(buttons: JoypadButton) -> impl Iterator<Item = JoypadButton> {
+    (0..8)
+        .map(|bit| JoypadButton::from_bits_truncate(1 << bit))
+        .filter(move |&button| buttons.contains(button))
+}
+
+/// The d-pad directions an axis of the left stick moves along, and the one it's pushed in.
+fn stick_directions(axis: Axis, value: i16, threshold: i16) -> (JoypadButton, JoypadButton) {
+    let (negative, positive) = match axis {
+        Axis::LeftX => (JoypadButton::LEFT, JoypadButton::RIGHT),
+        Axis::LeftY => (JoypadButton::UP, JoypadButton::DOWN),
+        _ => return (JoypadButton::empty(), JoypadButton::empty()),
+    };
+
+    let pushed = match value {
+        value if value <= -threshold => negative,
+        value if value >= threshold => positive,
+        _ => JoypadButton::empty(),
+    };
+    (negative | positive, pushed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    #[test]
+    fn test_stick_thresholds() {
+        let horizontal = JoypadButton::LEFT | JoypadButton::RIGHT;
+        assert_equal!(
+            stick_directions(Axis::LeftX, -20_000, 16_000),
+            (horizontal, JoypadButton::LEFT)
+        );
+        assert_equal!(
+            stick_directions(Axis::LeftX, 8_000, 16_000),
+            (horizontal, JoypadButton::empty())
+        );
+        assert_equal!(
+            stick_directions(Axis::LeftY, i16::MAX, 16_000),
+            (JoypadButton::UP | JoypadButton::DOWN, JoypadButton::DOWN)
+        );
+        assert_equal!(
+            stick_directions(Axis::TriggerLeft, i16::MAX, 16_000),
+            (JoypadButton::empty(), JoypadButton::empty())
+        );
+    }
+
+    #[test]
+    fn test_each_button() {
+        let buttons: Vec<_> = each_button(JoypadButton::START | JoypadButton::BUTTON_A).collect();
+        assert_equal!(buttons, vec![JoypadButton::BUTTON_A, JoypadButton::START]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::Config;
 use emulator::joypad::JoypadButton;
 use sdl2::keyboard::Keycode;
 use std::collections::HashMap;
@@ -5,11 +6,33 @@ use std::collections::HashMap;
 #[derive(Copy, Clone)]
 pub enum InputAction {
     CaptureScreenshot,
+    SaveState(u8),
+    LoadState(u8),
+    Rewind,
+    Reset,
+    PowerCycle,
+    Quit,
+}
+
+/// Players 3 and 4 need a Four Score.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+impl Player {
+    pub const ALL: [Player; 4] = [Player::One, Player::Two, Player::Three, Player::Four];
 }
 
 #[derive(Copy, Clone)]
 pub enum InputButton {
-    Joypad(JoypadButton),
+    Joypad(Player, JoypadButton),
+    ZapperTrigger,
+    /// The pixel of the picture under the mouse, None when it's outside the window
+    ZapperAim(Option<(usize, usize)>),
     Key(InputAction),
 }
 
@@ -34,18 +57,62 @@ impl InputEvent {
     }
 }
 
-pub fn create_keymap() -> HashMap<Keycode, InputButton> {
+/// Resolves the key names in the config. Errors name the setting with the bad or clashing key.
+pub fn create_keymap(config: &Config) -> Result<HashMap<Keycode, InputButton>, String> {
     let mut key_map: HashMap<Keycode, InputButton> = HashMap::new();
-    key_map.insert(Keycode::Down, InputButton::Joypad(JoypadButton::DOWN));
-    key_map.insert(Keycode::Up, InputButton::Joypad(JoypadButton::UP));
-    key_map.insert(Keycode::Right, InputButton::Joypad(JoypadButton::RIGHT));
-    key_map.insert(Keycode::Left, InputButton::Joypad(JoypadButton::LEFT));
-    key_map.insert(Keycode::Space, InputButton::Joypad(JoypadButton::SELECT));
-    key_map.insert(Keycode::Return, InputButton::Joypad(JoypadButton::START));
-    key_map.insert(Keycode::A, InputButton::Joypad(JoypadButton::BUTTON_A));
-    key_map.insert(Keycode::S, InputButton::Joypad(JoypadButton::BUTTON_B));
-
-    key_map.insert(Keycode::G, InputButton::Key(InputAction::CaptureScreenshot));
-
-    key_map
+    let mut bind = |setting: String, name: &str, input: InputButton| {
+        let key =
+            Keycode::from_name(name).ok_or(format!("{}: unknown key \"{}\"", setting, name))?;
+        match key_map.insert(key, input) {
+            Some(_) => Err(format!("{}: \"{}\" is bound twice", setting, name)),
+            None => Ok(()),
+        }
+    };
+
+    for ((table, bindings), player) in config.players().into_iter().zip(Player::ALL) {
+        for (setting, button, name) in bindings.buttons() {
+            let setting = format!("{}.{}", table, setting);
+            bind(setting, name, InputButton::Joypad(player, button))?;
+        }
+    }
+
+    let hotkeys = &config.hotkeys;
+    let actions = [
+        (
+            "screenshot",
+            &hotkeys.screenshot,
+            InputAction::CaptureScreenshot,
+        ),
+        ("rewind", &hotkeys.rewind, InputAction::Rewind),
+        ("reset", &hotkeys.reset, InputAction::Reset),
+        ("power_cycle", &hotkeys.power_cycle, InputAction::PowerCycle),
+    ];
+    for (setting, name, action) in actions {
+        bind(
+            format!("hotkeys.{}", setting),
+            name,
+            InputButton::Key(action),
+        )?;
+    }
+
+    for (index, name) in hotkeys.save_state.iter().enumerate() {
+        let slot = index as u8 + 1;
+        let setting = format!("hotkeys.save_state[{}]", index);
+        bind(
+            setting,
+            name,
+            InputButton::Key(InputAction::SaveState(slot)),
+        )?;
+    }
+    for (index, name) in hotkeys.load_state.iter().enumerate() {
+        let slot = index as u8 + 1;
+        let setting = format!("hotkeys.load_state[{}]", index);
+        bind(
+            setting,
+            name,
+            InputButton::Key(InputAction::LoadState(slot)),
+        )?;
+    }
+
+    Ok(key_map)
 }
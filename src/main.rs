@@ -1,86 +1,497 @@
+mod cli;
+mod config;
+mod gamepad;
 mod input;
+mod pacing;
 
+use crate::cli::{Options, WavOutput};
+use crate::config::Config;
+use crate::gamepad::Gamepads;
 use crate::input::{create_keymap, InputAction, InputButton, InputEvent};
+use crate::pacing::FramePacer;
+use apu::wav::WavWriter;
+use core::region::Region;
+use core::snapshot::{Snapshot, StateReader, StateWriter};
 use cpu6502::cpu::CPU;
+use emulator::battery::BatteryRam;
 use emulator::bus::NESBus;
 use emulator::cartridge::Rom;
-use emulator::joypad::Joypad;
+use emulator::controller::{FourScore, CONTROLLER_PORTS};
+use emulator::joypad::JoypadButton;
+use emulator::movie;
+use emulator::movie::{Movie, MovieCommand, MoviePlayer, MovieRecorder, MAX_PLAYERS};
+use emulator::rewind::RewindBuffer;
+use emulator::savestate;
+use emulator::zapper::Zapper;
 use ppu::PPU;
 use render::frame::Frame;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::event::Event;
+use sdl2::event::WindowEvent;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::surface::Surface;
 use sdl2::EventPump;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::path::Path;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
+use std::time::Instant;
 use std::{env, thread};
 
-const WINDOW_SCALE: f32 = 3.0;
+const AUDIO_BUFFER_SAMPLES: u16 = 1024;
+// If the queue grows past this, the emulator is running ahead of the sound card. Drop what's
+// queued rather than let the audio lag further and further behind the picture.
+const MAX_QUEUED_AUDIO_SECONDS: f32 = 0.1;
+
+// Rewinding goes back 2 frames per frame shown, with at most 64 MiB of history
+const REWIND_INTERVAL_FRAMES: usize = 2;
+const REWIND_BUFFER_BYTES: usize = 64 * 1024 * 1024;
+
+// Battery-backed saves are written about once a second while they change
+const BATTERY_FLUSH_INTERVAL_FRAMES: usize = 60;
+
+type FrameOutput = (Frame, Vec<f32>);
+
+/// Save states, rewinding and resets need the CPU registers, which the gameloop can't reach from
+/// inside the bus. The gameloop leaves its requests here, and the CPU loop carries them out
+/// between two instructions.
+#[derive(Default)]
+struct FrontendRequests {
+    state_action: Cell<Option<InputAction>>,
+    rewinding: Cell<bool>,
+    frame_ended: Cell<bool>,
+    commands: Cell<MovieCommand>,
+    quit: Cell<bool>,
+}
+
+impl FrontendRequests {
+    fn handle_input(&self, key_event: &InputEvent) {
+        match key_event.button {
+            InputButton::Key(InputAction::Rewind) => self.rewinding.set(key_event.key_down),
+            InputButton::Key(action @ (InputAction::SaveState(_) | InputAction::LoadState(_)))
+                if !key_event.key_down =>
+            {
+                self.state_action.set(Some(action))
+            }
+            InputButton::Key(InputAction::Reset) if !key_event.key_down => {
+                self.add_commands(MovieCommand::SOFT_RESET)
+            }
+            InputButton::Key(InputAction::PowerCycle) if !key_event.key_down => {
+                self.add_commands(MovieCommand::POWER)
+            }
+            InputButton::Key(InputAction::Quit) => self.quit.set(true),
+            _ => {}
+        }
+    }
+
+    fn add_commands(&self, commands: MovieCommand) {
+        self.commands.set(self.commands.get() | commands);
+    }
+}
+
+/// The movie being played back or recorded, if any.
+#[derive(Default)]
+struct MovieSession {
+    player: Option<MoviePlayer>,
+    recorder: Option<MovieRecorder<File>>,
+    fourscore: bool,
+}
+
+impl MovieSession {
+    fn new(options: &Options, rom: &Rom, region: Region) -> Self {
+        let mut session = MovieSession {
+            fourscore: options.four_score,
+            ..MovieSession::default()
+        };
+        if let Some(path) = &options.play_movie {
+            let movie = std::fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Movie::parse(&text))
+                .unwrap_or_else(|err| {
+                    eprintln!("Unable to load movie {}: {}", path, err);
+                    std::process::exit(1);
+                });
+            if movie.rom_checksum != movie::rom_checksum(rom) {
+                eprintln!("Warning: {} was recorded with a different ROM", path);
+            }
+            if movie.pal != (region == Region::Pal) {
+                eprintln!("Warning: {} was recorded on another region", path);
+            }
+            session.fourscore |= movie.fourscore;
+            session.player = Some(MoviePlayer::new(movie));
+        }
+        if let Some(path) = &options.record_movie {
+            let rom_filename = Path::new(&options.rom_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut movie = Movie::new(&rom_filename, rom);
+            movie.fourscore = session.fourscore;
+            movie.pal = region == Region::Pal;
+            let recorder = File::create(path)
+                .and_then(|file| MovieRecorder::new(file, &movie))
+                .unwrap_or_else(|err| {
+                    eprintln!("Unable to record movie {}: {}", path, err);
+                    std::process::exit(1);
+                });
+            session.recorder = Some(recorder);
+        }
+        session
+    }
+
+    fn is_playing(&self) -> bool {
+        self.player.is_some()
+    }
+
+    /// Decides the input of the next frame: the movie's while one is playing, otherwise what the
+    /// player asked for, which gets recorded. Returns the commands to carry out before the frame.
+    fn next_frame(&mut self, bus: &mut NESBus, requested: MovieCommand) -> MovieCommand {
+        let mut commands = requested;
+        if let Some(player) = self.player.as_mut() {
+            match player.play_frame(bus) {
+                Some(movie_commands) => commands = movie_commands,
+                None => {
+                    println!("Movie ended");
+                    for player in 0..MAX_PLAYERS {
+                        if let Some(joypad) = bus.joypad_mut(player) {
+                            joypad.set_buttons(JoypadButton::empty());
+                        }
+                    }
+                    self.player = None;
+                }
+            }
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = recorder.record_frame(commands, bus) {
+                eprintln!("Unable to record movie: {}", err);
+                self.recorder = None;
+            }
+        }
+        commands
+    }
+}
+
+/// The save file of a cartridge with a battery, kept next to the ROM.
+struct SaveFile {
+    ram: BatteryRam,
+    path: PathBuf,
+}
+
+impl SaveFile {
+    /// Movies are played and recorded from a blank save, so they play back the same.
+    fn open(bus: &NESBus, battery: bool, options: &Options) -> Option<Self> {
+        if !battery || options.play_movie.is_some() || options.record_movie.is_some() {
+            return None;
+        }
+
+        let mut ram = BatteryRam::new(bus.mapper()?, BATTERY_FLUSH_INTERVAL_FRAMES)?;
+        let path = Path::new(&options.rom_path).with_extension("sav");
+        let result = match std::fs::read(&path) {
+            Ok(data) => ram
+                .load(&data)
+                .map(|()| println!("Loaded save from {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.to_string()),
+        };
+        if let Err(err) = result {
+            // Don't overwrite a save that might still be good
+            eprintln!("Unable to load save from {}: {}", path.display(), err);
+            return None;
+        }
+        Some(SaveFile { ram, path })
+    }
+
+    fn on_frame(&mut self) {
+        if let Some(data) = self.ram.on_frame() {
+            self.write(&data);
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(data) = self.ram.take_changes() {
+            self.write(&data);
+        }
+    }
+
+    fn write(&self, data: &[u8]) {
+        if let Err(err) = std::fs::write(&self.path, data) {
+            eprintln!("Unable to write save to {}: {}", self.path.display(), err);
+        }
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <filename>", args[0]);
+    let mut options = cli::parse_args(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("{}", cli::usage(&args[0]));
+        std::process::exit(1);
+    });
+
+    let program = std::fs::read(&options.rom_path).unwrap();
+    let rom = Rom::new(&program).unwrap_or_else(|err| {
+        eprintln!("Unable to load {}: {}", options.rom_path, err);
         std::process::exit(1);
+    });
+    options.apply_expansion_device(rom.expansion_device);
+    let region = options.region.unwrap_or(rom.region());
+
+    match &options.wav_output {
+        Some(wav_output) => run_headless(rom, &options, region, wav_output),
+        None => run_with_window(rom, &options, region, load_config()),
     }
+}
 
-    let filename = &args[1];
-    let program = std::fs::read(filename).unwrap();
-    let rom = Rom::new(&program).unwrap();
+fn load_config() -> Config {
+    let Some(path) = Config::path() else {
+        return Config::default();
+    };
+    Config::load(&path).unwrap_or_else(|err| {
+        eprintln!("Invalid config {}: {}", path.display(), err);
+        std::process::exit(1);
+    })
+}
 
-    let (tx_frame, rx_frame): (Sender<Frame>, Receiver<Frame>) = mpsc::channel();
+fn run_with_window(rom: Rom, options: &Options, region: Region, config: Config) {
+    let (tx_frame, rx_frame): (Sender<FrameOutput>, Receiver<FrameOutput>) = mpsc::channel();
     let (tx_joycon, rx_joycon): (Sender<Vec<InputEvent>>, Receiver<Vec<InputEvent>>) =
         mpsc::channel();
 
-    let render_thread = thread::spawn(move || create_render_thread(rx_frame, tx_joycon));
+    let sample_rate = options.sample_rate;
+    let frame_rate = region.frame_rate();
+    let render_thread = thread::spawn(move || {
+        create_render_thread(rx_frame, tx_joycon, sample_rate, frame_rate, config)
+    });
 
-    let ppu = PPU::new(rom.chr_rom.clone(), rom.screen_mirroring);
+    let requests = Rc::new(FrontendRequests::default());
+    let gameloop_requests = requests.clone();
+    let movie = Rc::new(RefCell::new(MovieSession::new(options, &rom, region)));
+    let gameloop_movie = movie.clone();
+
+    let ppu = PPU::new_empty_rom();
     let mut bus = NESBus::new_with_callback(
         ppu,
-        Box::new(move |ppu, joypad| {
+        Box::new(move |bus| {
             let mut frame = Frame::new();
-            render::render(ppu, &mut frame);
-            tx_frame.send(frame).expect("Should send frame");
+            render::render(&bus.ppu, &mut frame);
+            let mut samples = bus.apu.take_samples();
+            if gameloop_requests.rewinding.get() {
+                // Backwards audio is just noise
+                samples.clear();
+            }
+            tx_frame.send((frame, samples)).expect("Should send frame");
 
+            let mut movie = gameloop_movie.borrow_mut();
             for key_event in rx_joycon.recv().expect("Should receive joycon state") {
-                update_joypad_state(joypad, key_event)
+                gameloop_requests.handle_input(&key_event);
+                if !movie.is_playing() {
+                    update_controller_state(bus, key_event)
+                }
             }
+            let commands = movie.next_frame(bus, gameloop_requests.commands.take());
+            gameloop_requests.commands.set(commands);
+            gameloop_requests.frame_ended.set(true);
         }),
     );
-    bus.rom = Some(Box::from(rom));
+    bus.apu.set_sample_rate(options.sample_rate);
+    bus.set_region(region);
+    plug_controllers(&mut bus, options, movie.borrow().fourscore);
+    let rom_hash = rom.hash;
+    let battery = rom.battery;
+    bus.load_rom(rom);
+    let mut save_file = SaveFile::open(&bus, battery, options);
+    movie
+        .borrow_mut()
+        .next_frame(&mut bus, MovieCommand::empty());
+
+    let mut rewind = RewindBuffer::new(REWIND_INTERVAL_FRAMES, REWIND_BUFFER_BYTES);
 
     let mut cpu = CPU::new(Box::from(bus));
     cpu.reset();
-    cpu.run();
+    let power_on = capture_state(&cpu);
+    let mut jam_reported = false;
+    cpu.run_with_callback(move |cpu| {
+        if requests.frame_ended.take() {
+            if let Some(addr) = cpu.jammed().filter(|_| !jam_reported) {
+                eprintln!(
+                    "The CPU jammed on a KIL opcode at {:#06X}, reset to continue",
+                    addr
+                );
+            }
+            jam_reported = cpu.jammed().is_some();
+            if requests.quit.get() {
+                if let Some(save_file) = save_file.as_mut() {
+                    save_file.flush();
+                }
+                std::process::exit(0);
+            }
+            carry_out_commands(cpu, requests.commands.take(), &power_on, &mut save_file);
+            if let Some(save_file) = save_file.as_mut() {
+                save_file.on_frame();
+            }
+            if requests.rewinding.get() {
+                if let Err(err) = rewind.rewind(cpu) {
+                    eprintln!("Unable to rewind: {}", err);
+                }
+            } else {
+                rewind.on_frame(cpu);
+            }
+        }
+
+        match requests.state_action.take() {
+            Some(InputAction::SaveState(slot)) => {
+                let path = save_state_path(&options.rom_path, slot);
+                match std::fs::write(&path, savestate::save_state(cpu, rom_hash)) {
+                    Ok(()) => println!("Saved state to {}", path.display()),
+                    Err(err) => eprintln!("Unable to save state to {}: {}", path.display(), err),
+                }
+            }
+            Some(InputAction::LoadState(slot)) => {
+                let path = save_state_path(&options.rom_path, slot);
+                let result = std::fs::read(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|data| savestate::load_state(cpu, rom_hash, &data));
+                match result {
+                    Ok(()) => println!("Loaded state from {}", path.display()),
+                    Err(err) => eprintln!("Unable to load state from {}: {}", path.display(), err),
+                }
+            }
+            _ => {}
+        }
+    });
 
     render_thread
         .join()
         .expect("Should be able to attach to the render thread");
 }
 
-fn create_render_thread(rx_frame: Receiver<Frame>, tx_joycon: Sender<Vec<InputEvent>>) -> ! {
+/// Records the audio of the first frames to a WAV file, as fast as the machine runs.
+fn run_headless(rom: Rom, options: &Options, region: Region, wav_output: &WavOutput) {
+    let mut wav = Some(WavWriter::create(&wav_output.path, options.sample_rate).unwrap());
+    let mut frames_left = wav_output.frames;
+    let finished = Rc::new(Cell::new(false));
+    let gameloop_finished = finished.clone();
+    let movie = Rc::new(RefCell::new(MovieSession::new(options, &rom, region)));
+    let gameloop_movie = movie.clone();
+    let commands = Rc::new(Cell::new(MovieCommand::empty()));
+    let gameloop_commands = commands.clone();
+
+    let ppu = PPU::new_empty_rom();
+    let mut bus = NESBus::new_with_callback(
+        ppu,
+        Box::new(move |bus| {
+            let samples = bus.apu.take_samples();
+            if let Some(wav) = wav.as_mut() {
+                wav.write_samples(&samples).expect("Should write samples");
+            }
+
+            let next_commands = gameloop_movie
+                .borrow_mut()
+                .next_frame(bus, MovieCommand::empty());
+            gameloop_commands.set(next_commands);
+
+            frames_left = frames_left.saturating_sub(1);
+            if frames_left == 0 {
+                if let Some(wav) = wav.take() {
+                    wav.finalize().expect("Should finalize WAV file");
+                }
+                gameloop_finished.set(true);
+            }
+        }),
+    );
+    bus.apu.set_sample_rate(options.sample_rate);
+    bus.set_region(region);
+    plug_controllers(&mut bus, options, movie.borrow().fourscore);
+    bus.load_rom(rom);
+    movie
+        .borrow_mut()
+        .next_frame(&mut bus, MovieCommand::empty());
+
+    let mut cpu = CPU::new(Box::from(bus));
+    cpu.reset();
+    let power_on = capture_state(&cpu);
+    cpu.run_until(|cpu| {
+        carry_out_commands(cpu, commands.take(), &power_on, &mut None);
+        finished.get()
+    });
+}
+
+/// Both ports have a joypad unless asked for something else.
+fn plug_controllers(bus: &mut NESBus, options: &Options, fourscore: bool) {
+    if fourscore {
+        for port in 0..CONTROLLER_PORTS {
+            bus.plug_controller(port, Box::new(FourScore::new(port)));
+        }
+    } else if options.zapper {
+        bus.plug_controller(1, Box::new(Zapper::new()));
+    }
+}
+
+fn capture_state(cpu: &CPU) -> Vec<u8> {
+    let mut state = StateWriter::new();
+    cpu.save_state(&mut state);
+    state.into_bytes()
+}
+
+/// Resets the console as asked for by the player or the movie. A power cycle goes back to the
+/// state captured right after power on, except for the save the battery kept.
+fn carry_out_commands(
+    cpu: &mut CPU,
+    commands: MovieCommand,
+    power_on: &[u8],
+    save_file: &mut Option<SaveFile>,
+) {
+    if commands.contains(MovieCommand::POWER) {
+        if let Some(save_file) = save_file.as_mut() {
+            save_file.flush();
+        }
+        cpu.load_state(&mut StateReader::new(power_on))
+            .expect("Should restore the power on state");
+        if let Some(save_file) = save_file {
+            save_file.ram.restore();
+        }
+    } else if commands.contains(MovieCommand::SOFT_RESET) {
+        cpu.reset();
+    }
+}
+
+fn create_render_thread(
+    rx_frame: Receiver<FrameOutput>,
+    tx_joycon: Sender<Vec<InputEvent>>,
+    sample_rate: u32,
+    frame_rate: f64,
+    config: Config,
+) -> ! {
     println!("Started render thread");
 
     let sdl_context = sdl2::init().unwrap();
+    let audio_queue = create_audio_queue(&sdl_context, sample_rate);
     let video_subsystem = sdl_context.video().unwrap();
     let window = video_subsystem
         .window(
             "NES Emulator in Rust by acr92",
-            (Frame::WIDTH as f32 * WINDOW_SCALE) as u32,
-            (Frame::HEIGHT as f32 * WINDOW_SCALE) as u32,
+            (Frame::WIDTH as f32 * config.window_scale) as u32,
+            (Frame::HEIGHT as f32 * config.window_scale) as u32,
         )
         .position_centered()
         .build()
         .unwrap();
 
-    let key_map = create_keymap();
+    let key_map = create_keymap(&config).unwrap_or_else(|err| exit_with_config_error(&err));
+    let mut gamepads = Gamepads::new(sdl_context.game_controller().unwrap(), &config)
+        .unwrap_or_else(|err| exit_with_config_error(&err));
 
     let mut canvas = window.into_canvas().present_vsync().build().unwrap();
     let mut event_pump = sdl_context.event_pump().unwrap();
-    canvas.set_scale(WINDOW_SCALE, WINDOW_SCALE).unwrap();
+    canvas
+        .set_scale(config.window_scale, config.window_scale)
+        .unwrap();
 
     let creator = canvas.texture_creator();
     let mut texture = creator
@@ -91,8 +502,10 @@ fn create_render_thread(rx_frame: Receiver<Frame>, tx_joycon: Sender<Vec<InputEv
         )
         .unwrap();
 
+    let mut pacer = FramePacer::new(frame_rate);
     loop {
-        let mut frame = rx_frame.recv().unwrap();
+        let (mut frame, samples) = rx_frame.recv().unwrap();
+        thread::sleep(pacer.delay(Instant::now()));
 
         texture
             .update(None, &frame.data, Frame::WIDTH * Frame::RGB_SIZE)
@@ -101,7 +514,14 @@ fn create_render_thread(rx_frame: Receiver<Frame>, tx_joycon: Sender<Vec<InputEv
         canvas.copy(&texture, None, None).unwrap();
 
         canvas.present();
-        let key_events = process_input(&key_map, &mut event_pump);
+        queue_audio(&audio_queue, &samples, sample_rate);
+
+        let key_events = process_input(
+            &key_map,
+            &mut gamepads,
+            &mut event_pump,
+            config.window_scale,
+        );
 
         for event in &key_events {
             if let InputButton::Key(key) = event.button {
@@ -111,7 +531,7 @@ fn create_render_thread(rx_frame: Receiver<Frame>, tx_joycon: Sender<Vec<InputEv
                 }
 
                 if matches!(key, InputAction::CaptureScreenshot) {
-                    save_screenshot(&mut frame).unwrap();
+                    save_screenshot(&mut frame, &config.screenshot_dir).unwrap();
                 }
             }
         }
@@ -120,9 +540,34 @@ fn create_render_thread(rx_frame: Receiver<Frame>, tx_joycon: Sender<Vec<InputEv
     }
 }
 
+fn create_audio_queue(sdl_context: &sdl2::Sdl, sample_rate: u32) -> AudioQueue<f32> {
+    let audio_subsystem = sdl_context.audio().unwrap();
+    let spec = AudioSpecDesired {
+        freq: Some(sample_rate as i32),
+        channels: Some(1),
+        samples: Some(AUDIO_BUFFER_SAMPLES),
+    };
+
+    let audio_queue = audio_subsystem.open_queue::<f32, _>(None, &spec).unwrap();
+    audio_queue.resume();
+    audio_queue
+}
+
+fn queue_audio(audio_queue: &AudioQueue<f32>, samples: &[f32], sample_rate: u32) {
+    let max_queued_bytes =
+        (sample_rate as f32 * MAX_QUEUED_AUDIO_SECONDS) as u32 * std::mem::size_of::<f32>() as u32;
+    if audio_queue.size() > max_queued_bytes {
+        audio_queue.clear();
+    }
+
+    audio_queue.queue_audio(samples).unwrap();
+}
+
 fn process_input(
     key_map: &HashMap<Keycode, InputButton>,
+    gamepads: &mut Gamepads,
     event_pump: &mut EventPump,
+    window_scale: f32,
 ) -> Vec<InputEvent> {
     let mut key_events: Vec<InputEvent> = vec![];
     for event in event_pump.poll_iter() {
@@ -131,7 +576,7 @@ fn process_input(
             | Event::KeyDown {
                 keycode: Some(Keycode::Escape),
                 ..
-            } => std::process::exit(0),
+            } => key_events.push(InputEvent::pressed(InputButton::Key(InputAction::Quit))),
 
             Event::KeyDown { keycode, .. } => {
                 if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::AcBack)) {
@@ -143,23 +588,81 @@ fn process_input(
                     key_events.push(InputEvent::released(*key))
                 }
             }
-            _ => {}
+
+            Event::MouseMotion { x, y, .. } => {
+                key_events.push(InputEvent::pressed(zapper_aim(x, y, window_scale)));
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                key_events.push(InputEvent::pressed(zapper_aim(x, y, window_scale)));
+                key_events.push(InputEvent::pressed(InputButton::ZapperTrigger));
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => key_events.push(InputEvent::released(InputButton::ZapperTrigger)),
+            Event::Window {
+                win_event: WindowEvent::Leave,
+                ..
+            } => key_events.push(InputEvent::pressed(InputButton::ZapperAim(None))),
+            event => gamepads.handle_event(&event, &mut key_events),
         }
     }
     key_events
 }
 
-fn update_joypad_state(joypad: &mut Joypad, key_event: InputEvent) {
-    if let InputButton::Joypad(joypad_button) = key_event.button {
-        if key_event.key_down {
-            joypad.set_pressed(joypad_button);
-        } else {
-            joypad.set_released(joypad_button);
+/// Mouse coordinates are in window pixels, the picture is scaled up to fill the window.
+fn zapper_aim(x: i32, y: i32, window_scale: f32) -> InputButton {
+    let aim = (x >= 0 && y >= 0).then(|| {
+        (
+            (x as f32 / window_scale) as usize,
+            (y as f32 / window_scale) as usize,
+        )
+    });
+    InputButton::ZapperAim(aim)
+}
+
+fn update_controller_state(bus: &mut NESBus, key_event: InputEvent) {
+    match key_event.button {
+        InputButton::Joypad(player, joypad_button) => {
+            let Some(joypad) = bus.joypad_mut(player as usize) else {
+                return;
+            };
+            if key_event.key_down {
+                joypad.set_pressed(joypad_button);
+            } else {
+                joypad.set_released(joypad_button);
+            }
         }
+        InputButton::ZapperTrigger => {
+            if let Some(zapper) = bus.zapper_mut() {
+                zapper.set_trigger(key_event.key_down);
+            }
+        }
+        InputButton::ZapperAim(aim) => {
+            if let Some(zapper) = bus.zapper_mut() {
+                zapper.aim(aim);
+            }
+        }
+        InputButton::Key(_) => {}
     }
 }
 
-fn save_screenshot(frame: &mut Frame) -> Result<(), String> {
+/// Save states are kept next to the ROM, one file per slot.
+fn save_state_path(rom_path: &str, slot: u8) -> PathBuf {
+    PathBuf::from(format!("{}.state{}", rom_path, slot))
+}
+
+fn exit_with_config_error(err: &str) -> ! {
+    eprintln!("Invalid config: {}", err);
+    std::process::exit(1);
+}
+
+fn save_screenshot(frame: &mut Frame, dir: &Path) -> Result<(), String> {
     Surface::from_data(
         frame.data.as_mut_slice(),
         Frame::WIDTH as u32,
@@ -168,8 +671,48 @@ fn save_screenshot(frame: &mut Frame) -> Result<(), String> {
         PixelFormatEnum::RGB24,
     )
     .unwrap()
-    .save_bmp(Path::new("hello.bmp"))?;
+    .save_bmp(dir.join("hello.bmp"))?;
 
     println!("Saved screenshot");
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A ROM that leaves NMI off and polls $2002 for vblank, like most test ROMs do.
+    fn polling_rom() -> Rom {
+        let mut program = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+        program.resize(16, 0);
+
+        let mut prg_rom = vec![0xEA; 0x4000];
+        // LDA $2002, BPL $8000, JMP $8000
+        prg_rom[..8].copy_from_slice(&[0xAD, 0x02, 0x20, 0x10, 0xFB, 0x4C, 0x00, 0x80]);
+        prg_rom[0x3FFC..0x3FFE].copy_from_slice(&[0x00, 0x80]);
+        program.extend(prg_rom);
+        program.extend(vec![0; 0x2000]);
+        Rom::new(&program).unwrap()
+    }
+
+    #[test]
+    fn test_headless_run_ends_without_nmi() {
+        let path = std::env::temp_dir().join("nes_emulator_headless_test.wav");
+        let path = path.to_str().unwrap();
+        let args = ["nes", "polling.nes", "--wav", path, "--frames", "5"].map(String::from);
+        let options = cli::parse_args(&args).unwrap();
+
+        run_headless(
+            polling_rom(),
+            &options,
+            Region::Ntsc,
+            options.wav_output.as_ref().unwrap(),
+        );
+
+        let wav = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let samples = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]) / 2;
+        let samples_per_frame = options.sample_rate / 60;
+        assert!(samples > 4 * samples_per_frame && samples < 6 * samples_per_frame);
+    }
+}
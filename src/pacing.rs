@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+// Further behind than this, the emulator gives up on catching up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Shows frames at the rate of the console rather than that of the display, 50 a second for PAL
+/// games even on a 60 Hz or 144 Hz monitor.
+pub struct FramePacer {
+    frame_duration: Duration,
+    next_frame: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: f64) -> Self {
+        FramePacer {
+            frame_duration: Duration::from_secs_f64(1.0 / frame_rate),
+            next_frame: None,
+        }
+    }
+
+    /// How long to wait before showing the next frame. The deadlines follow each other at the
+    /// frame rate, so the time spent emulating the frame doesn't add up.
+    pub fn delay(&mut self, now: Instant) -> Duration {
+        let next_frame = match self.next_frame {
+            Some(next_frame) if now.saturating_duration_since(next_frame) <= MAX_LAG => next_frame,
+            _ => now,
+        };
+        self.next_frame = Some(next_frame + self.frame_duration);
+        next_frame.saturating_duration_since(now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal;
+
+    #[test]
+    fn test_deadlines_keep_the_frame_rate() {
+        let mut pacer = FramePacer::new(50.0);
+        let start = Instant::now();
+        assert_equal!(pacer.delay(start), Duration::ZERO);
+
+        // The frame took 5 ms to emulate
+        let now = start + Duration::from_millis(5);
+        assert_equal!(pacer.delay(now), Duration::from_millis(15));
+
+        let now = start + Duration::from_millis(45);
+        assert_equal!(pacer.delay(now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_falling_far_behind_starts_over() {
+        let mut pacer = FramePacer::new(50.0);
+        let start = Instant::now();
+        pacer.delay(start);
+
+        let now = start + Duration::from_secs(1);
+        assert_equal!(pacer.delay(now), Duration::ZERO);
+        let now = now + Duration::from_millis(1);
+        assert_equal!(pacer.delay(now), Duration::from_millis(19));
+    }
+}
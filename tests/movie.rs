@@ -0,0 +1,123 @@
+use core::snapshot::{Snapshot, StateWriter};
+use cpu6502::cpu::CPU;
+use emulator::bus::NESBus;
+use emulator::cartridge::Rom;
+use emulator::joypad::JoypadButton;
+use emulator::movie::{Movie, MovieCommand, MovieFrame, MoviePlayer};
+use ppu::PPU;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+mod common;
+
+/// Plays the movie on nestest from power on, and returns the state of the machine at the end of
+/// the last frame.
+fn play(movie: &Movie) -> Vec<u8> {
+    let program = std::fs::read(test_file!("nestest.nes")).unwrap();
+    let rom = Rom::new(&program).unwrap();
+
+    let player = Rc::new(RefCell::new(MoviePlayer::new(movie.clone())));
+    let commands = Rc::new(Cell::new(None));
+
+    let gameloop_player = player.clone();
+    let gameloop_commands = commands.clone();
+    let mut bus = NESBus::new_with_callback(
+        PPU::new_empty_rom(),
+        Box::new(move |bus| {
+            let next = gameloop_player.borrow_mut().play_frame(bus);
+            gameloop_commands.set(Some(next));
+        }),
+    );
+    bus.load_rom(rom);
+    player.borrow_mut().play_frame(&mut bus);
+
+    let mut cpu = CPU::new(Box::from(bus));
+    cpu.reset();
+
+    let mut state = StateWriter::new();
+    cpu.run_until(|cpu| match commands.take() {
+        Some(Some(commands)) => {
+            if commands.contains(MovieCommand::SOFT_RESET) {
+                cpu.reset();
+            }
+            false
+        }
+        Some(None) => {
+            // The movie is over
+            cpu.save_state(&mut state);
+            true
+        }
+        None => false,
+    });
+    state.into_bytes()
+}
+
+fn movie_with(frames: &[(MovieCommand, JoypadButton)]) -> Movie {
+    let program = std::fs::read(test_file!("nestest.nes")).unwrap();
+    let mut movie = Movie::new("nestest", &Rom::new(&program).unwrap());
+    movie.frames = frames
+        .iter()
+        .map(|&(commands, buttons)| {
+            let mut frame = MovieFrame::new(commands);
+            frame.joypads[0] = buttons;
+            frame
+        })
+        .collect();
+    movie
+}
+
+#[test]
+fn test_movie_playback_is_deterministic() {
+    let mut frames = vec![(MovieCommand::empty(), JoypadButton::empty()); 60];
+    frames[20].1 = JoypadButton::DOWN;
+    frames[30].1 = JoypadButton::START;
+    frames[40].0 = MovieCommand::SOFT_RESET;
+    let movie = Movie::parse(&movie_with(&frames).to_fm2()).unwrap();
+
+    let first = play(&movie);
+    assert_eq!(play(&movie), first);
+
+    let idle = movie_with(&[(MovieCommand::empty(), JoypadButton::empty()); 60]);
+    assert_ne!(play(&idle), first);
+}
+
+/// A ROM that leaves NMI off and polls $2002 for vblank, like many test ROMs do.
+fn polling_rom() -> Rom {
+    let mut program = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    program.resize(16, 0);
+
+    let mut prg_rom = vec![0xEA; 0x4000];
+    // LDA $2002, BPL $8000, JMP $8000
+    prg_rom[..8].copy_from_slice(&[0xAD, 0x02, 0x20, 0x10, 0xFB, 0x4C, 0x00, 0x80]);
+    prg_rom[0x3FFC..0x3FFE].copy_from_slice(&[0x00, 0x80]);
+    program.extend(prg_rom);
+    program.extend(vec![0; 0x2000]);
+    Rom::new(&program).unwrap()
+}
+
+#[test]
+fn test_movie_advances_on_frames_without_nmi() {
+    let rom = polling_rom();
+    let mut movie = Movie::new("polling", &rom);
+    movie.frames = vec![MovieFrame::new(MovieCommand::empty()); 10];
+
+    let player = Rc::new(RefCell::new(MoviePlayer::new(movie)));
+    let gameloop_player = player.clone();
+    let mut bus = NESBus::new_with_callback(
+        PPU::new_empty_rom(),
+        Box::new(move |bus| {
+            gameloop_player.borrow_mut().play_frame(bus);
+        }),
+    );
+    bus.load_rom(rom);
+    player.borrow_mut().play_frame(&mut bus);
+
+    let mut cpu = CPU::new(Box::from(bus));
+    cpu.reset();
+    for _ in 0..8 {
+        cpu.run_until_frame();
+    }
+    assert!(!player.borrow().is_finished());
+    cpu.run_until_frame();
+    assert!(player.borrow().is_finished());
+}
@@ -12,7 +12,7 @@ fn test_nestest() {
     let expected: Vec<_> = std::fs::read_to_string(test_file!("nestest.log"))
         .unwrap()
         .split("\r\n")
-        .map(|a| String::from(a))
+        .map(String::from)
         .collect();
 
     let program = std::fs::read(test_file!("nestest.nes")).unwrap();
@@ -20,7 +20,7 @@ fn test_nestest() {
 
     let ppu = PPU::new_empty_rom();
     let mut bus = NESBus::new(ppu);
-    bus.rom = Some(Box::from(rom));
+    bus.load_rom(rom);
     bus.cycles = 7;
     bus.ppu.cycles = 21;
     bus.ppu.scanline = 0;
@@ -30,20 +30,21 @@ fn test_nestest() {
     cpu.register.pc = 0xC000;
 
     let mut index = 0;
-    cpu.run_with_callback(|cpu| {
-        let actual = trace(cpu);
-
-        // Skip the last instruction, it's just another RTS. We didn't start the program from the
-        // same instruction that the nestest.log is from.
+    cpu.run_until(|cpu| {
+        // Stop before the last instruction, it's just another RTS. We didn't start the program
+        // from the same instruction that the nestest.log is from.
         if index == expected.len() - 1 {
-            return;
+            return true;
         }
 
+        let actual = trace(cpu);
+
         if expected[index] != actual {
             dbg!(&cpu.register);
         }
 
         assert_equal!(expected[index], actual);
-        index = index + 1;
+        index += 1;
+        false
     });
 }